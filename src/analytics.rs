@@ -0,0 +1,442 @@
+use std::collections::BTreeMap;
+
+use mongodb::bson::{doc, Bson, Document};
+
+use crate::{RatingScale, RATING_SCALES};
+
+/// How section ratings on one scale are distributed in one rubric section.
+#[derive(Debug)]
+pub struct RatingDistribution {
+    pub section: String,
+    /// `None` for the count of sections that are not rated at all
+    pub scale: Option<RatingScale>,
+    /// Number of sections rated with each value
+    pub histogram: BTreeMap<u8, u64>,
+    /// Number of sections rated n/a
+    pub not_applicable: u64,
+    /// Number of sections not rated
+    pub unrated: u64,
+}
+
+impl RatingDistribution {
+    pub fn rated(&self) -> u64 {
+        self.histogram.values().sum()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        let rated = self.rated();
+        let total: u64 = self
+            .histogram
+            .iter()
+            .map(|(value, count)| *value as u64 * count)
+            .sum();
+        (rated > 0).then(|| total as f64 / rated as f64)
+    }
+
+    pub fn median(&self) -> Option<f64> {
+        let rated = self.rated();
+        if rated == 0 {
+            return None;
+        }
+
+        // The values at the two middle positions, counted from 0
+        let nth = |n: u64| {
+            let mut seen = 0;
+            for (value, count) in &self.histogram {
+                seen += count;
+                if seen > n {
+                    return *value as f64;
+                }
+            }
+            0.0
+        };
+        Some((nth((rated - 1) / 2) + nth(rated / 2)) / 2.0)
+    }
+}
+
+/// How often a tag is used.
+#[derive(Debug)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: u64,
+}
+
+/// The most frequent tags of a rubric section.
+#[derive(Debug)]
+pub struct SectionTags {
+    pub section: String,
+    pub tags: Vec<TagCount>,
+}
+
+/// Two tags and the number of sections carrying both.
+#[derive(Debug)]
+pub struct TagPair {
+    pub first: String,
+    pub second: String,
+    pub count: u64,
+}
+
+/// The DMPs of one funder or institution.
+#[derive(Debug)]
+pub struct Breakdown {
+    /// `None` for DMPs without a funder or institution
+    pub name: Option<String>,
+    pub dmps: u64,
+    pub mean_score: Option<f64>,
+    /// Mean section rating as a fraction of its scale (see `Rating::fraction`)
+    pub mean_rating: Option<f64>,
+}
+
+/// Summary statistics of the ratings and tags of a set of DMPs.
+#[derive(Debug, Default)]
+pub struct Analytics {
+    pub dmps: u64,
+    pub ratings: Vec<RatingDistribution>,
+    pub section_tags: Vec<SectionTags>,
+    pub co_occurrence: Vec<TagPair>,
+    pub by_funder: Vec<Breakdown>,
+    pub by_institution: Vec<Breakdown>,
+}
+
+/// Reads a number stored as any BSON numeric type.
+pub fn number(doc: &Document, key: &str) -> Option<f64> {
+    match doc.get(key) {
+        Some(Bson::Int32(n)) => Some(*n as f64),
+        Some(Bson::Int64(n)) => Some(*n as f64),
+        Some(Bson::Double(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+pub fn count(doc: &Document, key: &str) -> u64 {
+    number(doc, key).unwrap_or(0.0) as u64
+}
+
+/// Expression computing the section rating at `rating` as a fraction of its
+/// scale, or null if it is not rated or n/a.
+pub fn rating_fraction(rating: &str) -> Document {
+    let branches: Vec<Document> = RATING_SCALES
+        .iter()
+        .map(|scale| {
+            let (min, max) = scale.bounds();
+            doc! {
+                "case": { "$eq": [format!("{}.scale", rating), scale.as_str()] },
+                "then": {
+                    "$divide": [
+                        { "$subtract": [format!("{}.value", rating), min as i32] },
+                        (max - min) as i32
+                    ]
+                }
+            }
+        })
+        .collect();
+
+    doc! { "$switch": { "branches": branches, "default": Bson::Null } }
+}
+
+/// Counts the sections of the DMPs matching `filter` by title, scale and
+/// value. The scale is missing from the group of unrated sections, and the
+/// value from that of sections rated n/a.
+pub fn rating_pipeline(filter: Document) -> Vec<Document> {
+    vec![
+        doc! { "$match": filter },
+        doc! { "$unwind": "$sections" },
+        doc! {
+            "$group": {
+                "_id": {
+                    "section": "$sections.title",
+                    "scale": "$sections.rating.scale",
+                    "value": "$sections.rating.value"
+                },
+                "count": { "$sum": 1 }
+            }
+        },
+        doc! { "$sort": { "_id.section": 1, "_id.scale": 1, "_id.value": 1 } },
+    ]
+}
+
+/// The `top` most frequent tags of each section of the DMPs matching
+/// `filter`.
+pub fn section_tags_pipeline(filter: Document, top: usize) -> Vec<Document> {
+    vec![
+        doc! { "$match": filter },
+        doc! { "$unwind": "$sections" },
+        doc! { "$unwind": "$sections.tags" },
+        doc! {
+            "$group": {
+                "_id": { "section": "$sections.title", "tag": "$sections.tags" },
+                "count": { "$sum": 1 }
+            }
+        },
+        doc! { "$sort": { "count": -1, "_id.tag": 1 } },
+        doc! {
+            "$group": {
+                "_id": "$_id.section",
+                "tags": { "$push": { "tag": "$_id.tag", "count": "$count" } }
+            }
+        },
+        doc! { "$project": { "tags": { "$slice": ["$tags", top as i64] } } },
+        doc! { "$sort": { "_id": 1 } },
+    ]
+}
+
+/// The `top` pairs of tags most often found on the same section of the DMPs
+/// matching `filter`.
+pub fn co_occurrence_pipeline(filter: Document, top: usize) -> Vec<Document> {
+    vec![
+        doc! { "$match": filter },
+        doc! { "$unwind": "$sections" },
+        doc! { "$project": { "first": "$sections.tags", "second": "$sections.tags" } },
+        doc! { "$unwind": "$first" },
+        doc! { "$unwind": "$second" },
+        // Count each pair once, in alphabetical order
+        doc! { "$match": { "$expr": { "$lt": ["$first", "$second"] } } },
+        doc! {
+            "$group": {
+                "_id": { "first": "$first", "second": "$second" },
+                "count": { "$sum": 1 }
+            }
+        },
+        doc! { "$sort": { "count": -1, "_id.first": 1, "_id.second": 1 } },
+        doc! { "$limit": top as i64 },
+    ]
+}
+
+/// Groups the DMPs matching `filter` by `field` of their project, e.g.
+/// `funder`, with their number, mean overall score and mean section rating.
+pub fn breakdown_pipeline(filter: Document, field: &str) -> Vec<Document> {
+    let key = format!("$project_info.{}", field);
+
+    vec![
+        doc! { "$match": filter },
+        doc! { "$unwind": { "path": "$sections", "preserveNullAndEmptyArrays": true } },
+        // The mean section rating of each DMP first, so that DMPs count
+        // equally whatever their number of sections
+        doc! {
+            "$group": {
+                "_id": { "key": &key, "dmp": "$_id" },
+                "name": { "$first": &key },
+                "score": { "$first": "$overall_rating.score" },
+                "rating": { "$avg": rating_fraction("$sections.rating") }
+            }
+        },
+        doc! {
+            "$group": {
+                "_id": "$_id.key",
+                "name": { "$first": "$name" },
+                "dmps": { "$sum": 1 },
+                "score": { "$avg": "$score" },
+                "rating": { "$avg": "$rating" }
+            }
+        },
+        doc! { "$sort": { "dmps": -1, "name": 1 } },
+    ]
+}
+
+/// Reads the results of `rating_pipeline`, one distribution per section and
+/// scale.
+pub fn rating_distributions(results: &[Document]) -> Vec<RatingDistribution> {
+    let mut distributions: Vec<RatingDistribution> = Vec::new();
+
+    for result in results {
+        let id = match result.get_document("_id") {
+            Ok(id) => id,
+            Err(_) => continue,
+        };
+        let section = id.get_str("section").unwrap_or("(untitled)").to_string();
+        let scale = id
+            .get_str("scale")
+            .ok()
+            .and_then(|s| s.parse::<RatingScale>().ok());
+        let value = number(id, "value").map(|v| v as u8);
+        let count = count(result, "count");
+
+        let distribution = match distributions
+            .iter_mut()
+            .find(|d| d.section == section && d.scale == scale)
+        {
+            Some(distribution) => distribution,
+            None => {
+                distributions.push(RatingDistribution {
+                    section,
+                    scale,
+                    histogram: BTreeMap::new(),
+                    not_applicable: 0,
+                    unrated: 0,
+                });
+                distributions.last_mut().expect("just pushed")
+            }
+        };
+        match (scale, value) {
+            (None, _) => distribution.unrated += count,
+            (Some(_), None) => distribution.not_applicable += count,
+            (Some(_), Some(value)) => *distribution.histogram.entry(value).or_insert(0) += count,
+        }
+    }
+
+    distributions
+}
+
+/// Reads the results of `section_tags_pipeline`.
+pub fn section_tags(results: &[Document]) -> Vec<SectionTags> {
+    results
+        .iter()
+        .map(|result| SectionTags {
+            section: result.get_str("_id").unwrap_or("(untitled)").to_string(),
+            tags: result
+                .get_array("tags")
+                .into_iter()
+                .flatten()
+                .filter_map(Bson::as_document)
+                .map(|tag| TagCount {
+                    tag: tag.get_str("tag").unwrap_or_default().to_string(),
+                    count: count(tag, "count"),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Reads the results of `co_occurrence_pipeline`.
+pub fn tag_pairs(results: &[Document]) -> Vec<TagPair> {
+    results
+        .iter()
+        .filter_map(|result| {
+            let id = result.get_document("_id").ok()?;
+            Some(TagPair {
+                first: id.get_str("first").ok()?.to_string(),
+                second: id.get_str("second").ok()?.to_string(),
+                count: count(result, "count"),
+            })
+        })
+        .collect()
+}
+
+/// Reads the results of `breakdown_pipeline`.
+pub fn breakdowns(results: &[Document]) -> Vec<Breakdown> {
+    results
+        .iter()
+        .map(|result| Breakdown {
+            name: result
+                .get_str("name")
+                .ok()
+                .filter(|n| !n.trim().is_empty())
+                .map(str::to_string),
+            dmps: count(result, "dmps"),
+            mean_score: number(result, "score"),
+            mean_rating: number(result, "rating"),
+        })
+        .collect()
+}
+
+/// A bar of `value` out of `max`, at most `width` characters long and at
+/// least one if `value` is above zero.
+pub fn bar(value: f64, max: f64, width: usize) -> String {
+    if max <= 0.0 {
+        return String::new();
+    }
+    let length = (value / max * width as f64).round() as usize;
+    "#".repeat(length.max(usize::from(value > 0.0)))
+}
+
+fn show_mean(mean: Option<f64>, decimals: usize) -> String {
+    mean.map_or("-".to_string(), |m| format!("{:.*}", decimals, m))
+}
+
+fn show_percent(fraction: Option<f64>) -> String {
+    fraction.map_or("-".to_string(), |f| format!("{:.0}%", f * 100.0))
+}
+
+impl Analytics {
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Analytics of {} DMP(s)\n", self.dmps);
+
+        text.push_str("\nSection ratings\n");
+        for distribution in &self.ratings {
+            let scale = match distribution.scale {
+                Some(scale) => scale,
+                None => continue,
+            };
+            let (min, max) = scale.bounds();
+            let fraction = distribution
+                .mean()
+                .map(|mean| (mean - min as f64) / (max - min) as f64);
+            text.push_str(&format!(
+                "{} ({}): {} rated, {} n/a; mean {} ({} of the scale), median {}\n",
+                distribution.section,
+                scale,
+                distribution.rated(),
+                distribution.not_applicable,
+                show_mean(distribution.mean(), 2),
+                show_percent(fraction),
+                distribution
+                    .median()
+                    .map_or("-".to_string(), |m| m.to_string())
+            ));
+
+            let most = distribution.histogram.values().copied().max().unwrap_or(0);
+            for value in min..=max {
+                let count = distribution.histogram.get(&value).copied().unwrap_or(0);
+                let line = format!(
+                    "   {:>5} {:>4} {}",
+                    scale.label(value),
+                    count,
+                    bar(count as f64, most as f64, 40)
+                );
+                text.push_str(line.trim_end());
+                text.push('\n');
+            }
+        }
+        let unrated: Vec<String> = self
+            .ratings
+            .iter()
+            .filter(|d| d.unrated > 0)
+            .map(|d| format!("{} ({})", d.section, d.unrated))
+            .collect();
+        if !unrated.is_empty() {
+            text.push_str(&format!("Not rated: {}\n", unrated.join(", ")));
+        }
+
+        text.push_str("\nMost frequent tags\n");
+        for section in &self.section_tags {
+            let tags: Vec<String> = section
+                .tags
+                .iter()
+                .map(|t| format!("{} ({})", t.tag, t.count))
+                .collect();
+            text.push_str(&format!("{}: {}\n", section.section, tags.join(", ")));
+        }
+
+        text.push_str("\nTags used together on a section\n");
+        for pair in &self.co_occurrence {
+            text.push_str(&format!(
+                "{} + {}: {}\n",
+                pair.first, pair.second, pair.count
+            ));
+        }
+
+        for (title, breakdowns) in [
+            ("funder", &self.by_funder),
+            ("institution", &self.by_institution),
+        ] {
+            text.push_str(&format!(
+                "\nBy {}: DMPs, mean overall score, mean section rating\n",
+                title
+            ));
+            for breakdown in breakdowns {
+                text.push_str(&format!(
+                    "{}: {}, {}, {}\n",
+                    breakdown
+                        .name
+                        .as_deref()
+                        .map_or(format!("(no {})", title), str::to_string),
+                    breakdown.dmps,
+                    show_mean(breakdown.mean_score, 2),
+                    show_percent(breakdown.mean_rating)
+                ));
+            }
+        }
+
+        text
+    }
+}
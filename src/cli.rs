@@ -1,21 +1,268 @@
-use futures_util::stream::TryStreamExt;
 use mongodb::bson::DateTime;
 use std::io::{self, Write};
 use std::sync::Arc;
 
+use crate::conflict::{merge_view, section_merge_fields, ConflictError};
+use crate::migrations::SCHEMA_VERSION;
+use crate::update::DmpUpdate;
 use crate::{
-    db::DatabaseManager, Author, AuthorIdType, DataManagementPlan, Identifier, MachineActionable,
-    Metrics, OverallRating, ProjectInfo, RelatedDMP, RelatedDataset, RelatedPublication,
-    RelatedSoftware, Section, SectionCrossReference, Subsection, VersionHistory,
+    db::DatabaseManager,
+    identifiers::{normalize_author_identifier, normalize_identifier},
+    matching::{fold, normalize_tag, parse_tags, resolve_section_title, MatchMode},
+    query::{end_of_day, parse_date, DmpQuery, SectionPath},
+    query_parser::parse_query,
+    related::{
+        build_related_entity, related_entities, RelatedEntity, RelatedEntityFields, CLEAR_VALUE,
+        ENTITY_TYPES,
+    },
+    report::write_dmp_report,
+    scales::RubricScales,
+    vocabulary::TagVocabulary,
+    Annotation, AnnotationAnchor, Author, AuthorIdType, DataManagementPlan, Identifier,
+    IdentifierType, OverallRating, ProjectInfo, Rating, RatingScale, ReferenceType, RelationType,
+    SavedSearch, Section, SectionCrossReference, Subsection, VocabularyTag, RUBRIC_SECTIONS,
 };
 
+/// Reads lines until an empty line and joins them, returning `None` if the
+/// first line is empty.
+fn read_paragraphs() -> io::Result<Option<String>> {
+    let mut lines = Vec::new();
+
+    loop {
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        lines.push(line.trim_end().to_string());
+    }
+
+    if lines.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(lines.join("\n")))
+    }
+}
+
+/// Asks for a field value, showing `current` in brackets if set. Returns
+/// `None` if nothing was entered.
+fn prompt_field(label: &str, current: Option<String>) -> io::Result<Option<String>> {
+    match current {
+        Some(current) => print!("{} [{}]: ", label, current),
+        None => print!("{}: ", label),
+    }
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    let input = input.trim();
+    Ok((!input.is_empty()).then(|| input.to_string()))
+}
+
+/// Asks for a rating on `scale` until a valid one or `n/a` is entered.
+/// Nothing entered returns `None` if `empty` describes what that means,
+/// e.g. "not rated", and asks again otherwise.
+fn prompt_rating(
+    label: &str,
+    scale: RatingScale,
+    empty: Option<&str>,
+) -> io::Result<Option<Rating>> {
+    loop {
+        match empty {
+            Some(empty) => print!(
+                "{} ({} or n/a; leave empty if {}): ",
+                label,
+                scale.hint(),
+                empty
+            ),
+            None => print!("{} ({} or n/a): ", label, scale.hint()),
+        }
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "No rating entered",
+            ));
+        }
+
+        if input.trim().is_empty() {
+            if empty.is_some() {
+                return Ok(None);
+            }
+            println!("A rating is required");
+            continue;
+        }
+        match scale.parse_rating(&input) {
+            Ok(rating) => return Ok(Some(rating)),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+/// Asks for a rating on `scale` to search from or up to until a valid one is
+/// entered, or nothing if `optional`.
+fn prompt_rating_bound(
+    label: &str,
+    scale: RatingScale,
+    optional: bool,
+) -> io::Result<Option<Rating>> {
+    loop {
+        if optional {
+            print!("{} ({}, leave empty for any): ", label, scale.hint());
+        } else {
+            print!("{} ({}): ", label, scale.hint());
+        }
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "No rating entered",
+            ));
+        }
+
+        if input.trim().is_empty() {
+            if optional {
+                return Ok(None);
+            }
+            println!("A rating is required");
+            continue;
+        }
+        match scale.parse_rating(&input) {
+            Ok(rating) if rating.value().is_none() => {
+                println!("Sections that do not apply have no rating to search by")
+            }
+            Ok(rating) => return Ok(Some(rating)),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+/// Asks for a `YYYY-MM-DD` date until a valid one or nothing is entered.
+/// With `inclusive_end`, the date stands for the end of that day, so that a
+/// range up to it includes the whole day.
+fn prompt_date(label: &str, inclusive_end: bool) -> io::Result<Option<DateTime>> {
+    loop {
+        print!("{} (YYYY-MM-DD): ", label);
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
+
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        match parse_date(input) {
+            Some(date) if inclusive_end => return Ok(Some(end_of_day(date))),
+            Some(date) => return Ok(Some(date)),
+            None => println!("'{}' is not a valid date; expected YYYY-MM-DD", input),
+        }
+    }
+}
+
+/// Asks for an overall score from 1 to 5 until a valid one is entered.
+/// Nothing entered returns `None` if `empty` describes what that means and
+/// asks again otherwise.
+fn prompt_score(label: &str, empty: Option<&str>) -> io::Result<Option<f32>> {
+    loop {
+        match empty {
+            Some(empty) => print!("{} (1-5, leave empty {}): ", label, empty),
+            None => print!("{} (1-5): ", label),
+        }
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "No score entered",
+            ));
+        }
+
+        match input.trim() {
+            "" if empty.is_some() => return Ok(None),
+            "" => println!("A score is required"),
+            input => match input.parse::<f32>() {
+                Ok(score) if (1.0..=5.0).contains(&score) => return Ok(Some(score)),
+                _ => println!(
+                    "'{}' is not a valid score; expected a number from 1 to 5",
+                    input
+                ),
+            },
+        }
+    }
+}
+
+/// Asks for comma-separated tags until every one is in `vocabulary` and may
+/// be used in `section` (any section if `None`), replacing synonyms with
+/// their tags. Entering a tag ending in `?` lists the vocabulary tags it
+/// starts and asks again.
+fn prompt_tags(
+    label: &str,
+    vocabulary: &TagVocabulary,
+    section: Option<&str>,
+) -> io::Result<Vec<String>> {
+    loop {
+        if vocabulary.is_empty() {
+            print!("{} (comma-separated): ", label);
+        } else {
+            print!(
+                "{} (comma-separated; end a tag with ? to list tags): ",
+                label
+            );
+        }
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(Vec::new());
+        }
+        let entries: Vec<&str> = input
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .collect();
+
+        let prefixes: Vec<&str> = entries.iter().filter_map(|e| e.strip_suffix('?')).collect();
+        if !prefixes.is_empty() {
+            for prefix in prefixes {
+                let completions = vocabulary.complete(prefix, section);
+                if completions.is_empty() {
+                    println!("No tags start with '{}'", prefix.trim());
+                } else {
+                    println!("   {}", completions.join(", "));
+                }
+            }
+            continue;
+        }
+
+        let mut tags: Vec<String> = Vec::new();
+        let mut valid = true;
+        for entry in entries {
+            match vocabulary.resolve(entry, section) {
+                Ok(tag) if !tags.contains(&tag) => tags.push(tag),
+                Ok(_) => {}
+                Err(e) => {
+                    println!("{}", e);
+                    valid = false;
+                }
+            }
+        }
+        if valid {
+            return Ok(tags);
+        }
+    }
+}
+
 pub struct DmpCli {
     db_manager: Arc<DatabaseManager>,
+    scales: RubricScales,
 }
 
 impl DmpCli {
-    pub fn new(db_manager: Arc<DatabaseManager>) -> Self {
-        Self { db_manager }
+    pub fn new(db_manager: Arc<DatabaseManager>, scales: RubricScales) -> Self {
+        Self { db_manager, scales }
     }
 
     pub async fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -29,9 +276,22 @@ impl DmpCli {
             println!("3. Search DMPs by section rating");
             println!("4. Search DMPs by related entity");
             println!("5. Update DMP section rating");
-            println!("6. Return to main menu");
-
-            print!("Choose an option (1-6): ");
+            println!("6. Advanced search (multiple criteria)");
+            println!("7. Full-text search in titles, section text and comments");
+            println!("8. Search with a query expression");
+            println!("9. Saved searches");
+            println!("10. View DMP details");
+            println!("11. Annotate a passage of section text");
+            println!("12. Export DMP report as HTML");
+            println!("13. Add a section cross-reference");
+            println!("14. Manage related datasets, publications, software and DMPs");
+            println!("15. Explore the relationship graph of a DMP or research output");
+            println!("16. Update several ratings of a DMP at once");
+            println!("17. Manage the tag vocabulary");
+            println!("18. Rating and tag analytics");
+            println!("19. Return to main menu");
+
+            print!("Choose an option (1-19): ");
             io::stdout().flush()?;
 
             let mut input = String::new();
@@ -43,7 +303,20 @@ impl DmpCli {
                 "3" => self.search_by_rating().await?,
                 "4" => self.search_by_related_entity().await?,
                 "5" => self.update_rating().await?,
-                "6" => {
+                "6" => self.advanced_search().await?,
+                "7" => self.search_text().await?,
+                "8" => self.search_by_expression().await?,
+                "9" => self.manage_saved_searches().await?,
+                "10" => self.view_dmp().await?,
+                "11" => self.annotate_section().await?,
+                "12" => self.export_report().await?,
+                "13" => self.add_cross_reference().await?,
+                "14" => self.manage_related_entities().await?,
+                "15" => self.explore_graph().await?,
+                "16" => self.update_ratings().await?,
+                "17" => self.manage_tag_vocabulary().await?,
+                "18" => self.show_analytics().await?,
+                "19" => {
                     println!("Returning to main menu...");
                     break;
                 }
@@ -56,6 +329,7 @@ impl DmpCli {
 
     async fn add_dmp(&self) -> Result<(), Box<dyn std::error::Error>> {
         println!("\nAdding a new DMP rating");
+        let vocabulary = self.db_manager.load_tag_vocabulary().await?;
 
         print!("DMP title: ");
         io::stdout().flush()?;
@@ -72,10 +346,16 @@ impl DmpCli {
         let mut id_type = String::new();
         io::stdin().read_line(&mut id_type)?;
 
-        // Create the DMP ID
-        let dmp_id = Identifier {
-            identifier: identifier.trim().to_string(),
-            id_type: id_type.trim().to_string(),
+        // Create the DMP ID in its canonical form
+        let dmp_id = match normalize_identifier(&identifier, &IdentifierType::from(id_type)) {
+            Ok((id_type, identifier)) => Identifier {
+                identifier,
+                id_type,
+            },
+            Err(e) => {
+                println!("{}", e);
+                return Ok(());
+            }
         };
 
         // Author information
@@ -110,25 +390,35 @@ impl DmpCli {
             let mut email = String::new();
             io::stdin().read_line(&mut email)?;
 
+            let id_type = if id_type.trim().is_empty() {
+                AuthorIdType::Other // Default to Other if empty
+            } else {
+                match id_type.trim().parse::<AuthorIdType>() {
+                    Ok(parsed_id_type) => parsed_id_type,
+                    Err(_) => {
+                        println!("Invalid ID type, using 'other'");
+                        AuthorIdType::Other
+                    }
+                }
+            };
+
+            // ORCID iDs and ISNIs are checked before the author is added
+            let identifier = if identifier.trim().is_empty() {
+                None
+            } else {
+                match normalize_author_identifier(&identifier, Some(&id_type)) {
+                    Ok(identifier) => Some(identifier),
+                    Err(e) => {
+                        println!("{}. Please enter the author again.", e);
+                        continue;
+                    }
+                }
+            };
+
             authors.push(Author {
                 name: name.trim().to_string(),
-                identifier: if identifier.trim().is_empty() {
-                    None
-                } else {
-                    Some(identifier.trim().to_string())
-                },
-
-                id_type: if id_type.trim().is_empty() {
-                    Some(AuthorIdType::Other) // Default to Other if empty
-                } else {
-                    match id_type.trim().parse::<AuthorIdType>() {
-                        Ok(parsed_id_type) => Some(parsed_id_type),
-                        Err(_) => {
-                            println!("Invalid ID type, using 'other'");
-                            Some(AuthorIdType::Other)
-                        }
-                    }
-                },
+                identifier,
+                id_type: Some(id_type),
                 affiliation: if affiliation.trim().is_empty() {
                     None
                 } else {
@@ -195,34 +485,17 @@ impl DmpCli {
 
         // DMP Sections
         let mut sections = Vec::new();
-        let section_titles = [
-            "Data Description & Collection",
-            "Documentation & Metadata",
-            "Ethical & Legal Compliance",
-            "Storage & Backup During the Project",
-            "Data Sharing & Long-Term Preservation",
-            "Responsibilities & Resources",
-            "FAIR Principles",
-        ];
-
-        for section_title in &section_titles {
+
+        for section_title in &RUBRIC_SECTIONS {
             println!("\nRating section: {}", section_title);
 
-            print!("Rating (1-5): ");
-            io::stdout().flush()?;
-            let mut rating_input = String::new();
-            io::stdin().read_line(&mut rating_input)?;
-            let rating = rating_input.trim().parse::<u8>().ok();
+            println!("Section text from the plan (optional, end with an empty line):");
+            let text = read_paragraphs()?;
 
-            print!("Tags (comma-separated): ");
-            io::stdout().flush()?;
-            let mut tags_input = String::new();
-            io::stdin().read_line(&mut tags_input)?;
-            let tags = tags_input
-                .trim()
-                .split(',')
-                .map(|s| s.trim().to_string())
-                .collect::<Vec<String>>();
+            let scale = self.scales.scale_for(section_title);
+            let rating = prompt_rating("Rating", scale, Some("not rated"))?;
+
+            let tags = prompt_tags("Tags", &vocabulary, Some(section_title))?;
 
             print!("Comments: ");
             io::stdout().flush()?;
@@ -248,21 +521,12 @@ impl DmpCli {
                         break;
                     }
 
-                    print!("Rating (1-5): ");
-                    io::stdout().flush()?;
-                    let mut sub_rating_input = String::new();
-                    io::stdin().read_line(&mut sub_rating_input)?;
-                    let sub_rating = sub_rating_input.trim().parse::<u8>().ok();
+                    println!("Subsection text (optional, end with an empty line):");
+                    let sub_text = read_paragraphs()?;
 
-                    print!("Tags (comma-separated): ");
-                    io::stdout().flush()?;
-                    let mut sub_tags_input = String::new();
-                    io::stdin().read_line(&mut sub_tags_input)?;
-                    let sub_tags = sub_tags_input
-                        .trim()
-                        .split(',')
-                        .map(|s| s.trim().to_string())
-                        .collect::<Vec<String>>();
+                    let sub_rating = prompt_rating("Rating", scale, Some("not rated"))?;
+
+                    let sub_tags = prompt_tags("Tags", &vocabulary, Some(section_title))?;
 
                     print!("Comments: ");
                     io::stdout().flush()?;
@@ -271,6 +535,7 @@ impl DmpCli {
 
                     subsections.push(Subsection {
                         title: subsection_title.trim().to_string(),
+                        text: sub_text,
                         rating: sub_rating,
                         tags: sub_tags,
                         comments: if sub_comments.trim().is_empty() {
@@ -284,6 +549,7 @@ impl DmpCli {
 
             sections.push(Section {
                 title: section_title.to_string(),
+                text,
                 rating,
                 tags,
                 comments: if comments.trim().is_empty() {
@@ -293,18 +559,13 @@ impl DmpCli {
                 },
                 subsections,
                 cross_references: None,
+                annotations: Vec::new(),
             });
         }
 
         // Overall rating
-        print!("\nOverall DMP rating (1-5): ");
-        io::stdout().flush()?;
-        let mut overall_rating_input = String::new();
-        io::stdin().read_line(&mut overall_rating_input)?;
-        let overall_score = match overall_rating_input.trim().parse::<f32>() {
-            Ok(score) if score >= 1.0 && score <= 5.0 => score,
-            _ => 3.0, // Default to middle value if invalid
-        };
+        println!();
+        let overall_score = prompt_score("Overall DMP rating", None)?.unwrap_or_default();
 
         print!("Reviewer name: ");
         io::stdout().flush()?;
@@ -316,15 +577,7 @@ impl DmpCli {
         let mut overall_comments = String::new();
         io::stdin().read_line(&mut overall_comments)?;
 
-        print!("Overall tags (comma-separated): ");
-        io::stdout().flush()?;
-        let mut overall_tags_input = String::new();
-        io::stdin().read_line(&mut overall_tags_input)?;
-        let overall_tags = overall_tags_input
-            .trim()
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .collect::<Vec<String>>();
+        let overall_tags = prompt_tags("Overall tags", &vocabulary, None)?;
 
         let overall_rating = OverallRating {
             score: overall_score,
@@ -353,6 +606,7 @@ impl DmpCli {
             related_datasets: None,
             related_publications: None,
             related_software: None,
+            schema_version: SCHEMA_VERSION,
         };
 
         // Add the DMP to the database
@@ -362,27 +616,116 @@ impl DmpCli {
         Ok(())
     }
 
-    async fn search_by_tag(&self) -> Result<(), Box<dyn std::error::Error>> {
-        print!("Section title to search (e.g., Data Description & Collection): ");
+    /// Lists the rubric sections and asks for one by number or (partial)
+    /// title. Returns `None` if the input is left empty.
+    fn select_section_title(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        println!("\nRubric sections:");
+        for (i, title) in RUBRIC_SECTIONS.iter().enumerate() {
+            println!("{}. {}", i + 1, title);
+        }
+
+        print!("Section (number or title): ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        if input.trim().is_empty() {
+            return Ok(None);
+        }
+
+        match resolve_section_title(input.trim()) {
+            Some(title) => {
+                println!("Using section: {}", title);
+                Ok(Some(title.to_string()))
+            }
+            None => {
+                println!(
+                    "No rubric section matches '{}', searching for it as typed",
+                    input.trim()
+                );
+                Ok(Some(input.trim().to_string()))
+            }
+        }
+    }
+
+    /// Like `select_section_title`, but a subsection can be chosen by
+    /// appending `/` and its title, e.g. `2/Data volume`.
+    fn select_section_path(&self) -> Result<Option<SectionPath>, Box<dyn std::error::Error>> {
+        println!("\nRubric sections:");
+        for (i, title) in RUBRIC_SECTIONS.iter().enumerate() {
+            println!("{}. {}", i + 1, title);
+        }
+
+        print!("Section (number or title; add /subsection for a subsection): ");
         io::stdout().flush()?;
-        let mut section_title = String::new();
-        io::stdin().read_line(&mut section_title)?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        if input.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let mut path = match input.trim().parse::<SectionPath>() {
+            Ok(path) => path,
+            Err(e) => {
+                println!("{}", e);
+                return Ok(None);
+            }
+        };
+        match resolve_section_title(&path.section) {
+            Some(title) => {
+                println!("Using section: {}", title);
+                path.section = title.to_string();
+            }
+            None => println!(
+                "No rubric section matches '{}', searching for it as typed",
+                path.section
+            ),
+        }
+
+        Ok(Some(path))
+    }
+
+    async fn search_by_tag(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = match self.select_section_path()? {
+            Some(path) => path,
+            None => {
+                println!("Section title cannot be empty");
+                return Ok(());
+            }
+        };
 
         print!("Tag to search: ");
         io::stdout().flush()?;
         let mut tag = String::new();
         io::stdin().read_line(&mut tag)?;
 
+        print!("Match mode (exact, prefix, fuzzy) [exact]: ");
+        io::stdout().flush()?;
+        let mut mode_input = String::new();
+        io::stdin().read_line(&mut mode_input)?;
+        let mode = if mode_input.trim().is_empty() {
+            MatchMode::Exact
+        } else {
+            match mode_input.trim().parse::<MatchMode>() {
+                Ok(mode) => mode,
+                Err(_) => {
+                    println!("Invalid match mode, using 'exact'");
+                    MatchMode::Exact
+                }
+            }
+        };
+
         let dmps = self
             .db_manager
-            .find_dmps_by_section_tag(section_title.trim(), tag.trim())
+            .find_dmps_by_section_tag(&path, tag.trim(), mode)
             .await?;
 
         println!(
-            "\nFound {} DMP(s) with '{}' tag in '{}' section:",
+            "\nFound {} DMP(s) with '{}' tag in '{}':",
             dmps.len(),
             tag.trim(),
-            section_title.trim()
+            path
         );
 
         for (i, dmp) in dmps.iter().enumerate() {
@@ -401,16 +744,12 @@ impl DmpCli {
                 );
             }
 
-            // Find the specific section
-            if let Some(section) = dmp
-                .sections
-                .iter()
-                .find(|s| s.title == section_title.trim())
-            {
-                if let Some(rating) = section.rating {
-                    println!("   Section rating: {}/5", rating);
+            // Find the specific section or subsection
+            if let Some((rating, comments)) = path.rating_and_comments(dmp) {
+                if let Some(rating) = rating {
+                    println!("   Section rating: {}", rating);
                 }
-                if let Some(ref comments) = section.comments {
+                if let Some(comments) = comments {
                     println!("   Comments: {}", comments);
                 }
             }
@@ -422,36 +761,26 @@ impl DmpCli {
     }
 
     async fn search_by_rating(&self) -> Result<(), Box<dyn std::error::Error>> {
-        print!("Section title to search (e.g., Data Description & Collection): ");
-        io::stdout().flush()?;
-        let mut section_title = String::new();
-        io::stdin().read_line(&mut section_title)?;
-
-        print!("Minimum rating (1-5): ");
-        io::stdout().flush()?;
-        let mut rating_input = String::new();
-        io::stdin().read_line(&mut rating_input)?;
-        let rating = match rating_input.trim().parse::<u8>() {
-            Ok(r) if r >= 1 && r <= 5 => r,
-            Ok(_) => {
-                println!("Rating must be between 1 and 5, using default of 3");
-                3
-            }
-            Err(_) => {
-                println!("Invalid rating, using default of 3");
-                3
+        let path = match self.select_section_path()? {
+            Some(path) => path,
+            None => {
+                println!("Section title cannot be empty");
+                return Ok(());
             }
         };
 
-        let dmps = self
-            .db_manager
-            .find_dmps_by_rating(section_title.trim(), rating)
-            .await?;
+        let scale = self.scales.scale_for(&path.section);
+        let rating = match prompt_rating_bound("Minimum rating", scale, false)? {
+            Some(rating) => rating,
+            None => return Ok(()),
+        };
+
+        let dmps = self.db_manager.find_dmps_by_rating(&path, rating).await?;
 
         println!(
-            "\nFound {} DMP(s) with '{}' section rated {} or higher:",
+            "\nFound {} DMP(s) with '{}' rated {} or higher:",
             dmps.len(),
-            section_title.trim(),
+            path,
             rating
         );
 
@@ -471,16 +800,12 @@ impl DmpCli {
                 );
             }
 
-            // Find the specific section
-            if let Some(section) = dmp
-                .sections
-                .iter()
-                .find(|s| s.title == section_title.trim())
-            {
-                if let Some(section_rating) = section.rating {
-                    println!("   Section rating: {}/5", section_rating);
+            // Find the specific section or subsection
+            if let Some((section_rating, comments)) = path.rating_and_comments(dmp) {
+                if let Some(section_rating) = section_rating {
+                    println!("   Section rating: {}", section_rating);
                 }
-                if let Some(ref comments) = section.comments {
+                if let Some(comments) = comments {
                     println!("   Comments: {}", comments);
                 }
             }
@@ -519,9 +844,16 @@ impl DmpCli {
         let mut entity_id = String::new();
         io::stdin().read_line(&mut entity_id)?;
 
+        print!("Relationship type (optional, e.g., IsSupplementTo): ");
+        io::stdout().flush()?;
+        let mut relation_type = String::new();
+        io::stdin().read_line(&mut relation_type)?;
+        let relation_type =
+            (!relation_type.trim().is_empty()).then(|| RelationType::from(relation_type));
+
         let dmps = self
             .db_manager
-            .find_dmps_by_related_entity(entity_type, entity_id.trim())
+            .find_dmps_by_related_entity(entity_type, entity_id.trim(), relation_type)
             .await?;
 
         println!(
@@ -553,7 +885,9 @@ impl DmpCli {
         Ok(())
     }
 
-    async fn update_rating(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Asks for a DMP identifier and looks the DMP up, reporting if it is
+    /// not found.
+    async fn prompt_dmp(&self) -> Result<Option<DataManagementPlan>, Box<dyn std::error::Error>> {
         print!("DMP identifier (e.g., DOI or URL): ");
         io::stdout().flush()?;
         let mut identifier = String::new();
@@ -564,81 +898,1631 @@ impl DmpCli {
         let mut id_type = String::new();
         io::stdin().read_line(&mut id_type)?;
 
-        // Check if the DMP exists
         let dmp = self
             .db_manager
             .find_dmp_by_id(identifier.trim(), id_type.trim())
             .await?;
 
-        match dmp {
-            Some(dmp) => {
-                println!("Found DMP: {}", dmp.title);
+        if dmp.is_none() {
+            println!("DMP with the given identifier was not found");
+        }
 
-                println!("\nAvailable sections:");
-                for (i, section) in dmp.sections.iter().enumerate() {
-                    println!(
-                        "{}. {} (Current rating: {})",
-                        i + 1,
-                        section.title,
-                        section
-                            .rating
-                            .map_or("Not rated".to_string(), |r| r.to_string())
-                    );
-                }
+        Ok(dmp)
+    }
 
-                print!("\nSelect section number to update: ");
-                io::stdout().flush()?;
-                let mut section_num = String::new();
-                io::stdin().read_line(&mut section_num)?;
+    async fn view_dmp(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dmp) = self.prompt_dmp().await? {
+            print_dmp_details(&dmp);
+        }
 
-                let section_index = match section_num.trim().parse::<usize>() {
-                    Ok(num) if num > 0 && num <= dmp.sections.len() => num - 1,
-                    _ => {
-                        println!("Invalid section number");
-                        return Ok(());
-                    }
-                };
+        Ok(())
+    }
 
-                let section = &dmp.sections[section_index];
-                println!("Updating section: {}", section.title);
+    async fn annotate_section(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let dmp = match self.prompt_dmp().await? {
+            Some(dmp) => dmp,
+            None => return Ok(()),
+        };
 
-                print!("New rating (1-5): ");
-                io::stdout().flush()?;
-                let mut rating_input = String::new();
-                io::stdin().read_line(&mut rating_input)?;
-                let rating = match rating_input.trim().parse::<u8>() {
-                    Ok(r) if r >= 1 && r <= 5 => r,
-                    _ => {
-                        println!("Rating must be between 1 and 5");
-                        return Ok(());
-                    }
-                };
+        let annotatable: Vec<&Section> = dmp.sections.iter().filter(|s| s.text.is_some()).collect();
 
-                print!("New comments: ");
-                io::stdout().flush()?;
-                let mut comments = String::new();
-                io::stdin().read_line(&mut comments)?;
+        if annotatable.is_empty() {
+            println!("None of this DMP's sections has any text to annotate");
+            return Ok(());
+        }
 
-                let success = self
-                    .db_manager
-                    .update_dmp_rating(
-                        identifier.trim(),
-                        id_type.trim(),
-                        &section.title,
-                        rating,
-                        comments.trim(),
-                    )
-                    .await?;
+        println!("\nSections with text:");
+        for (i, section) in annotatable.iter().enumerate() {
+            println!("{}. {}", i + 1, section.title);
+        }
 
-                if success {
-                    println!("Section rating updated successfully");
-                } else {
-                    println!("Failed to update section rating");
+        print!("\nSelect section number to annotate: ");
+        io::stdout().flush()?;
+        let mut section_num = String::new();
+        io::stdin().read_line(&mut section_num)?;
+
+        let section = match section_num.trim().parse::<usize>() {
+            Ok(num) if num > 0 && num <= annotatable.len() => annotatable[num - 1],
+            _ => {
+                println!("Invalid section number");
+                return Ok(());
+            }
+        };
+        let text = section.text.as_deref().unwrap_or_default();
+
+        println!();
+        for (i, line) in text.lines().enumerate() {
+            println!("{:>4} | {}", i + 1, line);
+        }
+
+        print!("\nAnchor by (1) quoted passage or (2) character range? ");
+        io::stdout().flush()?;
+        let mut anchor_type = String::new();
+        io::stdin().read_line(&mut anchor_type)?;
+
+        let anchor = if anchor_type.trim() == "2" {
+            print!("Start and end character positions (e.g., 10-42): ");
+            io::stdout().flush()?;
+            let mut range = String::new();
+            io::stdin().read_line(&mut range)?;
+
+            match range
+                .trim()
+                .split_once('-')
+                .map(|(start, end)| (start.trim().parse::<u32>(), end.trim().parse::<u32>()))
+            {
+                Some((Ok(start), Ok(end))) => AnnotationAnchor::Range { start, end },
+                _ => {
+                    println!("Invalid range");
+                    return Ok(());
                 }
             }
-            None => println!("DMP with the given identifier was not found"),
+        } else {
+            print!("Passage to annotate (copied exactly from the text): ");
+            io::stdout().flush()?;
+            let mut quote = String::new();
+            io::stdin().read_line(&mut quote)?;
+
+            AnnotationAnchor::Quote {
+                text: quote.trim().to_string(),
+            }
+        };
+
+        print!("Your name: ");
+        io::stdout().flush()?;
+        let mut author = String::new();
+        io::stdin().read_line(&mut author)?;
+
+        let vocabulary = self.db_manager.load_tag_vocabulary().await?;
+        let tags = prompt_tags("Tags", &vocabulary, Some(&section.title))?;
+
+        print!("Comment: ");
+        io::stdout().flush()?;
+        let mut comment = String::new();
+        io::stdin().read_line(&mut comment)?;
+
+        let annotation = Annotation {
+            anchor,
+            author: author.trim().to_string(),
+            tags,
+            comment: comment.trim().to_string(),
+            created_date: DateTime::now(),
+        };
+
+        match annotation.passage(text) {
+            Some(passage) => println!("Annotating: \"{}\"", passage),
+            None => {
+                println!("That passage could not be found in the section text");
+                return Ok(());
+            }
+        }
+
+        let success = self
+            .db_manager
+            .add_annotation(
+                &dmp.dmp_id.identifier,
+                dmp.dmp_id.id_type.as_str(),
+                &section.title,
+                annotation,
+            )
+            .await?;
+
+        if success {
+            println!("Annotation added successfully");
+        } else {
+            println!("Failed to add annotation");
         }
 
         Ok(())
     }
+
+    async fn add_cross_reference(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let dmp = match self.prompt_dmp().await? {
+            Some(dmp) => dmp,
+            None => return Ok(()),
+        };
+
+        println!("\nSections:");
+        for (i, section) in dmp.sections.iter().enumerate() {
+            println!("{}. {}", i + 1, section.title);
+        }
+
+        print!("\nSelect the section number the reference is made from: ");
+        io::stdout().flush()?;
+        let mut section_num = String::new();
+        io::stdin().read_line(&mut section_num)?;
+
+        let section = match section_num.trim().parse::<usize>() {
+            Ok(num) if num > 0 && num <= dmp.sections.len() => &dmp.sections[num - 1],
+            _ => {
+                println!("Invalid section number");
+                return Ok(());
+            }
+        };
+
+        print!("Reference a section of (1) this DMP or (2) another DMP? ");
+        io::stdout().flush()?;
+        let mut target_choice = String::new();
+        io::stdin().read_line(&mut target_choice)?;
+
+        let (target_dmp, target_section) = if target_choice.trim() == "2" {
+            if let Some(ref related_dmps) = dmp.related_dmps {
+                println!("Related DMPs:");
+                for related in related_dmps {
+                    println!(
+                        "   {} ({}/{})",
+                        related.title, related.dmp_id.id_type, related.dmp_id.identifier
+                    );
+                }
+            }
+
+            print!("Referenced DMP identifier: ");
+            io::stdout().flush()?;
+            let mut identifier = String::new();
+            io::stdin().read_line(&mut identifier)?;
+
+            print!("Identifier type (doi, handle, ark, url, other): ");
+            io::stdout().flush()?;
+            let mut id_type = String::new();
+            io::stdin().read_line(&mut id_type)?;
+
+            let target_section = match self.select_section_title()? {
+                Some(title) => title,
+                None => {
+                    println!("Section title cannot be empty");
+                    return Ok(());
+                }
+            };
+
+            (
+                Some(Identifier {
+                    identifier: identifier.trim().to_string(),
+                    id_type: IdentifierType::from(id_type),
+                }),
+                target_section,
+            )
+        } else {
+            print!("Select the referenced section number: ");
+            io::stdout().flush()?;
+            let mut target_num = String::new();
+            io::stdin().read_line(&mut target_num)?;
+
+            match target_num.trim().parse::<usize>() {
+                Ok(num) if num > 0 && num <= dmp.sections.len() => {
+                    (None, dmp.sections[num - 1].title.clone())
+                }
+                _ => {
+                    println!("Invalid section number");
+                    return Ok(());
+                }
+            }
+        };
+
+        print!("Reference type (contradicts, supports, depends-on): ");
+        io::stdout().flush()?;
+        let mut reference_type = String::new();
+        io::stdin().read_line(&mut reference_type)?;
+
+        let reference_type = match reference_type.trim().parse::<ReferenceType>() {
+            Ok(reference_type) => reference_type,
+            Err(e) => {
+                println!("{}", e);
+                return Ok(());
+            }
+        };
+
+        print!("Note: ");
+        io::stdout().flush()?;
+        let mut note = String::new();
+        io::stdin().read_line(&mut note)?;
+
+        let reference = SectionCrossReference {
+            section_title: target_section,
+            reference_note: note.trim().to_string(),
+            reference_type: Some(reference_type),
+            target_dmp,
+        };
+
+        match self
+            .db_manager
+            .add_cross_reference(
+                &dmp.dmp_id.identifier,
+                dmp.dmp_id.id_type.as_str(),
+                &section.title,
+                reference,
+            )
+            .await
+        {
+            Ok(true) => println!("Cross-reference added successfully"),
+            Ok(false) => println!("Failed to add cross-reference"),
+            Err(e) => println!("Cannot add cross-reference: {}", e),
+        }
+
+        Ok(())
+    }
+
+    /// Asks which kind of related entity to work with.
+    fn select_entity_type(&self) -> Result<Option<&'static str>, Box<dyn std::error::Error>> {
+        println!("Entity type:");
+        for (i, entity_type) in ENTITY_TYPES.iter().enumerate() {
+            println!("{}. {}", i + 1, entity_type);
+        }
+
+        print!("Choose entity type (1-{}): ", ENTITY_TYPES.len());
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        let entity_type = match input.trim().parse::<usize>() {
+            Ok(num) if num > 0 && num <= ENTITY_TYPES.len() => Some(ENTITY_TYPES[num - 1]),
+            _ => {
+                println!("Invalid entity type");
+                None
+            }
+        };
+
+        Ok(entity_type)
+    }
+
+    /// Prompts for the fields of a related entity of `entity_type`, showing
+    /// the current values of `existing` when editing one.
+    fn prompt_related_fields(
+        &self,
+        entity_type: &str,
+        existing: Option<&RelatedEntity>,
+    ) -> io::Result<RelatedEntityFields> {
+        let current = |value: Option<&str>| -> Option<String> { value.map(str::to_string) };
+        let dataset = match existing {
+            Some(RelatedEntity::Dataset(dataset)) => Some(dataset),
+            _ => None,
+        };
+        let publication = match existing {
+            Some(RelatedEntity::Publication(publication)) => Some(publication),
+            _ => None,
+        };
+        let software = match existing {
+            Some(RelatedEntity::Software(software)) => Some(software),
+            _ => None,
+        };
+        let related_dmp = match existing {
+            Some(RelatedEntity::Dmp(dmp)) => Some(dmp),
+            _ => None,
+        };
+
+        if existing.is_some() {
+            println!(
+                "Press Enter to keep the current value, or enter {} to clear an optional field.",
+                CLEAR_VALUE
+            );
+        }
+
+        let mut fields = RelatedEntityFields {
+            identifier: prompt_field(
+                "Identifier (e.g., DOI)",
+                current(existing.map(|e| e.identifier().identifier.as_str())),
+            )?,
+            id_type: prompt_field(
+                "Identifier type (doi, handle, ark, url, other)",
+                current(existing.map(|e| e.identifier().id_type.as_str())),
+            )?,
+            relationship: prompt_field(
+                "Relationship type (DataCite relationType, e.g., IsSupplementTo, References, IsDerivedFrom)",
+                current(existing.map(|e| e.relationship().as_str())),
+            )?,
+            title: prompt_field(
+                if entity_type == "software" {
+                    "Name"
+                } else {
+                    "Title"
+                },
+                current(existing.map(|e| e.title())),
+            )?,
+            ..Default::default()
+        };
+
+        match entity_type {
+            "dataset" => {
+                fields.repository = prompt_field(
+                    "Repository (optional)",
+                    dataset.and_then(|d| d.repository.clone()),
+                )?;
+                fields.url = prompt_field("URL (optional)", dataset.and_then(|d| d.url.clone()))?;
+            }
+            "publication" => {
+                fields.authors = prompt_field(
+                    "Authors, separated by semicolons (optional)",
+                    publication
+                        .filter(|p| !p.authors.is_empty())
+                        .map(|p| p.authors.join("; ")),
+                )?;
+                fields.journal = prompt_field(
+                    "Journal (optional)",
+                    publication.and_then(|p| p.journal.clone()),
+                )?;
+                fields.publication_date = prompt_field(
+                    "Publication date, YYYY-MM-DD (optional)",
+                    publication
+                        .and_then(|p| p.publication_date)
+                        .and_then(|d| d.try_to_rfc3339_string().ok())
+                        .map(|d| d[..10].to_string()),
+                )?;
+                fields.url =
+                    prompt_field("URL (optional)", publication.and_then(|p| p.url.clone()))?;
+            }
+            "software" => {
+                fields.version = prompt_field(
+                    "Version (optional)",
+                    software.and_then(|s| s.version.clone()),
+                )?;
+                fields.url = prompt_field("URL (optional)", software.and_then(|s| s.url.clone()))?;
+            }
+            _ => {
+                fields.notes = prompt_field(
+                    "Relationship notes (optional)",
+                    related_dmp.and_then(|d| d.relationship_notes.clone()),
+                )?;
+            }
+        }
+
+        Ok(fields)
+    }
+
+    /// Lists, attaches, edits and removes the datasets, publications,
+    /// software and DMPs related to a DMP.
+    async fn manage_related_entities(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut dmp = match self.prompt_dmp().await? {
+            Some(dmp) => dmp,
+            None => return Ok(()),
+        };
+        let (dmp_id, id_type) = (
+            dmp.dmp_id.identifier.clone(),
+            dmp.dmp_id.id_type.to_string(),
+        );
+
+        loop {
+            let related = related_entities(&dmp);
+            println!("\nRelated entities of {}:", dmp.title);
+            if related.is_empty() {
+                println!("   (none)");
+            }
+            for (i, entity) in related.iter().enumerate() {
+                println!("{}. {}", i + 1, entity);
+            }
+
+            println!("\n1. Attach a related entity");
+            println!("2. Edit a related entity");
+            println!("3. Remove a related entity");
+            println!("4. Back");
+
+            print!("Choose an option (1-4): ");
+            io::stdout().flush()?;
+            let mut choice = String::new();
+            io::stdin().read_line(&mut choice)?;
+
+            match choice.trim() {
+                "1" => {
+                    let entity_type = match self.select_entity_type()? {
+                        Some(entity_type) => entity_type,
+                        None => continue,
+                    };
+                    let fields = self.prompt_related_fields(entity_type, None)?;
+                    let entity = match build_related_entity(entity_type, fields, None) {
+                        Ok(entity) => entity,
+                        Err(e) => {
+                            println!("{}", e);
+                            continue;
+                        }
+                    };
+                    if let Some(note) = entity.custom_relationship_note() {
+                        println!("{}", note);
+                    }
+
+                    match self
+                        .db_manager
+                        .add_related_entity(&dmp_id, &id_type, &entity)
+                        .await
+                    {
+                        Ok(true) => println!("Related {} attached successfully", entity_type),
+                        Ok(false) => println!("Failed to attach related {}", entity_type),
+                        Err(e) => println!("Cannot attach related {}: {}", entity_type, e),
+                    }
+                }
+                "2" | "3" => {
+                    if related.is_empty() {
+                        println!("There are no related entities");
+                        continue;
+                    }
+
+                    print!("Select the related entity number: ");
+                    io::stdout().flush()?;
+                    let mut entity_num = String::new();
+                    io::stdin().read_line(&mut entity_num)?;
+
+                    let entity = match entity_num.trim().parse::<usize>() {
+                        Ok(num) if num > 0 && num <= related.len() => &related[num - 1],
+                        _ => {
+                            println!("Invalid entity number");
+                            continue;
+                        }
+                    };
+                    let entity_type = entity.entity_type();
+                    let entity_id = &entity.identifier().identifier;
+
+                    let result = if choice.trim() == "2" {
+                        let fields = self.prompt_related_fields(entity_type, Some(entity))?;
+                        let updated =
+                            match build_related_entity(entity_type, fields, Some(entity.clone())) {
+                                Ok(updated) => updated,
+                                Err(e) => {
+                                    println!("{}", e);
+                                    continue;
+                                }
+                            };
+                        if let Some(note) = updated.custom_relationship_note() {
+                            println!("{}", note);
+                        }
+                        self.db_manager
+                            .update_related_entity(
+                                &dmp_id,
+                                &id_type,
+                                entity_type,
+                                entity_id,
+                                &updated,
+                            )
+                            .await
+                    } else {
+                        self.db_manager
+                            .remove_related_entity(&dmp_id, &id_type, entity_type, entity_id)
+                            .await
+                    };
+
+                    match result {
+                        Ok(true) => println!("Related {} saved successfully", entity_type),
+                        Ok(false) => println!("No changes were made"),
+                        Err(e) => println!("Cannot change related {}: {}", entity_type, e),
+                    }
+                }
+                "4" => break,
+                _ => {
+                    println!("Invalid option. Please try again.");
+                    continue;
+                }
+            }
+
+            dmp = match self.db_manager.find_dmp_by_id(&dmp_id, &id_type).await? {
+                Some(dmp) => dmp,
+                None => {
+                    println!("DMP with the given identifier was not found");
+                    break;
+                }
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Walks the relationships around a DMP or research output and prints
+    /// them, optionally exporting the graph as DOT or GraphML.
+    async fn explore_graph(&self) -> Result<(), Box<dyn std::error::Error>> {
+        print!("Identifier of the DMP, dataset, publication or software: ");
+        io::stdout().flush()?;
+        let mut identifier = String::new();
+        io::stdin().read_line(&mut identifier)?;
+
+        print!("Entity type (dataset, publication, software, dmp; leave empty for any): ");
+        io::stdout().flush()?;
+        let mut entity_type = String::new();
+        io::stdin().read_line(&mut entity_type)?;
+        let entity_type = entity_type.trim().to_lowercase();
+
+        print!("Maximum number of hops (default 2): ");
+        io::stdout().flush()?;
+        let mut hops = String::new();
+        io::stdin().read_line(&mut hops)?;
+        let hops = hops.trim().parse::<usize>().unwrap_or(2);
+
+        print!("Only follow relationship types, comma-separated (leave empty for all): ");
+        io::stdout().flush()?;
+        let mut relations = String::new();
+        io::stdin().read_line(&mut relations)?;
+        let relations: Vec<RelationType> = relations
+            .split(',')
+            .filter(|r| !r.trim().is_empty())
+            .map(|r| RelationType::from(r.to_string()))
+            .collect();
+
+        let graph = match self
+            .db_manager
+            .relation_graph(
+                (!entity_type.is_empty()).then_some(entity_type.as_str()),
+                identifier.trim(),
+                hops,
+                &relations,
+            )
+            .await
+        {
+            Ok(graph) => graph,
+            Err(e) => {
+                println!("{}", e);
+                return Ok(());
+            }
+        };
+
+        println!("\n{}", graph.to_text());
+
+        print!("Export the graph to a file (.dot or .graphml; leave empty to skip): ");
+        io::stdout().flush()?;
+        let mut path = String::new();
+        io::stdin().read_line(&mut path)?;
+
+        if !path.trim().is_empty() {
+            match graph.write(path.trim()) {
+                Ok(()) => println!("Graph written to {}", path.trim()),
+                Err(e) => eprintln!("Error writing graph: {}", e),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn export_report(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let dmp = match self.prompt_dmp().await? {
+            Some(dmp) => dmp,
+            None => return Ok(()),
+        };
+
+        print!("Enter report file path (e.g., report.html): ");
+        io::stdout().flush()?;
+        let mut path = String::new();
+        io::stdin().read_line(&mut path)?;
+
+        match write_dmp_report(&dmp, path.trim()) {
+            Ok(()) => println!("Report written to {}", path.trim()),
+            Err(e) => eprintln!("Error writing report: {}", e),
+        }
+
+        Ok(())
+    }
+
+    async fn update_rating(&self) -> Result<(), Box<dyn std::error::Error>> {
+        print!("DMP identifier (e.g., DOI or URL): ");
+        io::stdout().flush()?;
+        let mut identifier = String::new();
+        io::stdin().read_line(&mut identifier)?;
+
+        print!("Identifier type (doi, handle, ark, url, other): ");
+        io::stdout().flush()?;
+        let mut id_type = String::new();
+        io::stdin().read_line(&mut id_type)?;
+
+        // Check if the DMP exists
+        let dmp = self
+            .db_manager
+            .find_dmp_by_id(identifier.trim(), id_type.trim())
+            .await?;
+
+        match dmp {
+            Some(dmp) => {
+                println!("Found DMP: {}", dmp.title);
+
+                println!("\nAvailable sections:");
+                print_numbered_sections(&dmp);
+
+                print!("\nSelect section or subsection number to update (e.g., 2 or 2.1): ");
+                io::stdout().flush()?;
+                let mut section_num = String::new();
+                io::stdin().read_line(&mut section_num)?;
+
+                let path = match numbered_section_path(&dmp, &section_num) {
+                    Ok(path) => path,
+                    Err(e) => {
+                        println!("{}", e);
+                        return Ok(());
+                    }
+                };
+                let (section_index, subsection_index) = path.position(&dmp)?;
+                let section = &dmp.sections[section_index];
+                let text = match subsection_index {
+                    Some(j) => {
+                        println!("Updating subsection: {}", path);
+                        &section.subsections[j].text
+                    }
+                    None => {
+                        println!("Updating section: {}", section.title);
+                        &section.text
+                    }
+                };
+
+                if let Some(ref text) = text {
+                    print_indented("Section text", text, 3);
+                }
+
+                let scale = self.scales.scale_for(&section.title);
+                let rating = match prompt_rating("New rating", scale, None)? {
+                    Some(rating) => rating,
+                    None => return Ok(()),
+                };
+
+                print!("New comments: ");
+                io::stdout().flush()?;
+                let mut comments = String::new();
+                io::stdin().read_line(&mut comments)?;
+
+                let mut read_at = dmp.last_modified;
+                let success = loop {
+                    let result = self
+                        .db_manager
+                        .update_dmp_rating(
+                            identifier.trim(),
+                            id_type.trim(),
+                            &path,
+                            rating,
+                            comments.trim(),
+                            read_at,
+                        )
+                        .await;
+                    let conflict = match result {
+                        Ok(success) => break success,
+                        Err(e) => e.downcast::<ConflictError>()?,
+                    };
+
+                    // Someone else saved the DMP since it was shown; let the
+                    // reviewer compare before overwriting their change
+                    println!("\n{}", conflict);
+                    print!(
+                        "{}",
+                        merge_view(&section_merge_fields(
+                            &path,
+                            &dmp,
+                            &conflict.current,
+                            rating,
+                            comments.trim()
+                        ))
+                    );
+                    if path.position(&conflict.current).is_err() {
+                        return Ok(());
+                    }
+
+                    print!("Save your values over the stored ones? (y/n): ");
+                    io::stdout().flush()?;
+                    let mut answer = String::new();
+                    io::stdin().read_line(&mut answer)?;
+                    if !answer.trim().eq_ignore_ascii_case("y") {
+                        println!("Section rating not updated");
+                        return Ok(());
+                    }
+                    read_at = conflict.current.last_modified;
+                };
+
+                if success {
+                    println!("Section rating updated successfully");
+                } else {
+                    println!("Failed to update section rating");
+                }
+            }
+            None => println!("DMP with the given identifier was not found"),
+        }
+
+        Ok(())
+    }
+
+    /// Collects changes to any number of section, subsection and overall
+    /// ratings and writes them in one update.
+    async fn update_ratings(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let dmp = match self.prompt_dmp().await? {
+            Some(dmp) => dmp,
+            None => return Ok(()),
+        };
+
+        println!("Found DMP: {}", dmp.title);
+        println!("\nSections:");
+        print_numbered_sections(&dmp);
+
+        print!("\nReviewer name: ");
+        io::stdout().flush()?;
+        let mut reviewer = String::new();
+        io::stdin().read_line(&mut reviewer)?;
+        let mut update = DmpUpdate::new(&reviewer);
+
+        loop {
+            print!("\nSection or subsection number to change (e.g., 2 or 2.1; leave empty to finish): ");
+            io::stdout().flush()?;
+            let mut number = String::new();
+            io::stdin().read_line(&mut number)?;
+            if number.trim().is_empty() {
+                break;
+            }
+
+            let path = match numbered_section_path(&dmp, &number) {
+                Ok(path) => path,
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            };
+
+            let scale = self.scales.scale_for(&path.section);
+            let rating = prompt_rating("New rating", scale, Some("unchanged"))?;
+
+            print!("New comments (leave empty to keep): ");
+            io::stdout().flush()?;
+            let mut comments = String::new();
+            io::stdin().read_line(&mut comments)?;
+            let comments = Some(comments.trim()).filter(|c| !c.is_empty());
+
+            update = match path.subsection {
+                Some(ref subsection) => {
+                    update.subsection(&path.section, subsection, rating, comments)
+                }
+                None => update.section(&path.section, rating, comments),
+            };
+        }
+
+        let score = prompt_score("New overall score", Some("to keep"))?;
+
+        print!("New overall comments (leave empty to keep): ");
+        io::stdout().flush()?;
+        let mut overall_comments = String::new();
+        io::stdin().read_line(&mut overall_comments)?;
+        let overall_comments = Some(overall_comments.trim()).filter(|c| !c.is_empty());
+        if score.is_some() || overall_comments.is_some() {
+            update = update.overall(score, overall_comments);
+        }
+
+        if update.is_empty() {
+            println!("No changes were made");
+            return Ok(());
+        }
+        match update.summary(&dmp) {
+            Ok(summary) => println!("\nChanges: {}", summary),
+            Err(e) => {
+                println!("{}", e);
+                return Ok(());
+            }
+        }
+
+        let (dmp_id, id_type) = (dmp.dmp_id.identifier.as_str(), dmp.dmp_id.id_type.as_str());
+        let mut read_at = dmp.last_modified;
+        loop {
+            let result = self
+                .db_manager
+                .apply_update(dmp_id, id_type, &update, read_at)
+                .await;
+            let conflict = match result {
+                Ok(true) => {
+                    println!("Ratings updated successfully");
+                    return Ok(());
+                }
+                Ok(false) => {
+                    println!("No changes were made");
+                    return Ok(());
+                }
+                Err(e) => e.downcast::<ConflictError>()?,
+            };
+
+            println!("\n{}", conflict);
+            print!(
+                "{}",
+                merge_view(&update.merge_fields(&dmp, &conflict.current))
+            );
+
+            print!("Save your values over the stored ones? (y/n): ");
+            io::stdout().flush()?;
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            if !answer.trim().eq_ignore_ascii_case("y") {
+                println!("Ratings not updated");
+                return Ok(());
+            }
+            read_at = conflict.current.last_modified;
+        }
+    }
+
+    async fn advanced_search(&self) -> Result<(), Box<dyn std::error::Error>> {
+        println!("\nAdvanced search (leave any criterion empty to skip it)");
+
+        let mut query = DmpQuery::new();
+
+        if let Some(section_title) = self.select_section_title()? {
+            query = query.section(&section_title);
+        }
+
+        print!("Section tags (comma-separated): ");
+        io::stdout().flush()?;
+        let mut tags_input = String::new();
+        io::stdin().read_line(&mut tags_input)?;
+        let tags = parse_tags(&tags_input);
+
+        if !tags.is_empty() {
+            print!("Match any or all of these tags? (any/all): ");
+            io::stdout().flush()?;
+            let mut tag_match = String::new();
+            io::stdin().read_line(&mut tag_match)?;
+            query = if tag_match.trim().to_lowercase() == "all" {
+                query.all_tags(tags)
+            } else {
+                query.any_tags(tags)
+            };
+        }
+
+        // Without a section, only sections rated 1-5 are compared
+        let scale = query
+            .section_title()
+            .map_or_else(RatingScale::default, |title| self.scales.scale_for(title));
+        if let Some(rating) = prompt_rating_bound("Minimum section rating", scale, true)? {
+            query = query.min_rating(rating);
+        }
+        if let Some(rating) = prompt_rating_bound("Maximum section rating", scale, true)? {
+            query = query.max_rating(rating);
+        }
+
+        print!("Minimum overall score (1-5): ");
+        io::stdout().flush()?;
+        let mut min_score = String::new();
+        io::stdin().read_line(&mut min_score)?;
+        if !min_score.trim().is_empty() {
+            match min_score.trim().parse::<f32>() {
+                Ok(score) if (1.0..=5.0).contains(&score) => query = query.min_overall_score(score),
+                _ => println!("Invalid score, ignoring minimum overall score"),
+            }
+        }
+
+        print!("Maximum overall score (1-5): ");
+        io::stdout().flush()?;
+        let mut max_score = String::new();
+        io::stdin().read_line(&mut max_score)?;
+        if !max_score.trim().is_empty() {
+            match max_score.trim().parse::<f32>() {
+                Ok(score) if (1.0..=5.0).contains(&score) => query = query.max_overall_score(score),
+                _ => println!("Invalid score, ignoring maximum overall score"),
+            }
+        }
+
+        print!("Funder: ");
+        io::stdout().flush()?;
+        let mut funder = String::new();
+        io::stdin().read_line(&mut funder)?;
+        if !funder.trim().is_empty() {
+            query = query.funder(funder.trim());
+        }
+
+        print!("Institution: ");
+        io::stdout().flush()?;
+        let mut institution = String::new();
+        io::stdin().read_line(&mut institution)?;
+        if !institution.trim().is_empty() {
+            query = query.institution(institution.trim());
+        }
+
+        print!("Author name: ");
+        io::stdout().flush()?;
+        let mut author = String::new();
+        io::stdin().read_line(&mut author)?;
+        if !author.trim().is_empty() {
+            query = query.author(author.trim());
+        }
+
+        let created_from = prompt_date("Created on or after", false)?;
+        let created_to = prompt_date("Created on or before", true)?;
+        if created_from.is_some() || created_to.is_some() {
+            query = query.created_between(created_from, created_to);
+        }
+
+        let reviewed_from = prompt_date("Reviewed on or after", false)?;
+        let reviewed_to = prompt_date("Reviewed on or before", true)?;
+        if reviewed_from.is_some() || reviewed_to.is_some() {
+            query = query.reviewed_between(reviewed_from, reviewed_to);
+        }
+
+        print!("Related entity type (dataset, publication, software, dmp): ");
+        io::stdout().flush()?;
+        let mut entity_type = String::new();
+        io::stdin().read_line(&mut entity_type)?;
+        if !entity_type.trim().is_empty() {
+            print!("Related entity identifier (e.g., DOI): ");
+            io::stdout().flush()?;
+            let mut entity_id = String::new();
+            io::stdin().read_line(&mut entity_id)?;
+
+            print!("Relationship type (optional, e.g., IsSupplementTo): ");
+            io::stdout().flush()?;
+            let mut relation_type = String::new();
+            io::stdin().read_line(&mut relation_type)?;
+            let relation_type =
+                (!relation_type.trim().is_empty()).then(|| RelationType::from(relation_type));
+
+            query = query.related_entity(
+                entity_type.trim().to_lowercase().as_str(),
+                entity_id.trim(),
+                relation_type,
+            );
+        }
+
+        self.run_query(&query).await?;
+        self.offer_to_save(&query, None).await
+    }
+
+    /// Runs `query` and prints the matching DMPs.
+    async fn run_query(&self, query: &DmpQuery) -> Result<(), Box<dyn std::error::Error>> {
+        let dmps = self.db_manager.find_dmps(query).await?;
+        print_query_results(&dmps, query);
+        Ok(())
+    }
+
+    /// Asks whether to save a search that was just run, and saves it under
+    /// the name the user gives.
+    async fn offer_to_save(
+        &self,
+        query: &DmpQuery,
+        expression: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        print!("Save this search? (y/n): ");
+        io::stdout().flush()?;
+        let mut save = String::new();
+        io::stdin().read_line(&mut save)?;
+
+        if save.trim().to_lowercase() != "y" {
+            return Ok(());
+        }
+
+        print!("Name for this search: ");
+        io::stdout().flush()?;
+        let mut name = String::new();
+        io::stdin().read_line(&mut name)?;
+
+        if name.trim().is_empty() {
+            println!("Search name cannot be empty, search not saved");
+            return Ok(());
+        }
+
+        if self
+            .db_manager
+            .find_saved_search(name.trim())
+            .await?
+            .is_some()
+        {
+            print!(
+                "A search named '{}' already exists. Replace it? (y/n): ",
+                name.trim()
+            );
+            io::stdout().flush()?;
+            let mut replace = String::new();
+            io::stdin().read_line(&mut replace)?;
+
+            if replace.trim().to_lowercase() != "y" {
+                println!("Search not saved");
+                return Ok(());
+            }
+        }
+
+        let search = SavedSearch {
+            id: None,
+            name: name.trim().to_string(),
+            expression: expression.map(|e| e.trim().to_string()),
+            query: query.clone(),
+            created_date: DateTime::now(),
+            last_run: Some(DateTime::now()),
+        };
+
+        self.db_manager.save_search(search).await?;
+        println!("Search saved as '{}'", name.trim());
+
+        Ok(())
+    }
+
+    async fn manage_saved_searches(&self) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            println!("\nSaved searches:");
+            println!("1. List saved searches");
+            println!("2. Run a saved search");
+            println!("3. Export the results of a saved search to JSON");
+            println!("4. Delete a saved search");
+            println!("5. Back");
+
+            print!("Choose an option (1-5): ");
+            io::stdout().flush()?;
+
+            let mut input = String::new();
+            io::stdin().read_line(&mut input)?;
+
+            match input.trim() {
+                "1" => {
+                    let searches = self.db_manager.list_saved_searches().await?;
+                    println!("\n{} saved search(es):", searches.len());
+
+                    for (i, search) in searches.iter().enumerate() {
+                        let definition = match search.expression {
+                            Some(ref expression) => expression.clone(),
+                            None => search
+                                .query
+                                .to_filter()
+                                .map_or("(invalid search)".to_string(), |f| f.to_string()),
+                        };
+                        println!("{}. {}: {}", i + 1, search.name, definition);
+                        if let Some(last_run) = search.last_run {
+                            println!("   Last run: {}", last_run);
+                        }
+                    }
+                }
+                "2" => {
+                    print!("Saved search name: ");
+                    io::stdout().flush()?;
+                    let mut name = String::new();
+                    io::stdin().read_line(&mut name)?;
+
+                    match self.db_manager.find_saved_search(name.trim()).await? {
+                        Some(search) => {
+                            let dmps = self.db_manager.run_saved_search(name.trim()).await?;
+                            print_query_results(&dmps, &search.query);
+                        }
+                        None => println!("No saved search named '{}'", name.trim()),
+                    }
+                }
+                "3" => {
+                    print!("Saved search name: ");
+                    io::stdout().flush()?;
+                    let mut name = String::new();
+                    io::stdin().read_line(&mut name)?;
+
+                    print!("Enter export file path: ");
+                    io::stdout().flush()?;
+                    let mut path = String::new();
+                    io::stdin().read_line(&mut path)?;
+
+                    match self
+                        .db_manager
+                        .export_saved_search(name.trim(), path.trim())
+                        .await
+                    {
+                        Ok(count) => println!("Successfully exported {} DMPs.", count),
+                        Err(e) => eprintln!("Error exporting DMPs: {}", e),
+                    }
+                }
+                "4" => {
+                    print!("Saved search name: ");
+                    io::stdout().flush()?;
+                    let mut name = String::new();
+                    io::stdin().read_line(&mut name)?;
+
+                    if self.db_manager.delete_saved_search(name.trim()).await? {
+                        println!("Saved search '{}' deleted", name.trim());
+                    } else {
+                        println!("No saved search named '{}'", name.trim());
+                    }
+                }
+                "5" => break,
+                _ => println!("Invalid option. Please try again."),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Summarizes the ratings and tags of every DMP, or of those matching a
+    /// search expression.
+    async fn show_analytics(&self) -> Result<(), Box<dyn std::error::Error>> {
+        print!("Search expression to limit the analytics to (leave empty for all DMPs): ");
+        io::stdout().flush()?;
+        let mut expression = String::new();
+        io::stdin().read_line(&mut expression)?;
+        let expression = expression.trim_end_matches(['\r', '\n']);
+
+        let query = if expression.trim().is_empty() {
+            DmpQuery::new()
+        } else {
+            match parse_query(expression, &self.scales) {
+                Ok(query) => query,
+                Err(e) => {
+                    println!("Invalid search expression: {}", e);
+                    println!("{}", e.pointer(expression));
+                    return Ok(());
+                }
+            }
+        };
+
+        let analytics = self.db_manager.analytics(&query, 5).await?;
+        println!();
+        print!("{}", analytics.to_text());
+
+        Ok(())
+    }
+
+    async fn manage_tag_vocabulary(&self) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            println!("\nTag vocabulary:");
+            println!("1. List the vocabulary");
+            println!("2. Add or change a tag");
+            println!("3. Remove a tag");
+            println!("4. Rename or merge a tag across all DMPs");
+            println!("5. Back");
+
+            print!("Choose an option (1-5): ");
+            io::stdout().flush()?;
+
+            let mut input = String::new();
+            io::stdin().read_line(&mut input)?;
+
+            match input.trim() {
+                "1" => {
+                    let vocabulary = self.db_manager.load_tag_vocabulary().await?;
+                    if vocabulary.is_empty() {
+                        println!("The vocabulary is empty; any tag may be entered");
+                    }
+                    for tag in vocabulary.roots() {
+                        print_vocabulary_tag(&vocabulary, tag, 0);
+                    }
+                }
+                "2" => self.edit_vocabulary_tag().await?,
+                "3" => {
+                    print!("Tag: ");
+                    io::stdout().flush()?;
+                    let mut name = String::new();
+                    io::stdin().read_line(&mut name)?;
+
+                    if self
+                        .db_manager
+                        .delete_vocabulary_tag(&normalize_tag(&name))
+                        .await?
+                    {
+                        println!("'{}' removed from the vocabulary", normalize_tag(&name));
+                    } else {
+                        println!("'{}' is not in the vocabulary", normalize_tag(&name));
+                    }
+                }
+                "4" => {
+                    print!("Tag to rename: ");
+                    io::stdout().flush()?;
+                    let mut from = String::new();
+                    io::stdin().read_line(&mut from)?;
+
+                    print!("New name (an existing tag to merge into it): ");
+                    io::stdout().flush()?;
+                    let mut to = String::new();
+                    io::stdin().read_line(&mut to)?;
+
+                    match self.db_manager.rename_tag(&from, &to).await {
+                        Ok(rename) => print!("{}", rename.to_text()),
+                        Err(e) => println!("Tag not renamed: {}", e),
+                    }
+                }
+                "5" => break,
+                _ => println!("Invalid option. Please try again."),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Asks for a tag and its synonyms, parent and sections, and adds it to
+    /// the vocabulary or changes it if it is already there.
+    async fn edit_vocabulary_tag(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let vocabulary = self.db_manager.load_tag_vocabulary().await?;
+
+        print!("Tag: ");
+        io::stdout().flush()?;
+        let mut name = String::new();
+        io::stdin().read_line(&mut name)?;
+        let name = normalize_tag(&name);
+        if name.is_empty() {
+            println!("Tag cannot be empty");
+            return Ok(());
+        }
+
+        let existing = vocabulary.find(&name).filter(|t| t.name == name);
+        if existing.is_some() {
+            println!(
+                "Press Enter to keep the current value, or enter {} to clear it.",
+                CLEAR_VALUE
+            );
+        }
+        let list = |values: &[String]| Some(values.join(", ")).filter(|v| !v.is_empty());
+
+        let synonyms = prompt_field(
+            "Synonyms (comma-separated)",
+            existing.and_then(|t| list(&t.synonyms)),
+        )?;
+        let parent = prompt_field(
+            "Broader tag (optional)",
+            existing.and_then(|t| t.parent.clone()),
+        )?;
+        println!("Sections the tag may be used in, by number or title (empty for every section):");
+        for (i, title) in RUBRIC_SECTIONS.iter().enumerate() {
+            println!("{}. {}", i + 1, title);
+        }
+        let sections = prompt_field(
+            "Sections (comma-separated)",
+            existing.and_then(|t| list(&t.sections)),
+        )?;
+
+        let keep = |input: Option<String>, current: Option<String>| match input {
+            Some(ref value) if value == CLEAR_VALUE => None,
+            Some(value) => Some(value),
+            None => current,
+        };
+        let synonyms = keep(synonyms, existing.and_then(|t| list(&t.synonyms)));
+        let parent = keep(parent, existing.and_then(|t| t.parent.clone()));
+        let sections = keep(sections, existing.and_then(|t| list(&t.sections)));
+
+        let mut section_titles = Vec::new();
+        for section in sections.iter().flat_map(|s| s.split(',')) {
+            if section.trim().is_empty() {
+                continue;
+            }
+            match resolve_section_title(section) {
+                Some(title) if !section_titles.contains(&title.to_string()) => {
+                    section_titles.push(title.to_string())
+                }
+                Some(_) => {}
+                None => {
+                    println!("No rubric section matches '{}'", section.trim());
+                    return Ok(());
+                }
+            }
+        }
+
+        let tag = VocabularyTag {
+            id: None,
+            name: name.clone(),
+            synonyms: synonyms.map_or(Vec::new(), |s| parse_tags(&s)),
+            parent: parent
+                .map(|p| vocabulary.canonical(&p))
+                .filter(|p| !p.is_empty()),
+            sections: section_titles,
+        };
+
+        match self.db_manager.save_vocabulary_tag(tag).await {
+            Ok(true) => println!("Tag '{}' updated", name),
+            Ok(false) => println!("Tag '{}' added to the vocabulary", name),
+            Err(e) => println!("Tag not saved: {}", e),
+        }
+
+        Ok(())
+    }
+
+    async fn search_by_expression(&self) -> Result<(), Box<dyn std::error::Error>> {
+        println!("\nSearch terms: section, tag, rating, score, funder, institution, author,");
+        println!("created, reviewed, dataset, publication, software, dmp");
+        println!(
+            "Example: section:\"FAIR Principles\" rating>=4 tag:reuse funder:NSF -tag:incomplete"
+        );
+
+        print!("Search expression: ");
+        io::stdout().flush()?;
+        let mut expression = String::new();
+        io::stdin().read_line(&mut expression)?;
+        let expression = expression.trim_end_matches(['\r', '\n']);
+
+        match parse_query(expression, &self.scales) {
+            Ok(query) => {
+                self.run_query(&query).await?;
+                self.offer_to_save(&query, Some(expression)).await?;
+            }
+            Err(e) => {
+                println!("Invalid search expression: {}", e);
+                println!("{}", e.pointer(expression));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn search_text(&self) -> Result<(), Box<dyn std::error::Error>> {
+        print!("Search text (use \"quotes\" for phrases, -word to exclude): ");
+        io::stdout().flush()?;
+        let mut search = String::new();
+        io::stdin().read_line(&mut search)?;
+
+        if search.trim().is_empty() {
+            println!("Search text cannot be empty");
+            return Ok(());
+        }
+
+        let hits = self.db_manager.search_text(search.trim(), 20).await?;
+
+        println!(
+            "\nFound {} DMP(s) matching '{}':",
+            hits.len(),
+            search.trim()
+        );
+
+        for (i, hit) in hits.iter().enumerate() {
+            println!(
+                "{}. {} (ID: {}/{}) [relevance: {:.2}]",
+                i + 1,
+                hit.dmp.title,
+                hit.dmp.dmp_id.id_type,
+                hit.dmp.dmp_id.identifier,
+                hit.score
+            );
+
+            for snippet in &hit.snippets {
+                println!(
+                    "   {}: {}",
+                    snippet.field,
+                    snippet.highlighted("\x1b[1m", "\x1b[0m")
+                );
+            }
+
+            println!();
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints the DMPs found by `query`, with the queried section's rating and
+/// comments if the query targets a section.
+fn print_query_results(dmps: &[DataManagementPlan], query: &DmpQuery) {
+    println!("\nFound {} DMP(s) matching all criteria:", dmps.len());
+
+    for (i, dmp) in dmps.iter().enumerate() {
+        println!(
+            "{}. {} (ID: {}/{})",
+            i + 1,
+            dmp.title,
+            dmp.dmp_id.id_type,
+            dmp.dmp_id.identifier
+        );
+
+        if let Some(ref overall_rating) = dmp.overall_rating {
+            println!(
+                "   Overall rating: {}/5 by {}",
+                overall_rating.score, overall_rating.reviewer
+            );
+        }
+
+        if let Some(section) = query
+            .section_title()
+            .and_then(|title| dmp.sections.iter().find(|s| fold(&s.title) == fold(title)))
+        {
+            if let Some(section_rating) = section.rating {
+                println!("   Section rating: {}", section_rating);
+            }
+            if let Some(ref comments) = section.comments {
+                println!("   Comments: {}", comments);
+            }
+        }
+
+        println!();
+    }
+}
+
+/// Lists the sections of `dmp` with their ratings, numbering subsections
+/// after their section, e.g. `2.1`.
+fn print_numbered_sections(dmp: &DataManagementPlan) {
+    let show_rating =
+        |rating: Option<Rating>| rating.map_or("Not rated".to_string(), |r| r.to_string());
+
+    for (i, section) in dmp.sections.iter().enumerate() {
+        println!(
+            "{}. {} (Current rating: {})",
+            i + 1,
+            section.title,
+            show_rating(section.rating)
+        );
+        for (j, subsection) in section.subsections.iter().enumerate() {
+            println!(
+                "   {}.{}. {} (Current rating: {})",
+                i + 1,
+                j + 1,
+                subsection.title,
+                show_rating(subsection.rating)
+            );
+        }
+    }
+}
+
+/// Prints `tag` with its synonyms and sections, followed by the tags below
+/// it, indented by `depth`.
+fn print_vocabulary_tag(vocabulary: &TagVocabulary, tag: &VocabularyTag, depth: usize) {
+    let mut line = format!("{}- {}", "   ".repeat(depth), tag.name);
+    if !tag.synonyms.is_empty() {
+        line.push_str(&format!(" (also: {})", tag.synonyms.join(", ")));
+    }
+    if !tag.sections.is_empty() {
+        line.push_str(&format!(" [{}]", tag.sections.join("; ")));
+    }
+    println!("{}", line);
+
+    for child in vocabulary.children(&tag.name) {
+        print_vocabulary_tag(vocabulary, child, depth + 1);
+    }
+}
+
+/// Resolves a number listed by `print_numbered_sections` to the path of
+/// its section or subsection.
+fn numbered_section_path(
+    dmp: &DataManagementPlan,
+    input: &str,
+) -> Result<SectionPath, &'static str> {
+    let (section_number, subsection_number) = match input.trim().split_once('.') {
+        Some((section, subsection)) => (section, Some(subsection)),
+        None => (input.trim(), None),
+    };
+    let section = section_number
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|i| dmp.sections.get(i))
+        .ok_or("Invalid section number")?;
+
+    match subsection_number {
+        Some(number) => {
+            let subsection = number
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|j| section.subsections.get(j))
+                .ok_or("Invalid subsection number")?;
+            Ok(SectionPath::subsection(&section.title, &subsection.title))
+        }
+        None => Ok(SectionPath::section(&section.title)),
+    }
+}
+
+/// Prints `text` under a `label:` line, indenting every line by `indent` spaces.
+fn print_indented(label: &str, text: &str, indent: usize) {
+    let padding = " ".repeat(indent);
+    println!("{}{}:", padding, label);
+    for line in text.lines() {
+        println!("{}  {}", padding, line);
+    }
+}
+
+/// Prints everything stored for a DMP: metadata, overall rating, and every
+/// section and subsection with its text, rating, tags and comments.
+fn print_dmp_details(dmp: &DataManagementPlan) {
+    println!("\n{}", dmp.title);
+    println!("{}", "=".repeat(dmp.title.chars().count()));
+    println!("ID: {}/{}", dmp.dmp_id.id_type, dmp.dmp_id.identifier);
+    println!("Version: {}", dmp.version);
+    println!("Created: {}", dmp.created_date);
+    println!("Last modified: {}", dmp.last_modified);
+
+    if !dmp.authors.is_empty() {
+        println!("Authors:");
+        for author in &dmp.authors {
+            let mut line = format!("   {}", author.name);
+            if let Some(ref affiliation) = author.affiliation {
+                line.push_str(&format!(" ({})", affiliation));
+            }
+            if let Some(ref identifier) = author.identifier {
+                line.push_str(&format!(" [{}]", identifier));
+            }
+            println!("{}", line);
+        }
+    }
+
+    if let Some(ref project) = dmp.project_info {
+        println!("Project: {}", project.project_title);
+        if let Some(ref funder) = project.funder {
+            println!("   Funder: {}", funder);
+        }
+        if let Some(ref grant_id) = project.grant_id {
+            println!("   Grant ID: {}", grant_id);
+        }
+        if let Some(ref institution) = project.institution {
+            println!("   Institution: {}", institution);
+        }
+    }
+
+    let related = related_entities(dmp);
+    if !related.is_empty() {
+        println!("Related entities:");
+        for entity in &related {
+            println!("   {}", entity);
+        }
+    }
+
+    if let Some(ref overall_rating) = dmp.overall_rating {
+        println!(
+            "Overall rating: {}/5 by {} on {}",
+            overall_rating.score, overall_rating.reviewer, overall_rating.review_date
+        );
+        if !overall_rating.overall_tags.is_empty() {
+            println!("   Tags: {}", overall_rating.overall_tags.join(", "));
+        }
+        if !overall_rating.comments.is_empty() {
+            println!("   Comments: {}", overall_rating.comments);
+        }
+    }
+
+    for (i, section) in dmp.sections.iter().enumerate() {
+        println!(
+            "\n{}. {} (Rating: {})",
+            i + 1,
+            section.title,
+            section
+                .rating
+                .map_or("Not rated".to_string(), |r| r.to_string())
+        );
+        if let Some(ref text) = section.text {
+            print_indented("Text", text, 3);
+        }
+        if !section.tags.is_empty() {
+            println!("   Tags: {}", section.tags.join(", "));
+        }
+        if let Some(ref comments) = section.comments {
+            println!("   Comments: {}", comments);
+        }
+        if let Some(ref cross_references) = section.cross_references {
+            println!("   Cross-references:");
+            for reference in cross_references {
+                let reference_type = reference
+                    .reference_type
+                    .map_or("references".to_string(), |t| t.to_string());
+                let target = reference.target_dmp.as_ref().map_or(String::new(), |t| {
+                    format!(" in DMP {}/{}", t.id_type, t.identifier)
+                });
+                println!(
+                    "      {} '{}'{}: {}",
+                    reference_type, reference.section_title, target, reference.reference_note
+                );
+            }
+        }
+        if !section.annotations.is_empty() {
+            println!("   Annotations:");
+            for (j, annotation) in section.annotations.iter().enumerate() {
+                let passage = section
+                    .text
+                    .as_deref()
+                    .and_then(|text| annotation.passage(text))
+                    .map_or("(passage not found)".to_string(), |p| format!("\"{}\"", p));
+                let tags = if annotation.tags.is_empty() {
+                    String::new()
+                } else {
+                    format!(" [{}]", annotation.tags.join(", "))
+                };
+                println!(
+                    "      [{}] {} - {}{}: {}",
+                    j + 1,
+                    passage,
+                    annotation.author,
+                    tags,
+                    annotation.comment
+                );
+            }
+        }
+
+        for (j, subsection) in section.subsections.iter().enumerate() {
+            println!(
+                "   {}.{}. {} (Rating: {})",
+                i + 1,
+                j + 1,
+                subsection.title,
+                subsection
+                    .rating
+                    .map_or("Not rated".to_string(), |r| r.to_string())
+            );
+            if let Some(ref text) = subsection.text {
+                print_indented("Text", text, 6);
+            }
+            if !subsection.tags.is_empty() {
+                println!("      Tags: {}", subsection.tags.join(", "));
+            }
+            if let Some(ref comments) = subsection.comments {
+                println!("      Comments: {}", comments);
+            }
+        }
+    }
 }
@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{Error as IoError, ErrorKind};
+use std::sync::Arc;
+
+use crate::dashboard::write_dashboard;
+use crate::db::DatabaseManager;
+use crate::identifiers::find_related_entities;
+use crate::query::DmpQuery;
+use crate::query_parser::parse_query;
+use crate::related::{build_related_entity, related_entities, RelatedEntityFields};
+use crate::server::{self, DEFAULT_ADDRESS};
+use crate::trends::{Period, TrendDate};
+use crate::RelationType;
+
+pub const USAGE: &str = "Usage:
+  essay_indexer                                Start the interactive menu
+  essay_indexer related list   --dmp ID --dmp-type TYPE
+  essay_indexer related add    --dmp ID --dmp-type TYPE --entity KIND --id ID --id-type TYPE
+                               --relationship TYPE --title TITLE [FIELDS]
+  essay_indexer related edit   --dmp ID --dmp-type TYPE --entity KIND --id ID [--new-id ID]
+                               [--id-type TYPE] [--relationship TYPE] [--title TITLE] [FIELDS]
+  essay_indexer related remove --dmp ID --dmp-type TYPE --entity KIND --id ID
+  essay_indexer graph --id ID [--entity KIND] [--hops N] [--relation TYPE,...]
+                      [--format text|dot|graphml] [--output PATH]
+  essay_indexer migrate [--dry-run]
+  essay_indexer check-scales
+  essay_indexer rename-tag --from TAG --to TAG
+  essay_indexer analytics [--query EXPRESSION] [--top N]
+  essay_indexer trends [--by month|quarter|year] [--date created|reviewed]
+                       [--funder NAME] [--institution NAME] [--csv PATH]
+  essay_indexer dashboard [--output DIRECTORY]
+  essay_indexer serve [--address HOST:PORT]
+
+KIND is one of dataset, publication, software, dmp. FIELDS are --repository and
+--url (dataset), --authors \"A; B\", --journal, --date YYYY-MM-DD and --url
+(publication), --version and --url (software), and --notes (dmp). When editing,
+omitted fields keep their value and a value of - clears an optional field.
+
+graph walks related entity entries from a DMP or research output (2 hops by
+default) and prints the graph, or writes it to PATH. Without --format, PATH is
+written as GraphML if it ends in .graphml or .xml and as DOT otherwise.
+
+migrate upgrades DMPs stored by earlier versions to the current schema, listing
+what each migration rewrote and the values it did not recognize. It also runs
+when the application starts; --dry-run only reports what would change.
+
+check-scales lists stored ratings that are not on the scale rubric_scales.json
+gives their section, e.g. ratings entered before the section's scale changed.
+They are kept as they are; a reviewer rates the section again to replace them.
+
+rename-tag renames a tag in the tag vocabulary and in every DMP, merging it into
+the other tag if TAG after --to already exists. The old name is kept as a
+synonym.
+
+analytics summarizes section ratings (mean, median and histogram per section),
+the N most frequent tags per section (5 by default), tags used together and
+DMPs by funder and institution. EXPRESSION limits it to the DMPs matching a
+search expression, e.g. \"funder:NSF created>=2024-01-01\".
+
+trends charts the mean overall score and the mean rating of each section (as a
+percentage of its scale) per quarter of the date DMPs were created, or per
+month or year with --by, or of their latest review with --date reviewed.
+--funder and --institution limit it to the DMPs of a funder or institution;
+--csv writes the figures to PATH instead.
+
+dashboard writes a static HTML site for viewing offline to DIRECTORY
+(dashboard by default): summary statistics and charts, sortable tables of
+funders and DMPs, a page per funder and the report of every DMP.
+
+serve answers JSON requests on HOST:PORT (127.0.0.1:8080 by default) to list,
+add, replace, delete and search DMPs, update their ratings and import and
+export them. /openapi.json describes every endpoint. /ui lists DMPs and
+drafts, and /ui/form rates a DMP in the browser.";
+
+/// Options accepted by `related` actions.
+const RELATED_OPTIONS: [&str; 16] = [
+    "dmp",
+    "dmp-type",
+    "entity",
+    "id",
+    "new-id",
+    "id-type",
+    "relationship",
+    "title",
+    "name",
+    "repository",
+    "url",
+    "authors",
+    "journal",
+    "date",
+    "version",
+    "notes",
+];
+
+/// Options accepted by `graph`.
+const GRAPH_OPTIONS: [&str; 6] = ["id", "entity", "hops", "relation", "format", "output"];
+
+/// Options accepted by `rename-tag`.
+const RENAME_TAG_OPTIONS: [&str; 2] = ["from", "to"];
+
+/// Options accepted by `analytics`.
+const ANALYTICS_OPTIONS: [&str; 2] = ["query", "top"];
+
+/// Options accepted by `trends`.
+const TRENDS_OPTIONS: [&str; 5] = ["by", "date", "funder", "institution", "csv"];
+
+/// Options accepted by `dashboard`.
+const DASHBOARD_OPTIONS: [&str; 1] = ["output"];
+
+/// Options accepted by `serve`.
+const SERVE_OPTIONS: [&str; 1] = ["address"];
+
+fn usage_error(message: String) -> Box<dyn Error> {
+    Box::new(IoError::new(
+        ErrorKind::InvalidInput,
+        format!("{}\n\n{}", message, USAGE),
+    ))
+}
+
+/// Collects `--name value` pairs, rejecting unknown, stray and repeated
+/// arguments.
+fn parse_options(
+    args: &[String],
+    known: &[&str],
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut options = HashMap::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let name = arg
+            .strip_prefix("--")
+            .ok_or_else(|| usage_error(format!("Unexpected argument '{}'", arg)))?;
+        if !known.contains(&name) {
+            return Err(usage_error(format!("Unknown option --{}", name)));
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| usage_error(format!("Missing value for --{}", name)))?;
+        if options.insert(name.to_string(), value.clone()).is_some() {
+            return Err(usage_error(format!("--{} is given more than once", name)));
+        }
+    }
+
+    Ok(options)
+}
+
+/// Takes a required option out of `options`.
+fn required(options: &mut HashMap<String, String>, name: &str) -> Result<String, Box<dyn Error>> {
+    options
+        .remove(name)
+        .ok_or_else(|| usage_error(format!("--{} is required", name)))
+}
+
+/// Runs the command given on the command line.
+pub async fn run(db_manager: &Arc<DatabaseManager>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    match args.first().map(String::as_str) {
+        Some("related") => related(db_manager, &args[1..]).await,
+        Some("graph") => graph(db_manager, &args[1..]).await,
+        Some("migrate") => migrate(db_manager, &args[1..]).await,
+        Some("check-scales") => check_scales(db_manager, &args[1..]).await,
+        Some("rename-tag") => rename_tag(db_manager, &args[1..]).await,
+        Some("analytics") => analytics(db_manager, &args[1..]).await,
+        Some("trends") => trends(db_manager, &args[1..]).await,
+        Some("dashboard") => dashboard(db_manager, &args[1..]).await,
+        Some("serve") => serve(db_manager, &args[1..]).await,
+        Some("help") | Some("--help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(command) => Err(usage_error(format!("Unknown command '{}'", command))),
+        None => Err(usage_error("Missing command".to_string())),
+    }
+}
+
+/// `related list|add|edit|remove`: manages the related entities of a DMP.
+async fn related(db_manager: &DatabaseManager, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let action = args
+        .first()
+        .ok_or_else(|| usage_error("Missing related action".to_string()))?;
+    let mut options = parse_options(&args[1..], &RELATED_OPTIONS)?;
+    let dmp_id = required(&mut options, "dmp")?;
+    let id_type = required(&mut options, "dmp-type")?;
+
+    let dmp = db_manager
+        .find_dmp_by_id(&dmp_id, &id_type)
+        .await?
+        .ok_or_else(|| {
+            IoError::new(
+                ErrorKind::NotFound,
+                format!("DMP {}/{} not found", id_type, dmp_id),
+            )
+        })?;
+
+    if action == "list" {
+        for entity in related_entities(&dmp) {
+            println!("{}", entity);
+        }
+        return Ok(());
+    }
+
+    let entity_type = required(&mut options, "entity")?.to_lowercase();
+    let entity_id = required(&mut options, "id")?;
+
+    let changed = match action.as_str() {
+        "add" => {
+            let fields = related_fields(&mut options, Some(entity_id.clone()));
+            let entity = build_related_entity(&entity_type, fields, None)
+                .map_err(|e| IoError::new(ErrorKind::InvalidInput, e))?;
+            if let Some(note) = entity.custom_relationship_note() {
+                eprintln!("{}", note);
+            }
+            db_manager
+                .add_related_entity(&dmp_id, &id_type, &entity)
+                .await?
+        }
+        "edit" => {
+            let existing = find_related_entities(&dmp, &entity_type, &entity_id)
+                .into_iter()
+                .next()
+                .ok_or_else(|| {
+                    IoError::new(
+                        ErrorKind::NotFound,
+                        format!(
+                            "DMP {}/{} does not list the {} {}",
+                            id_type, dmp_id, entity_type, entity_id
+                        ),
+                    )
+                })?;
+            let new_id = options.remove("new-id");
+            let fields = related_fields(&mut options, new_id);
+            let entity = build_related_entity(&entity_type, fields, Some(existing))
+                .map_err(|e| IoError::new(ErrorKind::InvalidInput, e))?;
+            if let Some(note) = entity.custom_relationship_note() {
+                eprintln!("{}", note);
+            }
+            db_manager
+                .update_related_entity(&dmp_id, &id_type, &entity_type, &entity_id, &entity)
+                .await?
+        }
+        "remove" => {
+            db_manager
+                .remove_related_entity(&dmp_id, &id_type, &entity_type, &entity_id)
+                .await?
+        }
+        _ => return Err(usage_error(format!("Unknown related action '{}'", action))),
+    };
+
+    if changed {
+        let done = match action.as_str() {
+            "add" => "attached",
+            "edit" => "updated",
+            _ => "removed",
+        };
+        println!("Related {} {} {}", entity_type, entity_id, done);
+    } else {
+        println!("No changes were made");
+    }
+
+    Ok(())
+}
+
+/// `graph`: prints or exports the relationship graph around an identifier.
+async fn graph(db_manager: &DatabaseManager, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut options = parse_options(args, &GRAPH_OPTIONS)?;
+    let identifier = required(&mut options, "id")?;
+    let entity_type = options.remove("entity").map(|e| e.to_lowercase());
+    let hops = match options.remove("hops") {
+        Some(hops) => hops
+            .parse::<usize>()
+            .map_err(|_| usage_error(format!("'{}' is not a valid number of hops", hops)))?,
+        None => 2,
+    };
+    let relations: Vec<RelationType> = options
+        .remove("relation")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|r| !r.trim().is_empty())
+        .map(|r| RelationType::from(r.to_string()))
+        .collect();
+    let format = options.remove("format");
+    if let Some(ref format) = format {
+        if !["dot", "graphml", "text"].contains(&format.as_str()) {
+            return Err(usage_error(format!("Unknown format '{}'", format)));
+        }
+    }
+
+    let graph = db_manager
+        .relation_graph(entity_type.as_deref(), &identifier, hops, &relations)
+        .await?;
+    let rendered = match format.as_deref() {
+        Some("dot") => Some(graph.to_dot()),
+        Some("graphml") => Some(graph.to_graphml()),
+        Some(_) => Some(graph.to_text()),
+        None => None,
+    };
+
+    if let Some(path) = options.remove("output") {
+        // Without --format, the file extension chooses the format
+        match rendered {
+            Some(rendered) => fs::write(&path, rendered)?,
+            None => graph.write(&path)?,
+        }
+        println!(
+            "Graph with {} nodes and {} relationships written to {}",
+            graph.nodes.len(),
+            graph.edges.len(),
+            path
+        );
+        return Ok(());
+    }
+
+    print!("{}", rendered.unwrap_or_else(|| graph.to_text()));
+    Ok(())
+}
+
+/// `migrate`: upgrades stored DMPs to the current schema version.
+async fn migrate(db_manager: &DatabaseManager, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut dry_run = false;
+    for arg in args {
+        match arg.as_str() {
+            "--dry-run" if !dry_run => dry_run = true,
+            "--dry-run" => {
+                return Err(usage_error("--dry-run is given more than once".to_string()))
+            }
+            _ => return Err(usage_error(format!("Unexpected argument '{}'", arg))),
+        }
+    }
+
+    let run = db_manager.run_migrations(dry_run).await?;
+    print!("{}", run.to_text());
+    if dry_run {
+        println!("Dry run: no changes were made");
+    }
+
+    Ok(())
+}
+
+/// `check-scales`: lists stored ratings off the scale of their section.
+async fn check_scales(db_manager: &DatabaseManager, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if let Some(arg) = args.first() {
+        return Err(usage_error(format!("Unexpected argument '{}'", arg)));
+    }
+
+    let off_scale = db_manager.off_scale_ratings().await?;
+    if off_scale.is_empty() {
+        println!("Every stored rating is on the scale of its section");
+        return Ok(());
+    }
+
+    println!(
+        "{} rating(s) are not on the scale of their section and were kept for review:",
+        off_scale.len()
+    );
+    for (dmp, off) in off_scale {
+        println!(
+            "  {}: {} is {} on the {} scale; the section is rated {}",
+            dmp,
+            off.path(),
+            off.rating,
+            off.rating.scale(),
+            off.scale
+        );
+    }
+
+    Ok(())
+}
+
+/// `rename-tag`: renames or merges a tag across the vocabulary and all DMPs.
+async fn rename_tag(db_manager: &DatabaseManager, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut options = parse_options(args, &RENAME_TAG_OPTIONS)?;
+    let from = required(&mut options, "from")?;
+    let to = required(&mut options, "to")?;
+
+    let rename = db_manager.rename_tag(&from, &to).await?;
+    print!("{}", rename.to_text());
+
+    Ok(())
+}
+
+/// `analytics`: summarizes the ratings and tags of the DMPs matching a search.
+async fn analytics(db_manager: &DatabaseManager, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut options = parse_options(args, &ANALYTICS_OPTIONS)?;
+    let query = match options.remove("query") {
+        Some(expression) => parse_query(&expression, db_manager.scales()).map_err(|e| {
+            usage_error(format!(
+                "Invalid search expression: {}\n{}",
+                e,
+                e.pointer(&expression)
+            ))
+        })?,
+        None => DmpQuery::new(),
+    };
+    let top = match options.remove("top") {
+        Some(top) => top
+            .parse::<usize>()
+            .ok()
+            .filter(|top| *top > 0)
+            .ok_or_else(|| usage_error(format!("'{}' is not a valid number of tags", top)))?,
+        None => 5,
+    };
+
+    let analytics = db_manager.analytics(&query, top).await?;
+    print!("{}", analytics.to_text());
+
+    Ok(())
+}
+
+/// `trends`: charts scores and section ratings over time, or writes them as
+/// CSV.
+async fn trends(db_manager: &DatabaseManager, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut options = parse_options(args, &TRENDS_OPTIONS)?;
+    let period = match options.remove("by") {
+        Some(period) => period.parse::<Period>().map_err(usage_error)?,
+        None => Period::default(),
+    };
+    let date = match options.remove("date") {
+        Some(date) => date.parse::<TrendDate>().map_err(usage_error)?,
+        None => TrendDate::default(),
+    };
+    let mut query = DmpQuery::new();
+    if let Some(funder) = options.remove("funder") {
+        query = query.funder(&funder);
+    }
+    if let Some(institution) = options.remove("institution") {
+        query = query.institution(&institution);
+    }
+
+    let trends = db_manager.trends(&query, date, period).await?;
+    match options.remove("csv") {
+        Some(file_path) => {
+            trends.write_csv(&file_path)?;
+            println!("Wrote {} period(s) to {}", trends.points.len(), file_path);
+        }
+        None => print!("{}", trends.to_chart()),
+    }
+
+    Ok(())
+}
+
+/// `dashboard`: writes a static HTML dashboard of every DMP.
+async fn dashboard(db_manager: &DatabaseManager, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut options = parse_options(args, &DASHBOARD_OPTIONS)?;
+    let directory = options
+        .remove("output")
+        .unwrap_or_else(|| "dashboard".to_string());
+
+    let dmps = db_manager.all_dmps().await?;
+    let pages = write_dashboard(&dmps, &directory)?;
+    println!(
+        "Wrote {} page(s) for {} DMP(s); open {}/index.html",
+        pages,
+        dmps.len(),
+        directory.trim_end_matches('/')
+    );
+
+    Ok(())
+}
+
+/// `serve`: serves the JSON API until the process is stopped.
+async fn serve(db_manager: &Arc<DatabaseManager>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut options = parse_options(args, &SERVE_OPTIONS)?;
+    let address = options
+        .remove("address")
+        .unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let scales = db_manager.scales().clone();
+
+    server::serve(Arc::clone(db_manager), scales, &address).await
+}
+
+/// Takes the related entity fields out of `options`.
+fn related_fields(
+    options: &mut HashMap<String, String>,
+    identifier: Option<String>,
+) -> RelatedEntityFields {
+    RelatedEntityFields {
+        identifier,
+        id_type: options.remove("id-type"),
+        relationship: options.remove("relationship"),
+        title: options.remove("title").or_else(|| options.remove("name")),
+        repository: options.remove("repository"),
+        url: options.remove("url"),
+        authors: options.remove("authors"),
+        journal: options.remove("journal"),
+        publication_date: options.remove("date"),
+        version: options.remove("version"),
+        notes: options.remove("notes"),
+    }
+}
@@ -0,0 +1,87 @@
+use mongodb::bson::DateTime;
+use thiserror::Error;
+
+use crate::query::SectionPath;
+use crate::{DataManagementPlan, Rating};
+
+/// An update was based on a read of a DMP that has since been changed by
+/// someone else, and was not applied.
+#[derive(Debug, Error)]
+#[error(
+    "DMP {}/{} was changed at {} after it was read at {}; the update was not applied",
+    .current.dmp_id.id_type, .current.dmp_id.identifier, .current.last_modified, .read_at
+)]
+pub struct ConflictError {
+    /// `last_modified` of the DMP as it was read
+    pub read_at: DateTime,
+    /// The DMP as it is stored now
+    pub current: DataManagementPlan,
+}
+
+/// One field of a three-way merge: as read, as stored now and as changed.
+#[derive(Debug)]
+pub struct MergeField {
+    pub name: String,
+    pub base: String,
+    pub current: String,
+    pub mine: String,
+}
+
+impl MergeField {
+    /// `true` if both the stored value and the change differ from the value
+    /// read, and from each other.
+    pub fn conflicts(&self) -> bool {
+        self.current != self.base && self.mine != self.base && self.mine != self.current
+    }
+}
+
+/// The merge fields of a rating update of the section or subsection at
+/// `path`: `base` is the DMP as read, `current` as stored now.
+pub fn section_merge_fields(
+    path: &SectionPath,
+    base: &DataManagementPlan,
+    current: &DataManagementPlan,
+    rating: Rating,
+    comments: &str,
+) -> Vec<MergeField> {
+    let show_rating =
+        |rating: Option<Rating>| rating.map_or("Not rated".to_string(), |r| r.to_string());
+    let missing = "(removed)".to_string();
+    let read = path.rating_and_comments(base);
+    let stored = path.rating_and_comments(current);
+
+    vec![
+        MergeField {
+            name: format!("{}: rating", path),
+            base: read
+                .as_ref()
+                .map_or(missing.clone(), |(r, _)| show_rating(*r)),
+            current: stored
+                .as_ref()
+                .map_or(missing.clone(), |(r, _)| show_rating(*r)),
+            mine: rating.to_string(),
+        },
+        MergeField {
+            name: format!("{}: comments", path),
+            base: read.map_or(missing.clone(), |(_, c)| c.unwrap_or_default()),
+            current: stored.map_or(missing, |(_, c)| c.unwrap_or_default()),
+            mine: comments.to_string(),
+        },
+    ]
+}
+
+/// Renders the fields side by side: as read, as stored now and as changed,
+/// marking those changed both by someone else and in the update with `!`.
+pub fn merge_view(fields: &[MergeField]) -> String {
+    let mut view = String::new();
+
+    for field in fields {
+        let marker = if field.conflicts() { "!" } else { " " };
+        view.push_str(&format!("{} {}\n", marker, field.name));
+        view.push_str(&format!("     When read:  {}\n", field.base));
+        view.push_str(&format!("     Stored now: {}\n", field.current));
+        view.push_str(&format!("     Your value: {}\n", field.mine));
+    }
+
+    view
+}
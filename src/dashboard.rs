@@ -0,0 +1,421 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use mongodb::bson::DateTime;
+
+use crate::matching::fold;
+use crate::report::{escape_html, render_dmp_report};
+use crate::{DataManagementPlan, RUBRIC_SECTIONS};
+
+const DASHBOARD_STYLE: &str = "body { font-family: sans-serif; max-width: 70em; margin: 2em auto; }
+.meta { color: #555; }
+.stats { display: flex; gap: 2em; list-style: none; padding: 0; }
+.stats strong { display: block; font-size: 1.6em; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.3em 0.6em; border-bottom: 1px solid #ddd; }
+th { cursor: pointer; background: #f4f4f4; }
+th[data-order=asc]::after { content: \" \\25B2\"; }
+th[data-order=desc]::after { content: \" \\25BC\"; }
+td.number { text-align: right; }
+svg text { font-size: 12px; }";
+
+/// Sorts a table with class `sortable` by the column whose header is
+/// clicked, comparing cells by their `data-sort` value if they have one.
+const SORT_SCRIPT: &str = "function sortKey(cell) {
+  var value = cell.dataset.sort !== undefined ? cell.dataset.sort : cell.textContent.trim();
+  return value !== \"\" && isFinite(value) ? parseFloat(value) : value.toLowerCase();
+}
+document.querySelectorAll(\"table.sortable th\").forEach(function (th) {
+  th.addEventListener(\"click\", function () {
+    var table = th.closest(\"table\");
+    var body = table.tBodies[0];
+    var ascending = th.dataset.order !== \"asc\";
+    table.querySelectorAll(\"th\").forEach(function (h) { delete h.dataset.order; });
+    th.dataset.order = ascending ? \"asc\" : \"desc\";
+    var rows = Array.from(body.rows);
+    rows.sort(function (a, b) {
+      var x = sortKey(a.cells[th.cellIndex]);
+      var y = sortKey(b.cells[th.cellIndex]);
+      var order = typeof x === \"number\" && typeof y === \"number\"
+        ? x - y
+        : String(x).localeCompare(String(y));
+      return ascending ? order : -order;
+    });
+    rows.forEach(function (row) { body.appendChild(row); });
+  });
+});";
+
+/// Width in pixels of the labels beside the bars of a chart.
+const CHART_LABEL_WIDTH: usize = 260;
+/// Width in pixels of a full-length bar.
+const CHART_BAR_WIDTH: usize = 400;
+const CHART_ROW_HEIGHT: usize = 24;
+
+/// A DMP and the page its drill-down is written to.
+struct Entry<'a> {
+    dmp: &'a DataManagementPlan,
+    page: String,
+}
+
+/// The DMPs of one funder and the page listing them.
+struct Funder<'a> {
+    name: String,
+    page: String,
+    entries: Vec<&'a Entry<'a>>,
+}
+
+/// Figures shown at the top of the index and every funder page.
+struct Summary {
+    dmps: usize,
+    reviewed: usize,
+    mean_score: Option<f64>,
+    /// Number of overall scores rounding to 1 through 5
+    scores: [usize; 5],
+    /// Title, number of rated sections and mean rating as a fraction of its
+    /// scale of every section, rubric sections first
+    sections: Vec<(String, usize, Option<f64>)>,
+}
+
+impl Summary {
+    fn of(entries: &[&Entry]) -> Self {
+        let scores: Vec<f64> = entries
+            .iter()
+            .filter_map(|e| e.dmp.overall_rating.as_ref())
+            .map(|r| r.score as f64)
+            .collect();
+        let mut histogram = [0; 5];
+        for score in &scores {
+            histogram[(score.round().clamp(1.0, 5.0) as usize) - 1] += 1;
+        }
+
+        let mut titles: Vec<String> = RUBRIC_SECTIONS.iter().map(|t| t.to_string()).collect();
+        let mut others: Vec<String> = entries
+            .iter()
+            .flat_map(|e| &e.dmp.sections)
+            .map(|s| s.title.clone())
+            .filter(|t| !titles.iter().any(|r| fold(r) == fold(t)))
+            .collect();
+        others.sort();
+        others.dedup_by(|a, b| fold(a) == fold(b));
+        titles.extend(others);
+
+        let sections = titles
+            .into_iter()
+            .map(|title| {
+                let fractions: Vec<f64> = entries
+                    .iter()
+                    .flat_map(|e| &e.dmp.sections)
+                    .filter(|s| fold(&s.title) == fold(&title))
+                    .filter_map(|s| s.rating.and_then(|r| r.fraction()))
+                    .collect();
+                (title, fractions.len(), mean(&fractions))
+            })
+            .collect();
+
+        Self {
+            dmps: entries.len(),
+            reviewed: scores.len(),
+            mean_score: mean(&scores),
+            scores: histogram,
+            sections,
+        }
+    }
+
+    fn to_html(&self, funders: Option<usize>) -> String {
+        let mut html = String::from("<ul class=\"stats\">\n");
+        html.push_str(&stat("DMPs", self.dmps.to_string()));
+        html.push_str(&stat("Reviewed", self.reviewed.to_string()));
+        html.push_str(&stat(
+            "Mean overall score",
+            self.mean_score
+                .map_or("-".to_string(), |s| format!("{:.2}/5", s)),
+        ));
+        if let Some(funders) = funders {
+            html.push_str(&stat("Funders", funders.to_string()));
+        }
+        html.push_str("</ul>\n");
+
+        html.push_str("<h2>Overall scores</h2>\n");
+        let most = self.scores.iter().copied().max().unwrap_or(0).max(1);
+        let bars: Vec<(String, f64, String)> = self
+            .scores
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                (
+                    (i + 1).to_string(),
+                    *count as f64 / most as f64,
+                    count.to_string(),
+                )
+            })
+            .collect();
+        html.push_str(&bar_chart(&bars));
+
+        html.push_str("<h2>Mean section ratings</h2>\n");
+        let bars: Vec<(String, f64, String)> = self
+            .sections
+            .iter()
+            .filter(|(_, rated, _)| *rated > 0)
+            .map(|(title, rated, mean)| {
+                let mean = mean.unwrap_or(0.0);
+                (
+                    title.clone(),
+                    mean,
+                    format!("{:.0}% ({} rated)", mean * 100.0, rated),
+                )
+            })
+            .collect();
+        if bars.is_empty() {
+            html.push_str("<p class=\"meta\">No sections are rated yet.</p>\n");
+        } else {
+            html.push_str(&bar_chart(&bars));
+        }
+
+        html
+    }
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+fn stat(label: &str, value: String) -> String {
+    format!(
+        "<li><strong>{}</strong>{}</li>\n",
+        escape_html(&value),
+        label
+    )
+}
+
+/// Renders horizontal bars as inline SVG. Each bar is a label, its length
+/// as a fraction of the full width and the text shown after it.
+fn bar_chart(bars: &[(String, f64, String)]) -> String {
+    let width = CHART_LABEL_WIDTH + CHART_BAR_WIDTH + 120;
+    let height = bars.len() * CHART_ROW_HEIGHT;
+    let mut svg = format!(
+        "<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" role=\"img\">\n",
+        width, height, width, height
+    );
+
+    for (i, (label, fraction, text)) in bars.iter().enumerate() {
+        let y = i * CHART_ROW_HEIGHT;
+        let length = (fraction.clamp(0.0, 1.0) * CHART_BAR_WIDTH as f64).round() as usize;
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>\n",
+            CHART_LABEL_WIDTH - 8,
+            y + 16,
+            escape_html(label)
+        ));
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#4a7fb5\"><title>{}: {}</title></rect>\n",
+            CHART_LABEL_WIDTH,
+            y + 4,
+            length,
+            CHART_ROW_HEIGHT - 8,
+            escape_html(label),
+            escape_html(text)
+        ));
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\">{}</text>\n",
+            CHART_LABEL_WIDTH + length + 6,
+            y + 16,
+            escape_html(text)
+        ));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// `date` as YYYY-MM-DD.
+fn show_date(date: DateTime) -> String {
+    date.try_to_rfc3339_string()
+        .map(|d| d.chars().take(10).collect())
+        .unwrap_or_default()
+}
+
+/// A file name for a page about `name`, distinct from those in `taken`.
+fn page_name(name: &str, taken: &mut HashSet<String>) -> String {
+    let mut slug = String::new();
+    for c in fold(name).chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let mut slug: String = slug.trim_end_matches('-').chars().take(60).collect();
+    if slug.is_empty() {
+        slug.push_str("page");
+    }
+
+    let mut page = format!("{}.html", slug);
+    let mut n = 2;
+    while !taken.insert(page.clone()) {
+        page = format!("{}-{}.html", slug, n);
+        n += 1;
+    }
+    page
+}
+
+/// A sortable table of DMPs linking to their drill-down pages, which are in
+/// `dmps/` under `root` relative to the page showing the table.
+fn dmp_table(entries: &[&Entry], root: &str) -> String {
+    let mut html = String::from(
+        "<table class=\"sortable\">\n<thead><tr><th>Title</th><th>ID</th><th>Funder</th><th>Institution</th><th>Created</th><th>Overall score</th><th>Sections rated</th></tr></thead>\n<tbody>\n",
+    );
+
+    for entry in entries {
+        let dmp = entry.dmp;
+        let project = dmp.project_info.as_ref();
+        let rated = dmp.sections.iter().filter(|s| s.rating.is_some()).count();
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}dmps/{}\">{}</a></td><td>{}/{}</td><td>{}</td><td>{}</td><td>{}</td>",
+            root,
+            entry.page,
+            escape_html(&dmp.title),
+            escape_html(dmp.dmp_id.id_type.as_str()),
+            escape_html(&dmp.dmp_id.identifier),
+            escape_html(project.and_then(|p| p.funder.as_deref()).unwrap_or("")),
+            escape_html(project.and_then(|p| p.institution.as_deref()).unwrap_or("")),
+            show_date(dmp.created_date)
+        ));
+        match dmp.overall_rating {
+            Some(ref rating) => {
+                html.push_str(&format!("<td class=\"number\">{}</td>", rating.score))
+            }
+            // Unreviewed DMPs sort below every score
+            None => html.push_str("<td class=\"number\" data-sort=\"-1\">-</td>"),
+        }
+        html.push_str(&format!(
+            "<td class=\"number\" data-sort=\"{}\">{}/{}</td></tr>\n",
+            rated,
+            rated,
+            dmp.sections.len()
+        ));
+    }
+
+    html.push_str("</tbody>\n</table>\n");
+    html
+}
+
+/// Wraps `body` in a standalone HTML page.
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n{}<script>\n{}\n</script>\n</body>\n</html>\n",
+        escape_html(title),
+        DASHBOARD_STYLE,
+        body,
+        SORT_SCRIPT
+    )
+}
+
+/// Writes a static HTML dashboard of `dmps` to `directory`: an index page
+/// with summary statistics, charts and tables of every funder and DMP, a
+/// page per funder in `funders/` and the report of every DMP in `dmps/`.
+/// Returns the number of pages written.
+pub fn write_dashboard(dmps: &[DataManagementPlan], directory: &str) -> io::Result<usize> {
+    let directory = Path::new(directory);
+    fs::create_dir_all(directory.join("dmps"))?;
+    fs::create_dir_all(directory.join("funders"))?;
+
+    let mut taken = HashSet::new();
+    let entries: Vec<Entry> = dmps
+        .iter()
+        .map(|dmp| Entry {
+            dmp,
+            page: page_name(
+                &format!("{}-{}", dmp.dmp_id.id_type.as_str(), dmp.dmp_id.identifier),
+                &mut taken,
+            ),
+        })
+        .collect();
+    let all: Vec<&Entry> = entries.iter().collect();
+
+    let mut taken = HashSet::new();
+    let mut funders: Vec<Funder> = Vec::new();
+    for entry in &entries {
+        let name = match entry
+            .dmp
+            .project_info
+            .as_ref()
+            .and_then(|p| p.funder.as_deref())
+            .filter(|f| !f.trim().is_empty())
+        {
+            Some(name) => name,
+            None => continue,
+        };
+        match funders.iter_mut().find(|f| fold(&f.name) == fold(name)) {
+            Some(funder) => funder.entries.push(entry),
+            None => funders.push(Funder {
+                name: name.to_string(),
+                page: page_name(name, &mut taken),
+                entries: vec![entry],
+            }),
+        }
+    }
+    funders.sort_by_key(|f| fold(&f.name));
+
+    let generated = DateTime::now().try_to_rfc3339_string().unwrap_or_default();
+    let mut pages = 0;
+
+    let mut body = format!(
+        "<h1>DMP assessment dashboard</h1>\n<p class=\"meta\">Generated {}</p>\n",
+        escape_html(&generated)
+    );
+    body.push_str(&Summary::of(&all).to_html(Some(funders.len())));
+    body.push_str("<h2>Funders</h2>\n");
+    body.push_str("<table class=\"sortable\">\n<thead><tr><th>Funder</th><th>DMPs</th><th>Reviewed</th><th>Mean overall score</th></tr></thead>\n<tbody>\n");
+    for funder in &funders {
+        let summary = Summary::of(&funder.entries);
+        body.push_str(&format!(
+            "<tr><td><a href=\"funders/{}\">{}</a></td><td class=\"number\">{}</td><td class=\"number\">{}</td>",
+            funder.page,
+            escape_html(&funder.name),
+            summary.dmps,
+            summary.reviewed
+        ));
+        match summary.mean_score {
+            Some(score) => body.push_str(&format!("<td class=\"number\">{:.2}</td></tr>\n", score)),
+            None => body.push_str("<td class=\"number\" data-sort=\"-1\">-</td></tr>\n"),
+        }
+    }
+    body.push_str("</tbody>\n</table>\n");
+    body.push_str("<h2>DMPs</h2>\n");
+    body.push_str(&dmp_table(&all, ""));
+    fs::write(
+        directory.join("index.html"),
+        page("DMP assessment dashboard", &body),
+    )?;
+    pages += 1;
+
+    for funder in &funders {
+        let mut body = format!(
+            "<p><a href=\"../index.html\">Dashboard</a></p>\n<h1>{}</h1>\n",
+            escape_html(&funder.name)
+        );
+        body.push_str(&Summary::of(&funder.entries).to_html(None));
+        body.push_str("<h2>DMPs</h2>\n");
+        body.push_str(&dmp_table(&funder.entries, "../"));
+        fs::write(
+            directory.join("funders").join(&funder.page),
+            page(&funder.name, &body),
+        )?;
+        pages += 1;
+    }
+
+    for entry in &entries {
+        // The drill-down is the DMP's report with a way back to the index
+        let report = render_dmp_report(entry.dmp).replacen(
+            "<body>\n",
+            "<body>\n<p><a href=\"../index.html\">Dashboard</a></p>\n",
+            1,
+        );
+        fs::write(directory.join("dmps").join(&entry.page), report)?;
+        pages += 1;
+    }
+
+    Ok(pages)
+}
@@ -1,35 +1,223 @@
 use futures_util::stream::TryStreamExt;
 use mongodb::{
-    bson::{doc, Bson, Document},
-    options::ClientOptions,
-    Client, Collection, Database,
+    bson::{self, doc, oid::ObjectId, Bson, Document},
+    error::ErrorKind as MongoErrorKind,
+    options::{
+        AggregateOptions, ClientOptions, Collation, CollationStrength, CountOptions, FindOptions,
+        IndexOptions, ReplaceOptions, UpdateOptions,
+    },
+    Client, Collection, Database, IndexModel,
 };
-use serde_json;
+use std::collections::{HashSet, VecDeque};
 use std::error::Error;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Error as IoError, ErrorKind};
 
-use crate::DataManagementPlan;
+use crate::analytics::{
+    breakdown_pipeline, breakdowns, co_occurrence_pipeline, rating_distributions, rating_pipeline,
+    section_tags, section_tags_pipeline, tag_pairs, Analytics,
+};
+use crate::conflict::ConflictError;
+use crate::graph::{follows, RelationGraph};
+use crate::identifiers::{
+    find_related_entities, id_type_variants, identifier_variants, normalize_dmp_identifiers,
+    same_identifier,
+};
+use crate::matching::{fold, matches, normalize_dmp_tags, normalize_tag, MatchMode};
+use crate::migrations::{upgrade_document, MigrationRun, SCHEMA_VERSION};
+use crate::query::{related_entity_array, DmpQuery, SectionPath};
+use crate::related::{RelatedEntity, ENTITY_TYPES};
+use crate::scales::{OffScaleRating, RubricScales};
+use crate::text_search::{search_terms, snippets_for, TextSearchHit};
+use crate::trends::{overall_pipeline, section_pipeline, Period, TrendDate, Trends};
+use crate::update::DmpUpdate;
+use crate::vocabulary::{canonicalize_dmp_tags, rename_dmp_tag, TagRename, TagVocabulary};
+use crate::{
+    Annotation, DataManagementPlan, Rating, RatingDraft, RelationType, SavedSearch,
+    SectionCrossReference, VocabularyTag,
+};
+
+const TEXT_INDEX_NAME: &str = "dmp_text_index";
+
+/// Collation comparing strings without regard to case or diacritics.
+fn insensitive_collation() -> Collation {
+    Collation::builder()
+        .locale("en")
+        .strength(CollationStrength::Primary)
+        .build()
+}
+
+/// Writes `dmps` to `file_path` as pretty-printed JSON, returning how many were written.
+fn write_dmps(dmps: &[DataManagementPlan], file_path: &str) -> Result<usize, Box<dyn Error>> {
+    let file = File::create(file_path)?;
+    let writer = BufWriter::new(file);
+
+    serde_json::to_writer_pretty(writer, dmps)?;
+
+    Ok(dmps.len())
+}
+
+/// Filter matching the DMP `id_type`/`dmp_id` in any accepted form of the
+/// identifier, combined with the conditions in `rest`.
+fn dmp_filter(dmp_id: &str, id_type: &str, rest: Document) -> Document {
+    let mut filter = doc! {
+        "dmp_id.identifier": { "$in": identifier_variants(dmp_id, Some(id_type)) },
+        "dmp_id.id_type": { "$in": id_type_variants(dmp_id, id_type) }
+    };
+    filter.extend(rest);
+    filter
+}
+
+/// Parses the ID of a rating draft.
+fn draft_id(id: &str) -> Result<ObjectId, IoError> {
+    ObjectId::parse_str(id.trim()).map_err(|_| {
+        IoError::new(
+            ErrorKind::InvalidInput,
+            format!("'{}' is not a valid draft ID", id),
+        )
+    })
+}
+
+/// Filter matching DMPs stored below `SCHEMA_VERSION`, including those stored
+/// before documents were versioned.
+fn outdated_filter() -> Document {
+    doc! {
+        "$or": [
+            { "schema_version": { "$exists": false } },
+            { "schema_version": { "$lt": SCHEMA_VERSION } }
+        ]
+    }
+}
 
 pub struct DatabaseManager {
+    #[allow(dead_code)]
     client: Client,
     db: Database,
+    // Scales every stored rating must be on
+    scales: RubricScales,
 }
 
 impl DatabaseManager {
-    pub async fn new(connection_string: &str, db_name: &str) -> Result<Self, Box<dyn Error>> {
+    pub async fn new(
+        connection_string: &str,
+        db_name: &str,
+        scales: RubricScales,
+    ) -> Result<Self, Box<dyn Error>> {
         let client_options = ClientOptions::parse(connection_string).await?;
         let client = Client::with_options(client_options)?;
         let db = client.database(db_name);
 
-        Ok(Self { client, db })
+        Ok(Self { client, db, scales })
+    }
+
+    /// The scale each rubric section is rated on.
+    pub fn scales(&self) -> &RubricScales {
+        &self.scales
     }
 
     pub fn get_dmp_collection(&self) -> Collection<DataManagementPlan> {
         self.db.collection("dmps")
     }
 
-    pub async fn add_dmp(&self, dmp: DataManagementPlan) -> Result<String, Box<dyn Error>> {
+    pub fn get_saved_search_collection(&self) -> Collection<SavedSearch> {
+        self.db.collection("saved_searches")
+    }
+
+    pub fn get_tag_vocabulary_collection(&self) -> Collection<VocabularyTag> {
+        self.db.collection("tag_vocabulary")
+    }
+
+    pub fn get_rating_draft_collection(&self) -> Collection<RatingDraft> {
+        self.db.collection("rating_drafts")
+    }
+
+    /// The `dmps` collection as raw documents, for reading documents that
+    /// may not match `DataManagementPlan`.
+    fn get_raw_dmp_collection(&self) -> Collection<Document> {
+        self.db.collection("dmps")
+    }
+
+    /// Returns the number of stored DMPs below `SCHEMA_VERSION`.
+    pub async fn count_outdated_dmps(&self) -> Result<u64, Box<dyn Error>> {
+        let collection = self.get_raw_dmp_collection();
+        let filter = outdated_filter();
+
+        Ok(collection.count_documents(filter, None).await?)
+    }
+
+    /// Upgrades every stored DMP below `SCHEMA_VERSION` by applying the
+    /// migrations it has not had yet, and records each migration in the
+    /// `migrations` collection. DMPs changed while the run reads them are
+    /// left as they are and listed in `skipped`. With `dry_run`, only
+    /// reports what would change.
+    pub async fn run_migrations(&self, dry_run: bool) -> Result<MigrationRun, Box<dyn Error>> {
+        let collection = self.get_raw_dmp_collection();
+        let filter = outdated_filter();
+        let mut cursor = collection.find(filter, None).await?;
+        let mut run = MigrationRun::new(dry_run);
+
+        while let Some(mut dmp) = cursor.try_next().await? {
+            // Leave the document alone if it is changed after it is read
+            let mut filter = doc! {
+                "_id": dmp.get_object_id("_id")?,
+                "schema_version": dmp.get("schema_version").cloned().unwrap_or(Bson::Null)
+            };
+            if let Some(last_modified) = dmp.get("last_modified") {
+                filter.insert("last_modified", last_modified.clone());
+            }
+
+            let title = dmp.get_str("title").unwrap_or("(untitled)").to_string();
+            let mut upgrade = MigrationRun::new(dry_run);
+            upgrade_document(&mut dmp, &mut upgrade);
+            if !dry_run
+                && collection
+                    .replace_one(filter, dmp, None)
+                    .await?
+                    .matched_count
+                    == 0
+            {
+                run.skipped.push(title);
+                continue;
+            }
+            run.merge(upgrade);
+        }
+
+        if !dry_run {
+            let migrations = self.db.collection::<Document>("migrations");
+            let options = UpdateOptions::builder().upsert(true).build();
+            for report in &run.reports {
+                migrations
+                    .update_one(
+                        doc! { "version": report.version },
+                        doc! {
+                            "$set": {
+                                "name": report.name,
+                                "last_run": mongodb::bson::DateTime::now()
+                            },
+                            "$setOnInsert": { "applied_at": mongodb::bson::DateTime::now() },
+                            "$inc": { "documents": report.documents as i64 }
+                        },
+                        options.clone(),
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(run)
+    }
+
+    /// Stores a new DMP with its identifiers in canonical form and tag
+    /// synonyms replaced by their vocabulary tags. Fails if any identifier is
+    /// invalid or a rating is not on the scale of its section.
+    pub async fn add_dmp(&self, mut dmp: DataManagementPlan) -> Result<String, Box<dyn Error>> {
+        normalize_dmp_identifiers(&mut dmp)
+            .map_err(|e| IoError::new(ErrorKind::InvalidInput, e))?;
+        self.scales
+            .check_dmp(&dmp, None)
+            .map_err(|e| IoError::new(ErrorKind::InvalidInput, e))?;
+        canonicalize_dmp_tags(&mut dmp, &self.load_tag_vocabulary().await?);
+        dmp.schema_version = SCHEMA_VERSION;
+
         let collection = self.get_dmp_collection();
         let result = collection.insert_one(dmp, None).await?;
 
@@ -46,31 +234,131 @@ impl DatabaseManager {
         id_type: &str,
     ) -> Result<Option<DataManagementPlan>, Box<dyn Error>> {
         let collection = self.get_dmp_collection();
-        let filter = doc! {
-            "dmp_id.identifier": dmp_id,
-            "dmp_id.id_type": id_type
-        };
+        let filter = dmp_filter(dmp_id, id_type, doc! {});
 
         let result = collection.find_one(filter, None).await?;
         Ok(result)
     }
 
+    /// Replaces a stored DMP with `dmp`, normalized as by `add_dmp`,
+    /// provided the DMP has not changed since `dmp` was read: its
+    /// `last_modified` must still be that of `dmp`. Fails with a
+    /// `ConflictError` holding the stored DMP if it has changed, and with
+    /// `InvalidInput` if `dmp` carries another DMP identifier or a new or
+    /// changed rating off the scale of its section.
+    pub async fn replace_dmp(
+        &self,
+        dmp_id: &str,
+        id_type: &str,
+        mut dmp: DataManagementPlan,
+    ) -> Result<(), Box<dyn Error>> {
+        let current = self.find_dmp_by_id(dmp_id, id_type).await?.ok_or_else(|| {
+            IoError::new(
+                ErrorKind::NotFound,
+                format!("DMP {}/{} not found", id_type, dmp_id),
+            )
+        })?;
+        let read_at = dmp.last_modified;
+        if current.last_modified != read_at {
+            return Err(Box::new(ConflictError { read_at, current }));
+        }
+
+        normalize_dmp_identifiers(&mut dmp)
+            .map_err(|e| IoError::new(ErrorKind::InvalidInput, e))?;
+        if dmp.dmp_id.id_type != current.dmp_id.id_type
+            || dmp.dmp_id.identifier != current.dmp_id.identifier
+        {
+            return Err(Box::new(IoError::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The DMP identifier {}/{} does not match {}/{}; a DMP cannot be re-keyed",
+                    dmp.dmp_id.id_type.as_str(),
+                    dmp.dmp_id.identifier,
+                    current.dmp_id.id_type.as_str(),
+                    current.dmp_id.identifier
+                ),
+            )));
+        }
+        self.scales
+            .check_dmp(&dmp, Some(&current))
+            .map_err(|e| IoError::new(ErrorKind::InvalidInput, e))?;
+        canonicalize_dmp_tags(&mut dmp, &self.load_tag_vocabulary().await?);
+        dmp.id = current.id;
+        dmp.schema_version = SCHEMA_VERSION;
+        dmp.last_modified = mongodb::bson::DateTime::now();
+
+        let collection = self.get_dmp_collection();
+        let filter = doc! { "_id": current.id, "last_modified": read_at };
+        let result = collection.replace_one(filter, dmp, None).await?;
+        if result.matched_count == 0 {
+            self.check_unchanged(dmp_id, id_type, read_at).await?;
+        }
+        Ok(())
+    }
+
+    /// Deletes a DMP. Returns `false` if there is no such DMP.
+    pub async fn delete_dmp(&self, dmp_id: &str, id_type: &str) -> Result<bool, Box<dyn Error>> {
+        let collection = self.get_dmp_collection();
+        let filter = dmp_filter(dmp_id, id_type, doc! {});
+
+        let result = collection.delete_one(filter, None).await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    /// Returns every distinct tag used in any section or subsection.
+    pub async fn distinct_section_tags(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let collection = self.get_dmp_collection();
+        let mut values = collection.distinct("sections.tags", None, None).await?;
+        values.extend(
+            collection
+                .distinct("sections.subsections.tags", None, None)
+                .await?,
+        );
+
+        let mut tags: Vec<String> = values
+            .into_iter()
+            .filter_map(|value| value.as_str().map(str::to_string))
+            .collect();
+        tags.sort();
+        tags.dedup();
+        Ok(tags)
+    }
+
+    /// Finds DMPs whose section or subsection at `path` carries a tag
+    /// matching `tag`, or a vocabulary tag below it. Titles and tags are
+    /// compared ignoring case and diacritics; in prefix and fuzzy mode the
+    /// tag is first expanded to the stored tags it matches.
     pub async fn find_dmps_by_section_tag(
         &self,
-        section_title: &str,
+        path: &SectionPath,
         tag: &str,
+        mode: MatchMode,
     ) -> Result<Vec<DataManagementPlan>, Box<dyn Error>> {
         let collection = self.get_dmp_collection();
-        let query = doc! {
-            "sections": {
-                "$elemMatch": {
-                    "title": section_title,
-                    "tags": tag
-                }
-            }
+        let tags: Vec<String> = match mode {
+            MatchMode::Exact => vec![tag.to_string()],
+            MatchMode::Prefix | MatchMode::Fuzzy => self
+                .distinct_section_tags()
+                .await?
+                .into_iter()
+                .filter(|candidate| matches(candidate, tag, mode))
+                .collect(),
         };
+        let vocabulary = self.load_tag_vocabulary().await?;
+        let mut tags: Vec<String> = tags.iter().flat_map(|t| vocabulary.expand(t)).collect();
+        tags.sort();
+        tags.dedup();
 
-        let mut cursor = collection.find(query, None).await?;
+        if tags.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let query = path.filter(doc! { "tags": { "$in": tags } });
+        let options = FindOptions::builder()
+            .collation(insensitive_collation())
+            .build();
+
+        let mut cursor = collection.find(query, options).await?;
         let mut dmps = Vec::new();
 
         while let Some(dmp) = cursor.try_next().await? {
@@ -80,25 +368,27 @@ impl DatabaseManager {
         Ok(dmps)
     }
 
+    /// Finds DMPs whose section or subsection at `path` is rated on the scale
+    /// of `min_rating` and at least as high.
     pub async fn find_dmps_by_rating(
         &self,
-        section_title: &str,
-        min_rating: u8,
+        path: &SectionPath,
+        min_rating: Rating,
     ) -> Result<Vec<DataManagementPlan>, Box<dyn Error>> {
         let collection = self.get_dmp_collection();
-        // Convert u8 to i32 for MongoDB compatibility
-        let min_rating_i32: i32 = min_rating as i32;
+        // Convert u8 to i32 for MongoDB compatibility; ratings that do not
+        // apply have a null value and never match
+        let min_value = min_rating.value().map(i32::from);
 
-        let query = doc! {
-            "sections": {
-                "$elemMatch": {
-                    "title": section_title,
-                    "rating": { "$gte": min_rating_i32 }
-                }
-            }
-        };
+        let query = path.filter(doc! {
+            "rating.scale": min_rating.scale().as_str(),
+            "rating.value": { "$gte": min_value }
+        });
+        let options = FindOptions::builder()
+            .collation(insensitive_collation())
+            .build();
 
-        let mut cursor = collection.find(query, None).await?;
+        let mut cursor = collection.find(query, options).await?;
         let mut dmps = Vec::new();
 
         while let Some(dmp) = cursor.try_next().await? {
@@ -108,30 +398,136 @@ impl DatabaseManager {
         Ok(dmps)
     }
 
+    /// Finds the DMPs listing the related entity `entity_id`, optionally
+    /// only those related to it by `relation_type`.
     pub async fn find_dmps_by_related_entity(
         &self,
         entity_type: &str,
         entity_id: &str,
+        relation_type: Option<RelationType>,
     ) -> Result<Vec<DataManagementPlan>, Box<dyn Error>> {
         let collection = self.get_dmp_collection();
-        let field_name = match entity_type {
-            "dataset" => "related_datasets.dataset_id.identifier",
-            "publication" => "related_publications.publication_id.identifier",
-            "software" => "related_software.software_id.identifier",
-            "dmp" => "related_dmps.dmp_id.identifier",
-            _ => {
-                return Err(Box::new(IoError::new(
-                    ErrorKind::InvalidInput,
-                    "Invalid entity type",
-                )))
-            }
-        };
+        let query = DmpQuery::new()
+            .related_entity(entity_type, entity_id, relation_type)
+            .to_filter()?;
+
+        let mut cursor = collection.find(query, None).await?;
+        let mut dmps = Vec::new();
 
-        let query = doc! {
-            field_name: entity_id
+        while let Some(dmp) = cursor.try_next().await? {
+            dmps.push(dmp);
+        }
+
+        Ok(dmps)
+    }
+
+    /// Walks related entity entries outwards from the DMP or research output
+    /// `identifier` for up to `max_hops` hops, in both directions: from a
+    /// DMP to the entities it lists, and from an entity to the DMPs listing
+    /// it. Without `entity_type`, every DMP and entity with the identifier is
+    /// a starting node. Only edges of `relations` are followed, or every edge
+    /// if it is empty.
+    pub async fn relation_graph(
+        &self,
+        entity_type: Option<&str>,
+        identifier: &str,
+        max_hops: usize,
+        relations: &[RelationType],
+    ) -> Result<RelationGraph, Box<dyn Error>> {
+        let collection = self.get_dmp_collection();
+        let kinds: Vec<&str> = match entity_type {
+            Some(kind) => vec![ENTITY_TYPES
+                .iter()
+                .find(|t| **t == kind)
+                .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "Invalid entity type"))?],
+            None => ENTITY_TYPES.to_vec(),
         };
 
-        let mut cursor = collection.find(query, None).await?;
+        let mut graph = RelationGraph::new();
+        let mut queue = VecDeque::new();
+
+        for kind in kinds {
+            if kind == "dmp" {
+                let filter =
+                    doc! { "dmp_id.identifier": { "$in": identifier_variants(identifier, None) } };
+                if let Some(dmp) = collection.find_one(filter, None).await? {
+                    queue.push_back(graph.dmp_node(&dmp, 0));
+                    continue;
+                }
+            }
+
+            let listing = self
+                .find_dmps_by_related_entity(kind, identifier, None)
+                .await?;
+            if let Some(entity) = listing
+                .iter()
+                .flat_map(|dmp| find_related_entities(dmp, kind, identifier))
+                .next()
+            {
+                queue.push_back(graph.entity_node(&entity, 0));
+            }
+        }
+
+        if graph.nodes.is_empty() {
+            return Err(Box::new(IoError::new(
+                ErrorKind::NotFound,
+                format!("No DMP or related entity has the identifier {}", identifier),
+            )));
+        }
+
+        let mut expanded = HashSet::new();
+        while let Some(index) = queue.pop_front() {
+            if !expanded.insert(index) || graph.nodes[index].depth >= max_hops {
+                continue;
+            }
+            let kind = graph.nodes[index].kind;
+            let node_id = graph.nodes[index].identifier.clone();
+            let depth = graph.nodes[index].depth;
+
+            // Entities listed by a stored DMP
+            if kind == "dmp" {
+                let filter =
+                    doc! { "dmp_id.identifier": { "$in": identifier_variants(&node_id, None) } };
+                if let Some(dmp) = collection.find_one(filter, None).await? {
+                    graph.dmp_node(&dmp, depth);
+                    queue.extend(graph.expand_dmp(index, &dmp, relations));
+                }
+            }
+
+            // DMPs listing the node
+            for dmp in self
+                .find_dmps_by_related_entity(kind, &node_id, None)
+                .await?
+            {
+                for entity in find_related_entities(&dmp, kind, &node_id) {
+                    if !follows(relations, entity.relationship()) {
+                        continue;
+                    }
+                    let from = graph.dmp_node(&dmp, depth + 1);
+                    graph.edge(from, index, entity.relationship());
+                    queue.push_back(from);
+                }
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Finds the DMPs matching every criterion of `query`. Text criteria are
+    /// compared ignoring case and diacritics.
+    pub async fn find_dmps(
+        &self,
+        query: &DmpQuery,
+    ) -> Result<Vec<DataManagementPlan>, Box<dyn Error>> {
+        let collection = self.get_dmp_collection();
+        let filter = query
+            .with_expanded_tags(&self.load_tag_vocabulary().await?)
+            .to_filter()?;
+        let options = FindOptions::builder()
+            .collation(insensitive_collation())
+            .build();
+
+        let mut cursor = collection.find(filter, options).await?;
         let mut dmps = Vec::new();
 
         while let Some(dmp) = cursor.try_next().await? {
@@ -141,7 +537,171 @@ impl DatabaseManager {
         Ok(dmps)
     }
 
-    pub async fn export_all_dmps(&self, file_path: &str) -> Result<usize, Box<dyn Error>> {
+    /// Runs an aggregation `pipeline` over the stored DMPs, comparing
+    /// strings without regard to case or diacritics.
+    async fn aggregate_dmps(
+        &self,
+        pipeline: Vec<Document>,
+    ) -> Result<Vec<Document>, Box<dyn Error>> {
+        let collection = self.get_raw_dmp_collection();
+        let options = AggregateOptions::builder()
+            .collation(insensitive_collation())
+            .build();
+
+        let mut cursor = collection.aggregate(pipeline, options).await?;
+        let mut results = Vec::new();
+
+        while let Some(result) = cursor.try_next().await? {
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+
+    /// Summarizes the section ratings, tags and funders and institutions of
+    /// the DMPs matching `query`, listing the `top` most frequent tags of each
+    /// section and pairs of tags.
+    pub async fn analytics(
+        &self,
+        query: &DmpQuery,
+        top: usize,
+    ) -> Result<Analytics, Box<dyn Error>> {
+        let filter = query
+            .with_expanded_tags(&self.load_tag_vocabulary().await?)
+            .to_filter()?;
+        let options = CountOptions::builder()
+            .collation(insensitive_collation())
+            .build();
+
+        Ok(Analytics {
+            dmps: self
+                .get_raw_dmp_collection()
+                .count_documents(filter.clone(), options)
+                .await?,
+            ratings: rating_distributions(
+                &self.aggregate_dmps(rating_pipeline(filter.clone())).await?,
+            ),
+            section_tags: section_tags(
+                &self
+                    .aggregate_dmps(section_tags_pipeline(filter.clone(), top))
+                    .await?,
+            ),
+            co_occurrence: tag_pairs(
+                &self
+                    .aggregate_dmps(co_occurrence_pipeline(filter.clone(), top))
+                    .await?,
+            ),
+            by_funder: breakdowns(
+                &self
+                    .aggregate_dmps(breakdown_pipeline(filter.clone(), "funder"))
+                    .await?,
+            ),
+            by_institution: breakdowns(
+                &self
+                    .aggregate_dmps(breakdown_pipeline(filter, "institution"))
+                    .await?,
+            ),
+        })
+    }
+
+    /// Buckets the DMPs matching `query` into periods by `date`, with the
+    /// mean overall score and section ratings of each period.
+    pub async fn trends(
+        &self,
+        query: &DmpQuery,
+        date: TrendDate,
+        period: Period,
+    ) -> Result<Trends, Box<dyn Error>> {
+        let filter = query
+            .with_expanded_tags(&self.load_tag_vocabulary().await?)
+            .to_filter()?;
+
+        Ok(Trends::from_results(
+            period,
+            date,
+            &self
+                .aggregate_dmps(overall_pipeline(filter.clone(), date))
+                .await?,
+            &self.aggregate_dmps(section_pipeline(filter, date)).await?,
+        ))
+    }
+
+    /// Creates the text index backing `search_text` if it does not exist yet,
+    /// replacing an older version of the index covering different fields.
+    /// Run once at startup.
+    pub async fn ensure_text_index(&self) -> Result<(), Box<dyn Error>> {
+        let collection = self.get_dmp_collection();
+        let index = IndexModel::builder()
+            .keys(doc! {
+                "title": "text",
+                "sections.text": "text",
+                "sections.comments": "text",
+                "sections.subsections.text": "text",
+                "sections.subsections.comments": "text",
+                "overall_rating.comments": "text",
+                "related_dmps.relationship_notes": "text"
+            })
+            .options(
+                IndexOptions::builder()
+                    .name(TEXT_INDEX_NAME.to_string())
+                    .weights(doc! { "title": 5 })
+                    .build(),
+            )
+            .build();
+
+        match collection.create_index(index.clone(), None).await {
+            Ok(_) => Ok(()),
+            // IndexOptionsConflict or IndexKeySpecsConflict: the index exists
+            // with other fields
+            Err(e) if matches!(*e.kind, MongoErrorKind::Command(ref c) if c.code == 85 || c.code == 86) =>
+            {
+                collection.drop_index(TEXT_INDEX_NAME, None).await?;
+                collection.create_index(index, None).await?;
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Searches DMP titles, section text, comments and relationship notes, returning the
+    /// best `limit` matches ranked by relevance with highlighted snippets.
+    pub async fn search_text(
+        &self,
+        search: &str,
+        limit: i64,
+    ) -> Result<Vec<TextSearchHit>, Box<dyn Error>> {
+        let collection = self.get_dmp_collection().clone_with_type::<Document>();
+        let filter = doc! { "$text": { "$search": search } };
+        let options = FindOptions::builder()
+            .projection(doc! { "score": { "$meta": "textScore" } })
+            .sort(doc! { "score": { "$meta": "textScore" } })
+            .limit(limit)
+            .build();
+
+        let terms = search_terms(search);
+        let mut cursor = collection.find(filter, options).await?;
+        let mut hits = Vec::new();
+
+        while let Some(mut document) = cursor.try_next().await? {
+            let score = document
+                .remove("score")
+                .and_then(|s| s.as_f64())
+                .unwrap_or(0.0);
+            let dmp: DataManagementPlan = bson::from_document(document)?;
+            let snippets = snippets_for(&dmp, &terms);
+
+            hits.push(TextSearchHit {
+                dmp,
+                score,
+                snippets,
+            });
+        }
+
+        Ok(hits)
+    }
+
+    /// Every stored DMP, as exported by `export_all_dmps`.
+    pub async fn all_dmps(&self) -> Result<Vec<DataManagementPlan>, Box<dyn Error>> {
         let collection = self.get_dmp_collection();
         let mut cursor = collection.find(doc! {}, None).await?;
 
@@ -151,12 +711,30 @@ impl DatabaseManager {
             dmps.push(dmp);
         }
 
-        let file = File::create(file_path)?;
-        let writer = BufWriter::new(file);
+        Ok(dmps)
+    }
 
-        serde_json::to_writer_pretty(writer, &dmps)?;
+    /// The stored ratings that are not on the scale of their section, with
+    /// the title and identifier of their DMP.
+    pub async fn off_scale_ratings(&self) -> Result<Vec<(String, OffScaleRating)>, Box<dyn Error>> {
+        let mut off_scale = Vec::new();
+        for dmp in self.all_dmps().await? {
+            let label = format!(
+                "{} ({}/{})",
+                dmp.title,
+                dmp.dmp_id.id_type.as_str(),
+                dmp.dmp_id.identifier
+            );
+            for rating in self.scales.off_scale(&dmp) {
+                off_scale.push((label.clone(), rating));
+            }
+        }
+        Ok(off_scale)
+    }
 
-        Ok(dmps.len())
+    pub async fn export_all_dmps(&self, file_path: &str) -> Result<usize, Box<dyn Error>> {
+        let dmps = self.all_dmps().await?;
+        write_dmps(&dmps, file_path)
     }
 
     pub async fn import_from_file(&self, file_path: &str) -> Result<usize, Box<dyn Error>> {
@@ -167,43 +745,635 @@ impl DatabaseManager {
         let reader = BufReader::new(file);
         let dmps: Vec<DataManagementPlan> = serde_json::from_reader(reader)?;
 
+        self.import_dmps(dmps).await
+    }
+
+    /// Stores exported DMPs as new DMPs, as `import_from_file` does with the
+    /// DMPs read from a file. Their ratings are kept as exported, even those
+    /// off the scale of their section, which `off_scale_ratings` lists.
+    pub async fn import_dmps(
+        &self,
+        dmps: Vec<DataManagementPlan>,
+    ) -> Result<usize, Box<dyn Error>> {
         if dmps.is_empty() {
             return Ok(0);
         }
 
+        let vocabulary = self.load_tag_vocabulary().await?;
+        // Normalize every DMP first so an invalid one imports nothing
+        let mut to_insert = Vec::with_capacity(dmps.len());
+        for mut dmp in dmps {
+            dmp.id = None;
+            // Reading the file brought the DMP to the current layout
+            dmp.schema_version = SCHEMA_VERSION;
+            normalize_dmp_tags(&mut dmp);
+            canonicalize_dmp_tags(&mut dmp, &vocabulary);
+            normalize_dmp_identifiers(&mut dmp).map_err(|e| {
+                IoError::new(
+                    ErrorKind::InvalidInput,
+                    format!("DMP '{}': {}", dmp.title, e),
+                )
+            })?;
+            to_insert.push(dmp);
+        }
+
         let collection = self.get_dmp_collection();
         let mut count = 0;
 
-        for dmp in dmps {
-            let mut dmp_to_insert = dmp;
-            dmp_to_insert.id = None;
-
-            collection.insert_one(dmp_to_insert, None).await?;
+        for dmp in to_insert {
+            collection.insert_one(dmp, None).await?;
             count += 1;
         }
 
         Ok(count)
     }
 
+    /// Sets the rating and comments of the section or subsection at `path`,
+    /// provided the DMP has not changed since it was read with
+    /// `last_modified` equal to `read_at`. Fails with a `ConflictError`
+    /// holding the stored DMP if it has.
     pub async fn update_dmp_rating(
         &self,
         dmp_id: &str,
         id_type: &str,
-        section_title: &str,
-        rating: u8,
+        path: &SectionPath,
+        rating: Rating,
         comments: &str,
+        read_at: mongodb::bson::DateTime,
     ) -> Result<bool, Box<dyn Error>> {
+        let collection = self.get_dmp_collection();
+        let mut filter = dmp_filter(dmp_id, id_type, path.filter(doc! {}));
+        filter.insert("last_modified", read_at);
+
+        let update = doc! {
+            "$set": {
+                path.field("rating"): bson::to_bson(&rating)?,
+                path.field("comments"): comments,
+                "last_modified": mongodb::bson::DateTime::now()
+            }
+        };
+        let options = UpdateOptions::builder()
+            .array_filters(path.array_filters())
+            .collation(insensitive_collation())
+            .build();
+
+        let result = collection.update_one(filter, update, options).await?;
+        if result.matched_count == 0 {
+            self.check_unchanged(dmp_id, id_type, read_at).await?;
+        }
+        Ok(result.modified_count > 0)
+    }
+
+    /// Writes every change in `update` to a DMP in a single update recording
+    /// one history entry, provided the DMP has not changed since it was read
+    /// with `last_modified` equal to `read_at`. Fails with a `ConflictError`
+    /// holding the stored DMP if it has, and writes nothing if any change is
+    /// invalid.
+    pub async fn apply_update(
+        &self,
+        dmp_id: &str,
+        id_type: &str,
+        update: &DmpUpdate,
+        read_at: mongodb::bson::DateTime,
+    ) -> Result<bool, Box<dyn Error>> {
+        let dmp = self.find_dmp_by_id(dmp_id, id_type).await?.ok_or_else(|| {
+            IoError::new(
+                ErrorKind::NotFound,
+                format!("DMP {}/{} not found", id_type, dmp_id),
+            )
+        })?;
+        if dmp.last_modified != read_at {
+            return Err(Box::new(ConflictError {
+                read_at,
+                current: dmp,
+            }));
+        }
+
+        let changes = update
+            .to_update(&dmp)
+            .map_err(|e| IoError::new(ErrorKind::InvalidInput, e))?;
+
+        // A single-document update is atomic: every change is written or none
+        let collection = self.get_dmp_collection();
+        let filter = dmp_filter(dmp_id, id_type, doc! { "last_modified": read_at });
+        let result = collection.update_one(filter, changes, None).await?;
+        if result.matched_count == 0 {
+            self.check_unchanged(dmp_id, id_type, read_at).await?;
+        }
+        Ok(result.modified_count > 0)
+    }
+
+    /// Fails with a `ConflictError` if the DMP exists and its `last_modified`
+    /// is no longer `read_at`.
+    async fn check_unchanged(
+        &self,
+        dmp_id: &str,
+        id_type: &str,
+        read_at: mongodb::bson::DateTime,
+    ) -> Result<(), Box<dyn Error>> {
+        match self.find_dmp_by_id(dmp_id, id_type).await? {
+            Some(current) if current.last_modified != read_at => {
+                Err(Box::new(ConflictError { read_at, current }))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn load_tag_vocabulary(&self) -> Result<TagVocabulary, Box<dyn Error>> {
+        let collection = self.get_tag_vocabulary_collection();
+        let mut cursor = collection.find(doc! {}, None).await?;
+        let mut tags = Vec::new();
+
+        while let Some(tag) = cursor.try_next().await? {
+            tags.push(tag);
+        }
+
+        Ok(TagVocabulary::new(tags))
+    }
+
+    /// Adds `tag` to the vocabulary, replacing the tag of the same name.
+    /// Fails if it does not fit the vocabulary (see `TagVocabulary::validate`).
+    /// Returns `true` if an existing tag was replaced.
+    pub async fn save_vocabulary_tag(&self, tag: VocabularyTag) -> Result<bool, Box<dyn Error>> {
+        let vocabulary = self.load_tag_vocabulary().await?;
+        vocabulary
+            .validate(&tag)
+            .map_err(|e| IoError::new(ErrorKind::InvalidInput, e))?;
+
+        let collection = self.get_tag_vocabulary_collection();
+        // The tag may be stored with other case or accents
+        let name = vocabulary.named(&tag.name).map_or(&tag.name, |t| &t.name);
+        let filter = doc! { "name": name };
+        let options = ReplaceOptions::builder().upsert(true).build();
+
+        let result = collection.replace_one(filter, tag, options).await?;
+        Ok(result.matched_count > 0)
+    }
+
+    /// Removes `name` from the vocabulary, moving the tags below it up to its
+    /// parent. DMPs keep the tag. Returns `false` if there is no such tag.
+    pub async fn delete_vocabulary_tag(&self, name: &str) -> Result<bool, Box<dyn Error>> {
+        let vocabulary = self.load_tag_vocabulary().await?;
+        let tag = match vocabulary.named(name) {
+            Some(tag) => tag,
+            None => return Ok(false),
+        };
+
+        let collection = self.get_tag_vocabulary_collection();
+        let options = UpdateOptions::builder()
+            .collation(insensitive_collation())
+            .build();
+        collection
+            .update_many(
+                doc! { "parent": &tag.name },
+                doc! { "$set": { "parent": &tag.parent } },
+                options,
+            )
+            .await?;
+        let result = collection
+            .delete_one(doc! { "name": &tag.name }, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    /// Renames the tag `from` to `to` in the vocabulary and in every DMP,
+    /// merging it into `to` where that tag already exists. The old name is
+    /// kept as a synonym of the new one.
+    pub async fn rename_tag(&self, from: &str, to: &str) -> Result<TagRename, Box<dyn Error>> {
+        let (from, to) = (normalize_tag(from), normalize_tag(to));
+        if from.is_empty() || to.is_empty() {
+            return Err(Box::new(IoError::new(
+                ErrorKind::InvalidInput,
+                "Both the tag and its new name are required",
+            )));
+        }
+        if fold(&from) == fold(&to) {
+            return Err(Box::new(IoError::new(
+                ErrorKind::InvalidInput,
+                format!("'{}' already has that name", from),
+            )));
+        }
+
+        let vocabulary = self.load_tag_vocabulary().await?;
+        let mut rename = TagRename {
+            from: from.clone(),
+            to: to.clone(),
+            merged: vocabulary
+                .find(&to)
+                .is_some_and(|t| fold(&t.name) == fold(&to)),
+            ..TagRename::default()
+        };
+        let changed = vocabulary.rename(&from, &to);
+        if let Some(source) = vocabulary.named(&from) {
+            let collection = self.get_tag_vocabulary_collection();
+            collection
+                .delete_one(doc! { "name": &source.name }, None)
+                .await?;
+            let options = ReplaceOptions::builder().upsert(true).build();
+            for tag in changed {
+                collection
+                    .replace_one(doc! { "name": &tag.name }, tag, options.clone())
+                    .await?;
+            }
+        }
+
         let collection = self.get_dmp_collection();
         let filter = doc! {
-            "dmp_id.identifier": dmp_id,
-            "dmp_id.id_type": id_type,
-            "sections.title": section_title
+            "$or": [
+                { "sections.tags": &from },
+                { "sections.subsections.tags": &from },
+                { "sections.annotations.tags": &from },
+                { "overall_rating.overall_tags": &from }
+            ]
+        };
+        let options = FindOptions::builder()
+            .collation(insensitive_collation())
+            .build();
+        let mut cursor = collection.find(filter, options).await?;
+        let mut dmps = Vec::new();
+
+        while let Some(dmp) = cursor.try_next().await? {
+            dmps.push(dmp);
+        }
+
+        for mut dmp in dmps {
+            if !rename_dmp_tag(&mut dmp, &from, &to) {
+                continue;
+            }
+            // Leave the DMP alone if it is changed after it is read
+            let filter = doc! { "_id": dmp.id, "last_modified": dmp.last_modified };
+            dmp.last_modified = mongodb::bson::DateTime::now();
+            let result = collection.replace_one(filter, &dmp, None).await?;
+            if result.matched_count > 0 {
+                rename.dmps += 1;
+            } else {
+                rename.skipped.push(dmp.title.clone());
+            }
+        }
+
+        Ok(rename)
+    }
+
+    /// Saves `search` under its name, replacing any search already saved with
+    /// that name. Returns `true` if an existing search was replaced.
+    pub async fn save_search(&self, search: SavedSearch) -> Result<bool, Box<dyn Error>> {
+        let collection = self.get_saved_search_collection();
+        let filter = doc! { "name": &search.name };
+        let options = ReplaceOptions::builder().upsert(true).build();
+
+        let result = collection.replace_one(filter, search, options).await?;
+        Ok(result.matched_count > 0)
+    }
+
+    pub async fn list_saved_searches(&self) -> Result<Vec<SavedSearch>, Box<dyn Error>> {
+        let collection = self.get_saved_search_collection();
+        let options = FindOptions::builder().sort(doc! { "name": 1 }).build();
+
+        let mut cursor = collection.find(doc! {}, options).await?;
+        let mut searches = Vec::new();
+
+        while let Some(search) = cursor.try_next().await? {
+            searches.push(search);
+        }
+
+        Ok(searches)
+    }
+
+    pub async fn find_saved_search(
+        &self,
+        name: &str,
+    ) -> Result<Option<SavedSearch>, Box<dyn Error>> {
+        let collection = self.get_saved_search_collection();
+        let result = collection.find_one(doc! { "name": name }, None).await?;
+        Ok(result)
+    }
+
+    pub async fn delete_saved_search(&self, name: &str) -> Result<bool, Box<dyn Error>> {
+        let collection = self.get_saved_search_collection();
+        let result = collection.delete_one(doc! { "name": name }, None).await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    /// Stores `draft`, replacing the draft with its ID if it has one.
+    /// Returns the ID of the draft.
+    pub async fn save_rating_draft(
+        &self,
+        mut draft: RatingDraft,
+    ) -> Result<String, Box<dyn Error>> {
+        let collection = self.get_rating_draft_collection();
+        let id = *draft.id.get_or_insert_with(ObjectId::new);
+        let options = ReplaceOptions::builder().upsert(true).build();
+
+        collection
+            .replace_one(doc! { "_id": id }, draft, options)
+            .await?;
+        Ok(id.to_hex())
+    }
+
+    /// Every rating draft, most recently saved first.
+    pub async fn list_rating_drafts(&self) -> Result<Vec<RatingDraft>, Box<dyn Error>> {
+        let collection = self.get_rating_draft_collection();
+        let options = FindOptions::builder().sort(doc! { "saved_at": -1 }).build();
+
+        let mut cursor = collection.find(doc! {}, options).await?;
+        let mut drafts = Vec::new();
+
+        while let Some(draft) = cursor.try_next().await? {
+            drafts.push(draft);
+        }
+
+        Ok(drafts)
+    }
+
+    pub async fn find_rating_draft(&self, id: &str) -> Result<Option<RatingDraft>, Box<dyn Error>> {
+        let collection = self.get_rating_draft_collection();
+        let result = collection
+            .find_one(doc! { "_id": draft_id(id)? }, None)
+            .await?;
+        Ok(result)
+    }
+
+    pub async fn delete_rating_draft(&self, id: &str) -> Result<bool, Box<dyn Error>> {
+        let collection = self.get_rating_draft_collection();
+        let result = collection
+            .delete_one(doc! { "_id": draft_id(id)? }, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    /// Runs the search saved under `name`, recording when it was last run.
+    pub async fn run_saved_search(
+        &self,
+        name: &str,
+    ) -> Result<Vec<DataManagementPlan>, Box<dyn Error>> {
+        let search = self.find_saved_search(name).await?.ok_or_else(|| {
+            IoError::new(
+                ErrorKind::NotFound,
+                format!("No saved search named '{}'", name),
+            )
+        })?;
+
+        let dmps = self.find_dmps(&search.query).await?;
+
+        self.get_saved_search_collection()
+            .update_one(
+                doc! { "name": name },
+                doc! { "$set": { "last_run": mongodb::bson::DateTime::now() } },
+                None,
+            )
+            .await?;
+
+        Ok(dmps)
+    }
+
+    /// Runs the search saved under `name` and exports its results the same
+    /// way `export_all_dmps` exports every DMP.
+    pub async fn export_saved_search(
+        &self,
+        name: &str,
+        file_path: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        let dmps = self.run_saved_search(name).await?;
+        write_dmps(&dmps, file_path)
+    }
+
+    /// Appends `annotation` to the annotations of a DMP section.
+    pub async fn add_annotation(
+        &self,
+        dmp_id: &str,
+        id_type: &str,
+        section_title: &str,
+        annotation: Annotation,
+    ) -> Result<bool, Box<dyn Error>> {
+        let collection = self.get_dmp_collection();
+        let filter = dmp_filter(
+            dmp_id,
+            id_type,
+            doc! {
+                "sections.title": section_title
+            },
+        );
+
+        let update = doc! {
+            "$push": { "sections.$.annotations": bson::to_bson(&annotation)? },
+            "$set": { "last_modified": mongodb::bson::DateTime::now() }
+        };
+
+        let result = collection.update_one(filter, update, None).await?;
+        Ok(result.modified_count > 0)
+    }
+
+    /// Adds a cross-reference from a section of a DMP to another section of
+    /// the same DMP or, if `reference.target_dmp` is set, of another DMP.
+    /// Fails if either DMP or section does not exist. The reference's section
+    /// title is stored as spelled in the target DMP.
+    pub async fn add_cross_reference(
+        &self,
+        dmp_id: &str,
+        id_type: &str,
+        section_title: &str,
+        mut reference: SectionCrossReference,
+    ) -> Result<bool, Box<dyn Error>> {
+        let not_found = |message: String| IoError::new(ErrorKind::NotFound, message);
+
+        let dmp = self
+            .find_dmp_by_id(dmp_id, id_type)
+            .await?
+            .ok_or_else(|| not_found(format!("DMP {}/{} not found", id_type, dmp_id)))?;
+
+        if !dmp.sections.iter().any(|s| s.title == section_title) {
+            return Err(Box::new(not_found(format!(
+                "Section '{}' not found in DMP {}/{}",
+                section_title, id_type, dmp_id
+            ))));
+        }
+
+        let target = match reference.target_dmp {
+            Some(ref target) => self
+                .find_dmp_by_id(&target.identifier, target.id_type.as_str())
+                .await?
+                .ok_or_else(|| {
+                    not_found(format!(
+                        "Referenced DMP {}/{} not found",
+                        target.id_type, target.identifier
+                    ))
+                })?,
+            None => dmp,
+        };
+
+        let target_section = target
+            .sections
+            .iter()
+            .find(|s| fold(&s.title) == fold(&reference.section_title))
+            .ok_or_else(|| {
+                not_found(format!(
+                    "Referenced section '{}' not found in DMP {}/{}",
+                    reference.section_title, target.dmp_id.id_type, target.dmp_id.identifier
+                ))
+            })?;
+        reference.section_title = target_section.title.clone();
+        if reference.target_dmp.is_some() {
+            reference.target_dmp = Some(target.dmp_id.clone());
+        }
+
+        let collection = self.get_dmp_collection();
+        let filter = dmp_filter(
+            dmp_id,
+            id_type,
+            doc! {
+                "sections.title": section_title
+            },
+        );
+
+        // Sections stored without cross-references hold null, which $push
+        // cannot append to
+        collection
+            .update_one(
+                dmp_filter(
+                    dmp_id,
+                    id_type,
+                    doc! {
+                        "sections": {
+                            "$elemMatch": { "title": section_title, "cross_references": null }
+                        }
+                    },
+                ),
+                doc! { "$set": { "sections.$.cross_references": [] } },
+                None,
+            )
+            .await?;
+
+        let update = doc! {
+            "$push": { "sections.$.cross_references": bson::to_bson(&reference)? },
+            "$set": { "last_modified": mongodb::bson::DateTime::now() }
+        };
+
+        let result = collection.update_one(filter, update, None).await?;
+        Ok(result.modified_count > 0)
+    }
+
+    /// Returns the DMP array holding entities of `entity_type` and the path
+    /// of their identifier within an entry.
+    fn related_entity_paths(entity_type: &str) -> Result<(&'static str, String), IoError> {
+        let (array, id_field, _) = related_entity_array(entity_type)
+            .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "Invalid entity type"))?;
+        Ok((array, format!("{}.identifier", id_field)))
+    }
+
+    /// Attaches a related entity to a DMP. Fails if the DMP already lists an
+    /// entity of the same kind with the same identifier.
+    pub async fn add_related_entity(
+        &self,
+        dmp_id: &str,
+        id_type: &str,
+        entity: &RelatedEntity,
+    ) -> Result<bool, Box<dyn Error>> {
+        let collection = self.get_dmp_collection();
+        let (array, id_path) = Self::related_entity_paths(entity.entity_type())?;
+        let identifier = &entity.identifier().identifier;
+
+        let duplicate = dmp_filter(
+            dmp_id,
+            id_type,
+            doc! {
+                array: {
+                    "$elemMatch": {
+                        id_path: { "$in": identifier_variants(identifier, None) }
+                    }
+                }
+            },
+        );
+        if collection.count_documents(duplicate, None).await? > 0 {
+            return Err(Box::new(IoError::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "DMP {}/{} already lists the {} {}",
+                    id_type,
+                    dmp_id,
+                    entity.entity_type(),
+                    identifier
+                ),
+            )));
+        }
+
+        // DMPs stored without related entities of this kind hold null, which
+        // $push cannot append to
+        collection
+            .update_one(
+                dmp_filter(
+                    dmp_id,
+                    id_type,
+                    doc! {
+                        array: null
+                    },
+                ),
+                doc! { "$set": { array: [] } },
+                None,
+            )
+            .await?;
+
+        let filter = dmp_filter(dmp_id, id_type, doc! {});
+        let update = doc! {
+            "$push": { array: entity.to_bson()? },
+            "$set": { "last_modified": mongodb::bson::DateTime::now() }
         };
 
+        let result = collection.update_one(filter, update, None).await?;
+        Ok(result.modified_count > 0)
+    }
+
+    /// Replaces the related entity of `entity_type` identified by
+    /// `entity_id` with `entity`. Fails if `entity` changes the identifier to
+    /// one the DMP already lists.
+    pub async fn update_related_entity(
+        &self,
+        dmp_id: &str,
+        id_type: &str,
+        entity_type: &str,
+        entity_id: &str,
+        entity: &RelatedEntity,
+    ) -> Result<bool, Box<dyn Error>> {
+        let collection = self.get_dmp_collection();
+        let (array, id_path) = Self::related_entity_paths(entity_type)?;
+        let new_id = &entity.identifier().identifier;
+
+        if !same_identifier(new_id, entity_id) {
+            let duplicate = dmp_filter(
+                dmp_id,
+                id_type,
+                doc! {
+                    array: {
+                        "$elemMatch": {
+                            id_path.as_str(): { "$in": identifier_variants(new_id, None) }
+                        }
+                    }
+                },
+            );
+            if collection.count_documents(duplicate, None).await? > 0 {
+                return Err(Box::new(IoError::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "DMP {}/{} already lists the {} {}",
+                        id_type, dmp_id, entity_type, new_id
+                    ),
+                )));
+            }
+        }
+
+        let filter = dmp_filter(
+            dmp_id,
+            id_type,
+            doc! {
+                array: {
+                    "$elemMatch": {
+                        id_path: { "$in": identifier_variants(entity_id, None) }
+                    }
+                }
+            },
+        );
         let update = doc! {
             "$set": {
-                "sections.$.rating": rating as i32,
-                "sections.$.comments": comments,
+                format!("{}.$", array): entity.to_bson()?,
                 "last_modified": mongodb::bson::DateTime::now()
             }
         };
@@ -211,4 +1381,33 @@ impl DatabaseManager {
         let result = collection.update_one(filter, update, None).await?;
         Ok(result.modified_count > 0)
     }
+
+    /// Removes the related entity of `entity_type` identified by `entity_id`
+    /// from a DMP, returning `false` if the DMP does not list it.
+    pub async fn remove_related_entity(
+        &self,
+        dmp_id: &str,
+        id_type: &str,
+        entity_type: &str,
+        entity_id: &str,
+    ) -> Result<bool, Box<dyn Error>> {
+        let collection = self.get_dmp_collection();
+        let (array, id_path) = Self::related_entity_paths(entity_type)?;
+        let variants = identifier_variants(entity_id, None);
+
+        let filter = dmp_filter(
+            dmp_id,
+            id_type,
+            doc! {
+                format!("{}.{}", array, id_path): { "$in": &variants }
+            },
+        );
+        let update = doc! {
+            "$pull": { array: { id_path: { "$in": &variants } } },
+            "$set": { "last_modified": mongodb::bson::DateTime::now() }
+        };
+
+        let result = collection.update_one(filter, update, None).await?;
+        Ok(result.modified_count > 0)
+    }
 }
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use crate::identifiers::canonical_form;
+use crate::related::{related_entities, RelatedEntity};
+use crate::report::escape_html;
+use crate::{DataManagementPlan, RelationType};
+
+/// GraphML attributes as (name, element, type).
+const GRAPHML_KEYS: [(&str, &str, &str); 7] = [
+    ("kind", "node", "string"),
+    ("identifier", "node", "string"),
+    ("id_type", "node", "string"),
+    ("label", "node", "string"),
+    ("depth", "node", "int"),
+    ("stored", "node", "boolean"),
+    ("relation", "edge", "string"),
+];
+
+/// A DMP or research output reached while walking relationships.
+#[derive(Debug)]
+pub struct GraphNode {
+    /// One of `ENTITY_TYPES`
+    pub kind: &'static str,
+    pub identifier: String,
+    pub id_type: String,
+    pub label: String,
+    /// Number of hops from the starting node
+    pub depth: usize,
+    /// Whether the node is a DMP stored in this database
+    pub stored: bool,
+}
+
+/// A relationship listed by the DMP `from` to the entity `to`, read as
+/// "`from` `relation` `to`".
+#[derive(Debug)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    pub relation: RelationType,
+}
+
+/// The DMPs and research outputs connected to a starting node by related
+/// entity entries, up to a number of hops.
+#[derive(Debug, Default)]
+pub struct RelationGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    // Nodes by kind and canonical identifier
+    index: HashMap<(&'static str, String), usize>,
+}
+
+impl RelationGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the index of the node for `kind`/`identifier`, adding it at
+    /// `depth` if it is not in the graph yet.
+    pub fn node(
+        &mut self,
+        kind: &'static str,
+        identifier: &str,
+        id_type: &str,
+        label: &str,
+        depth: usize,
+    ) -> usize {
+        let key = (kind, canonical_form(identifier));
+        if let Some(&index) = self.index.get(&key) {
+            return index;
+        }
+
+        self.nodes.push(GraphNode {
+            kind,
+            identifier: identifier.to_string(),
+            id_type: id_type.to_string(),
+            label: label.to_string(),
+            depth,
+            stored: false,
+        });
+        self.index.insert(key, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// Adds the node of a stored DMP, labelled with its title.
+    pub fn dmp_node(&mut self, dmp: &DataManagementPlan, depth: usize) -> usize {
+        let index = self.node(
+            "dmp",
+            &dmp.dmp_id.identifier,
+            dmp.dmp_id.id_type.as_str(),
+            &dmp.title,
+            depth,
+        );
+        let node = &mut self.nodes[index];
+        node.stored = true;
+        node.label = dmp.title.clone();
+        index
+    }
+
+    /// Adds the node of a related entity.
+    pub fn entity_node(&mut self, entity: &RelatedEntity, depth: usize) -> usize {
+        self.node(
+            entity.entity_type(),
+            &entity.identifier().identifier,
+            entity.identifier().id_type.as_str(),
+            entity.title(),
+            depth,
+        )
+    }
+
+    /// Adds an edge unless the same relationship is already in the graph.
+    pub fn edge(&mut self, from: usize, to: usize, relation: &RelationType) {
+        let exists = self
+            .edges
+            .iter()
+            .any(|e| e.from == from && e.to == to && e.relation == *relation);
+        if !exists {
+            self.edges.push(GraphEdge {
+                from,
+                to,
+                relation: relation.clone(),
+            });
+        }
+    }
+
+    /// Adds the edges from the stored DMP at `from` to each of its related
+    /// entities allowed by `relations`, returning the entity nodes reached.
+    pub fn expand_dmp(
+        &mut self,
+        from: usize,
+        dmp: &DataManagementPlan,
+        relations: &[RelationType],
+    ) -> Vec<usize> {
+        let depth = self.nodes[from].depth + 1;
+        let mut reached = Vec::new();
+
+        for entity in related_entities(dmp) {
+            if !follows(relations, entity.relationship()) {
+                continue;
+            }
+            let to = self.entity_node(&entity, depth);
+            self.edge(from, to, entity.relationship());
+            reached.push(to);
+        }
+
+        reached
+    }
+
+    /// Renders the graph as plain text: the nodes by distance from the start,
+    /// then one line per relationship.
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("Nodes:\n");
+        let mut order: Vec<usize> = (0..self.nodes.len()).collect();
+        order.sort_by_key(|&i| self.nodes[i].depth);
+
+        for i in order {
+            let node = &self.nodes[i];
+            let distance = match node.depth {
+                0 => "start".to_string(),
+                1 => "1 hop".to_string(),
+                depth => format!("{} hops", depth),
+            };
+            text.push_str(&format!(
+                "   [{}] {} {}/{}: {} ({})\n",
+                i + 1,
+                node.kind,
+                node.id_type,
+                node.identifier,
+                node.label,
+                distance
+            ));
+        }
+
+        text.push_str("Relationships:\n");
+        for edge in &self.edges {
+            text.push_str(&format!(
+                "   [{}] {} {} [{}] {}\n",
+                edge.from + 1,
+                self.nodes[edge.from].label,
+                edge.relation,
+                edge.to + 1,
+                self.nodes[edge.to].label
+            ));
+        }
+
+        text
+    }
+
+    /// Renders the graph in Graphviz DOT format.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dmp_relations {\n    rankdir=LR;\n");
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            let shape = match node.kind {
+                "dataset" => "cylinder",
+                "publication" => "note",
+                "software" => "component",
+                _ => "box",
+            };
+            let style = if node.kind == "dmp" && !node.stored {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            dot.push_str(&format!(
+                "    n{} [label=\"{}\\n{}/{}\", shape={}{}];\n",
+                i,
+                escape_dot(&node.label),
+                escape_dot(&node.id_type),
+                escape_dot(&node.identifier),
+                shape,
+                style
+            ));
+        }
+
+        for edge in &self.edges {
+            dot.push_str(&format!(
+                "    n{} -> n{} [label=\"{}\"];\n",
+                edge.from,
+                edge.to,
+                escape_dot(edge.relation.as_str())
+            ));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the graph as GraphML.
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        for (key, owner, key_type) in GRAPHML_KEYS {
+            xml.push_str(&format!(
+                "  <key id=\"{0}\" for=\"{1}\" attr.name=\"{0}\" attr.type=\"{2}\"/>\n",
+                key, owner, key_type
+            ));
+        }
+        xml.push_str("  <graph id=\"dmp_relations\" edgedefault=\"directed\">\n");
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            xml.push_str(&format!("    <node id=\"n{}\">\n", i));
+            let values = [
+                ("kind", node.kind.to_string()),
+                ("identifier", escape_html(&node.identifier)),
+                ("id_type", escape_html(&node.id_type)),
+                ("label", escape_html(&node.label)),
+                ("depth", node.depth.to_string()),
+                ("stored", node.stored.to_string()),
+            ];
+            for (key, value) in values {
+                xml.push_str(&format!("      <data key=\"{}\">{}</data>\n", key, value));
+            }
+            xml.push_str("    </node>\n");
+        }
+
+        for (i, edge) in self.edges.iter().enumerate() {
+            xml.push_str(&format!(
+                "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\">\n",
+                i, edge.from, edge.to
+            ));
+            xml.push_str(&format!(
+                "      <data key=\"relation\">{}</data>\n",
+                escape_html(edge.relation.as_str())
+            ));
+            xml.push_str("    </edge>\n");
+        }
+
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
+    /// Writes the graph to `file_path` as DOT, or as GraphML if the path
+    /// ends in `.graphml` or `.xml`.
+    pub fn write(&self, file_path: &str) -> io::Result<()> {
+        let lower = file_path.to_lowercase();
+        if lower.ends_with(".graphml") || lower.ends_with(".xml") {
+            fs::write(file_path, self.to_graphml())
+        } else {
+            fs::write(file_path, self.to_dot())
+        }
+    }
+}
+
+/// Returns `true` if an edge of `relation` is followed when only
+/// `relations` are wanted; an empty list follows every relation.
+pub fn follows(relations: &[RelationType], relation: &RelationType) -> bool {
+    relations.is_empty() || relations.contains(relation)
+}
+
+/// Escapes a DOT double-quoted string.
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
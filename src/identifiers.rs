@@ -0,0 +1,432 @@
+use crate::related::{related_entities, RelatedEntity};
+use crate::{AuthorIdType, DataManagementPlan, Identifier, IdentifierType};
+
+const DOI_PREFIXES: [&str; 7] = [
+    "https://doi.org/",
+    "http://doi.org/",
+    "https://dx.doi.org/",
+    "http://dx.doi.org/",
+    "doi.org/",
+    "dx.doi.org/",
+    "doi:",
+];
+
+const HANDLE_PREFIXES: [&str; 4] = [
+    "https://hdl.handle.net/",
+    "http://hdl.handle.net/",
+    "hdl.handle.net/",
+    "hdl:",
+];
+
+const ORCID_PREFIXES: [&str; 4] = [
+    "https://orcid.org/",
+    "http://orcid.org/",
+    "orcid.org/",
+    "orcid:",
+];
+
+const ISNI_PREFIXES: [&str; 4] = [
+    "https://isni.org/isni/",
+    "http://isni.org/isni/",
+    "isni.org/isni/",
+    "isni:",
+];
+
+/// Strips the first of `prefixes` that `value` starts with, ignoring case.
+fn strip_prefixes<'a>(value: &'a str, prefixes: &[&str]) -> Option<&'a str> {
+    prefixes.iter().find_map(|prefix| {
+        value
+            .get(..prefix.len())
+            .filter(|start| start.eq_ignore_ascii_case(prefix))
+            .map(|_| value[prefix.len()..].trim_start())
+    })
+}
+
+/// Canonical DOI: `10.<registrant>/<suffix>` in lowercase, as DOIs are
+/// case-insensitive. Accepts `doi:` and resolver URL forms.
+pub fn normalize_doi(value: &str) -> Result<String, String> {
+    let trimmed = value.trim();
+    let doi = strip_prefixes(trimmed, &DOI_PREFIXES).unwrap_or(trimmed);
+
+    let valid = match doi.split_once('/') {
+        Some((prefix, suffix)) => {
+            prefix.starts_with("10.")
+                && prefix[3..]
+                    .split('.')
+                    .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+                && !suffix.is_empty()
+                && !suffix.chars().any(char::is_whitespace)
+        }
+        None => false,
+    };
+
+    if valid {
+        Ok(doi.to_lowercase())
+    } else {
+        Err(format!(
+            "'{}' is not a valid DOI; expected e.g. 10.1234/abc",
+            value.trim()
+        ))
+    }
+}
+
+/// Canonical handle: `<prefix>/<suffix>` in lowercase. Accepts `hdl:` and
+/// resolver URL forms.
+pub fn normalize_handle(value: &str) -> Result<String, String> {
+    let trimmed = value.trim();
+    let handle = strip_prefixes(trimmed, &HANDLE_PREFIXES).unwrap_or(trimmed);
+
+    let valid = match handle.split_once('/') {
+        Some((prefix, suffix)) => {
+            !prefix.is_empty()
+                && prefix.chars().all(|c| c.is_ascii_digit() || c == '.')
+                && !suffix.is_empty()
+                && !suffix.chars().any(char::is_whitespace)
+        }
+        None => false,
+    };
+
+    if valid {
+        Ok(handle.to_lowercase())
+    } else {
+        Err(format!(
+            "'{}' is not a valid handle; expected e.g. 20.500.12345/abc",
+            value.trim()
+        ))
+    }
+}
+
+/// Canonical ARK: `ark:<NAAN>/<name>`. Accepts the older `ark:/` form and
+/// ARKs behind any resolver, e.g. `https://n2t.net/ark:/12345/x`.
+pub fn normalize_ark(value: &str) -> Result<String, String> {
+    let invalid = || {
+        format!(
+            "'{}' is not a valid ARK; expected e.g. ark:12345/abc",
+            value.trim()
+        )
+    };
+
+    let lower = value.to_ascii_lowercase();
+    let start = lower.find("ark:").ok_or_else(invalid)?;
+    let ark = value[start + 4..].trim().trim_start_matches('/');
+
+    match ark.split_once('/') {
+        Some((naan, name))
+            if !naan.is_empty()
+                && naan.chars().all(|c| c.is_ascii_alphanumeric())
+                && !name.is_empty()
+                && !name.chars().any(char::is_whitespace) =>
+        {
+            Ok(format!("ark:{}/{}", naan.to_lowercase(), name))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Canonical URL: scheme and host in lowercase. Only `http` and `https`
+/// URLs are accepted.
+pub fn normalize_url(value: &str) -> Result<String, String> {
+    let url = value.trim();
+    let (scheme, rest) = url
+        .split_once("://")
+        .filter(|(scheme, rest)| {
+            (scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https"))
+                && !rest.is_empty()
+                && !rest.chars().any(char::is_whitespace)
+        })
+        .ok_or_else(|| format!("'{}' is not a valid http(s) URL", url))?;
+
+    let (host, path) = match rest.find('/') {
+        Some(slash) => rest.split_at(slash),
+        None => (rest, ""),
+    };
+
+    Ok(format!(
+        "{}://{}{}",
+        scheme.to_lowercase(),
+        host.to_lowercase(),
+        path
+    ))
+}
+
+/// ISO 7064 MOD 11-2 check character of `digits`, as used by ORCID and ISNI.
+fn mod11_2_check(digits: &str) -> char {
+    let total = digits
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .fold(0, |total, digit| (total + digit) * 2);
+
+    match (12 - total % 11) % 11 {
+        10 => 'X',
+        check => char::from_digit(check, 10).unwrap_or('0'),
+    }
+}
+
+/// Strips `prefixes`, spaces and hyphens and validates the 16-character
+/// ORCID/ISNI form, returning it in uppercase.
+fn checked_16_digits(value: &str, prefixes: &[&str], kind: &str) -> Result<String, String> {
+    let trimmed = value.trim();
+    let compact: String = strip_prefixes(trimmed, prefixes)
+        .unwrap_or(trimmed)
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_uppercase();
+
+    let well_formed = compact.is_ascii()
+        && compact.len() == 16
+        && compact[..15].chars().all(|c| c.is_ascii_digit())
+        && compact[15..]
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == 'X');
+    if !well_formed {
+        return Err(format!(
+            "'{}' is not a valid {}; expected 16 digits, the last of which may be X",
+            trimmed, kind
+        ));
+    }
+
+    let check = mod11_2_check(&compact[..15]);
+    if !compact.ends_with(check) {
+        return Err(format!(
+            "'{}' is not a valid {}; the check digit should be {}",
+            trimmed, kind, check
+        ));
+    }
+
+    Ok(compact)
+}
+
+/// Canonical ORCID iD: `0000-0002-1825-0097`, with the checksum verified.
+/// Accepts the `https://orcid.org/` form.
+pub fn normalize_orcid(value: &str) -> Result<String, String> {
+    let compact = checked_16_digits(value, &ORCID_PREFIXES, "ORCID iD")?;
+    Ok(format!(
+        "{}-{}-{}-{}",
+        &compact[0..4],
+        &compact[4..8],
+        &compact[8..12],
+        &compact[12..16]
+    ))
+}
+
+/// Canonical ISNI: 16 characters without spaces, with the checksum
+/// verified. Accepts the spaced and `https://isni.org/isni/` forms.
+pub fn normalize_isni(value: &str) -> Result<String, String> {
+    checked_16_digits(value, &ISNI_PREFIXES, "ISNI")
+}
+
+/// Canonical URN: `urn:<NID>:<NSS>` with the scheme and namespace
+/// identifier in lowercase.
+pub fn normalize_urn(value: &str) -> Result<String, String> {
+    let trimmed = value.trim();
+    let invalid = || {
+        format!(
+            "'{}' is not a valid URN; expected e.g. urn:nbn:de:1234-5678",
+            trimmed
+        )
+    };
+
+    let rest = strip_prefixes(trimmed, &["urn:"]).ok_or_else(invalid)?;
+    match rest.split_once(':') {
+        Some((nid, nss))
+            if !nid.is_empty()
+                && nid.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                && !nss.is_empty()
+                && !nss.chars().any(char::is_whitespace) =>
+        {
+            Ok(format!("urn:{}:{}", nid.to_lowercase(), nss))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Normalizes an identifier of `id_type`, returning the canonical type and
+/// identifier. DOI, handle, ARK and URN forms given as `url` or `other` are
+/// stored as DOIs, handles, ARKs and URNs.
+pub fn normalize_identifier(
+    value: &str,
+    id_type: &IdentifierType,
+) -> Result<(IdentifierType, String), String> {
+    match id_type {
+        IdentifierType::Doi => Ok((IdentifierType::Doi, normalize_doi(value)?)),
+        IdentifierType::Handle => Ok((IdentifierType::Handle, normalize_handle(value)?)),
+        IdentifierType::Ark => Ok((IdentifierType::Ark, normalize_ark(value)?)),
+        IdentifierType::Urn => Ok((IdentifierType::Urn, normalize_urn(value)?)),
+        IdentifierType::Url => match detect_identifier(value) {
+            Some(detected) => Ok(detected),
+            None => Ok((IdentifierType::Url, normalize_url(value)?)),
+        },
+        IdentifierType::Other(_) => match detect_identifier(value) {
+            Some(detected) => Ok(detected),
+            None => Ok((id_type.clone(), value.trim().to_string())),
+        },
+    }
+}
+
+/// Recognizes DOIs, handles, ARKs and URNs written with a prefix or resolver
+/// URL (or a bare DOI), returning the type and canonical identifier.
+pub fn detect_identifier(value: &str) -> Option<(IdentifierType, String)> {
+    let trimmed = value.trim();
+
+    if strip_prefixes(trimmed, &DOI_PREFIXES).is_some() || trimmed.starts_with("10.") {
+        return normalize_doi(trimmed)
+            .ok()
+            .map(|doi| (IdentifierType::Doi, doi));
+    }
+    if strip_prefixes(trimmed, &HANDLE_PREFIXES).is_some() {
+        return normalize_handle(trimmed)
+            .ok()
+            .map(|handle| (IdentifierType::Handle, handle));
+    }
+    if trimmed.to_ascii_lowercase().contains("ark:") {
+        return normalize_ark(trimmed)
+            .ok()
+            .map(|ark| (IdentifierType::Ark, ark));
+    }
+    if strip_prefixes(trimmed, &["urn:"]).is_some() {
+        return normalize_urn(trimmed)
+            .ok()
+            .map(|urn| (IdentifierType::Urn, urn));
+    }
+
+    None
+}
+
+/// Every stored form an identifier typed by a user may match: as typed,
+/// canonical for `id_type`, and canonical for the type its form suggests.
+/// Identifiers stored before normalization are matched as typed.
+pub fn identifier_variants(value: &str, id_type: Option<&str>) -> Vec<String> {
+    let mut variants = vec![value.trim().to_string()];
+
+    if let Some(Ok((_, canonical))) =
+        id_type.map(|t| normalize_identifier(value, &IdentifierType::from(t.to_string())))
+    {
+        variants.push(canonical);
+    }
+    if let Some((_, detected)) = detect_identifier(value) {
+        variants.push(detected);
+    }
+
+    variants.sort();
+    variants.dedup();
+    variants
+}
+
+/// Every stored form of an identifier type matching `id_type` as typed.
+pub fn id_type_variants(value: &str, id_type: &str) -> Vec<String> {
+    let typed = IdentifierType::from(id_type.to_string());
+    let mut variants = vec![
+        id_type.trim().to_string(),
+        id_type.trim().to_lowercase(),
+        typed.to_string(),
+    ];
+    if let Ok((canonical, _)) = normalize_identifier(value, &typed) {
+        variants.push(canonical.to_string());
+    }
+
+    variants.sort();
+    variants.dedup();
+    variants
+}
+
+/// Normalizes an identifier in place.
+pub fn normalize_identifier_in_place(identifier: &mut Identifier) -> Result<(), String> {
+    let (id_type, value) = normalize_identifier(&identifier.identifier, &identifier.id_type)?;
+    identifier.id_type = id_type;
+    identifier.identifier = value;
+    Ok(())
+}
+
+/// Validates and normalizes an author identifier of `id_type`. ORCID iDs and
+/// ISNIs are checksum-verified.
+pub fn normalize_author_identifier(
+    value: &str,
+    id_type: Option<&AuthorIdType>,
+) -> Result<String, String> {
+    match id_type {
+        Some(AuthorIdType::Orcid) => normalize_orcid(value),
+        Some(AuthorIdType::Isni) => normalize_isni(value),
+        _ => Ok(value.trim().to_string()),
+    }
+}
+
+/// Normalizes every identifier of `dmp` in place: its own, its authors' and
+/// those of its related entities. Fails on the first invalid one.
+pub fn normalize_dmp_identifiers(dmp: &mut DataManagementPlan) -> Result<(), String> {
+    normalize_identifier_in_place(&mut dmp.dmp_id)?;
+
+    for author in &mut dmp.authors {
+        if let Some(ref identifier) = author.identifier {
+            author.identifier = Some(
+                normalize_author_identifier(identifier, author.id_type.as_ref())
+                    .map_err(|e| format!("Author {}: {}", author.name, e))?,
+            );
+        }
+    }
+
+    for dataset in dmp.related_datasets.iter_mut().flatten() {
+        normalize_identifier_in_place(&mut dataset.dataset_id)?;
+    }
+    for publication in dmp.related_publications.iter_mut().flatten() {
+        normalize_identifier_in_place(&mut publication.publication_id)?;
+    }
+    for software in dmp.related_software.iter_mut().flatten() {
+        normalize_identifier_in_place(&mut software.software_id)?;
+    }
+    for related_dmp in dmp.related_dmps.iter_mut().flatten() {
+        normalize_identifier_in_place(&mut related_dmp.dmp_id)?;
+    }
+
+    Ok(())
+}
+
+/// The form identifiers of unknown type are compared in: canonical if it is
+/// recognizably a DOI, handle or ARK, otherwise as given.
+pub fn canonical_form(value: &str) -> String {
+    detect_identifier(value).map_or_else(|| value.trim().to_string(), |(_, canonical)| canonical)
+}
+
+/// Returns `true` if `a` and `b` are the same identifier once normalized.
+pub fn same_identifier(a: &str, b: &str) -> bool {
+    canonical_form(a) == canonical_form(b)
+}
+
+/// Returns the related entities of `dmp` whose identifier matches `identifier`.
+pub fn find_related_entities(
+    dmp: &DataManagementPlan,
+    entity_type: &str,
+    identifier: &str,
+) -> Vec<RelatedEntity> {
+    related_entities(dmp)
+        .into_iter()
+        .filter(|e| {
+            e.entity_type() == entity_type
+                && same_identifier(&e.identifier().identifier, identifier)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_16_digits_validates_orcid_and_isni() {
+        assert_eq!(
+            normalize_orcid("https://orcid.org/0000-0002-1825-0097").unwrap(),
+            "0000-0002-1825-0097"
+        );
+        assert_eq!(
+            normalize_isni("0000 0001 2103 2683").unwrap(),
+            "0000000121032683"
+        );
+        assert!(normalize_orcid("0000-0002-1825-0098")
+            .unwrap_err()
+            .contains("check digit"));
+        assert!(normalize_orcid("12345678901234é")
+            .unwrap_err()
+            .contains("expected 16 digits"));
+        assert!(normalize_isni("000000012103268é").is_err());
+    }
+}
@@ -1,44 +1,421 @@
-use crate::{db::DatabaseManager, Essay};
-use futures_util::stream::TryStreamExt;
-use mongodb::bson::doc;
-use serde_json;
+use crate::matching::parse_tags;
+use crate::migrations::SCHEMA_VERSION;
+use crate::{
+    db::DatabaseManager, Author, AuthorIdType, DataManagementPlan, Identifier, IdentifierType,
+    MachineActionable, ProjectInfo, Rating, RatingScale, Section, Subsection, VersionHistory,
+    RUBRIC_SECTIONS,
+};
+use mongodb::bson::DateTime;
+use serde::Deserialize;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Error, ErrorKind};
+use std::io::{BufReader, Error, ErrorKind};
 use std::sync::Arc;
 
-pub struct ImportExport {
-    db_manager: Arc<DatabaseManager>,
+/// An essay record from the archives kept before the application rated DMPs.
+#[derive(Debug, Deserialize)]
+struct Essay {
+    title: String,
+    author: String,
+    date: Option<DateTime>,
+    sections: Vec<EssaySection>,
+    metadata: Option<EssayMetadata>,
 }
 
-impl ImportExport {
-    pub fn new(db_manager: Arc<DatabaseManager>) -> Self {
-        Self { db_manager }
+#[derive(Debug, Deserialize)]
+struct EssaySection {
+    title: String,
+    content: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    usefulness_rating: Option<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EssayMetadata {
+    word_count: Option<u32>,
+    #[serde(default)]
+    categories: Vec<String>,
+}
+
+/// Lowercases `title` and joins its words with dashes, for use in identifiers.
+fn slug(title: &str) -> String {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+/// Converts a legacy essay into a DMP: the author becomes the only author,
+/// each essay section becomes a section rated with its usefulness rating
+/// (1-5), and the section content is kept as the section's text. Fails if a
+/// usefulness rating is outside the 1-5 scale. Sections that `rubric_scales.json`
+/// rates on another scale keep the 1-5 rating, as other ratings entered before
+/// their section's scale changed do.
+fn essay_to_dmp(essay: Essay) -> Result<DataManagementPlan, String> {
+    let date = essay.date.unwrap_or_else(DateTime::now);
+
+    let mut sections = Vec::new();
+    for section in essay.sections {
+        let rating = section
+            .usefulness_rating
+            .map(|r| Rating::new(RatingScale::OneToFive, r))
+            .transpose()
+            .map_err(|e| {
+                format!(
+                    "Essay '{}', section '{}': {}",
+                    essay.title, section.title, e
+                )
+            })?;
+        sections.push(Section {
+            title: section.title,
+            text: section.content.filter(|c| !c.trim().is_empty()),
+            rating,
+            tags: parse_tags(&section.tags.join(",")),
+            comments: None,
+            subsections: Vec::new(),
+            cross_references: None,
+            annotations: Vec::new(),
+        });
     }
 
-    pub async fn export_all_essays(
-        &self,
-        file_path: &str,
-    ) -> Result<usize, Box<dyn std::error::Error>> {
-        // Get all essays from the database
-        let collection = self.db_manager.get_essay_collection();
-        let mut cursor = collection.find(doc! {}, None).await?;
+    let mut changes = "Imported from legacy essay format".to_string();
+    if let Some(metadata) = essay.metadata {
+        if let Some(word_count) = metadata.word_count {
+            changes.push_str(&format!("; word count: {}", word_count));
+        }
+        if !metadata.categories.is_empty() {
+            changes.push_str(&format!("; categories: {}", metadata.categories.join(", ")));
+        }
+    }
+
+    Ok(DataManagementPlan {
+        id: None,
+        dmp_id: Identifier {
+            identifier: format!("legacy-essay:{}", slug(&essay.title)),
+            id_type: IdentifierType::Other("other".to_string()),
+        },
+        title: essay.title,
+        authors: vec![Author {
+            name: essay.author,
+            affiliation: None,
+            identifier: None,
+            id_type: None,
+            email: None,
+        }],
+        project_info: None,
+        created_date: date,
+        last_modified: DateTime::now(),
+        version: "1.0".to_string(),
+        overall_rating: None,
+        sections,
+        machine_actionable: None,
+        history: Some(vec![VersionHistory {
+            version: "1.0".to_string(),
+            date: DateTime::now(),
+            reviewer: "legacy import".to_string(),
+            changes,
+        }]),
+        metrics: None,
+        related_dmps: None,
+        related_datasets: None,
+        related_publications: None,
+        related_software: None,
+        schema_version: SCHEMA_VERSION,
+    })
+}
 
-        let mut essays = Vec::new();
+/// A plan in the RDA DMP Common Standard (maDMP) JSON format. Only the parts
+/// that map onto rated sections are read.
+#[derive(Debug, Deserialize)]
+struct MaDmpFile {
+    dmp: MaDmp,
+}
+
+#[derive(Debug, Deserialize)]
+struct MaDmp {
+    title: String,
+    description: Option<String>,
+    created: Option<String>,
+    dmp_id: MaDmpIdentifier,
+    contact: Option<MaDmpContact>,
+    ethical_issues_exist: Option<String>,
+    ethical_issues_description: Option<String>,
+    #[serde(default)]
+    project: Vec<MaDmpProject>,
+    #[serde(default)]
+    cost: Vec<MaDmpCost>,
+    #[serde(default)]
+    dataset: Vec<MaDmpDataset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MaDmpIdentifier {
+    identifier: String,
+    #[serde(rename = "type")]
+    id_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MaDmpContact {
+    name: String,
+    mbox: Option<String>,
+    contact_id: Option<MaDmpIdentifier>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MaDmpProject {
+    title: String,
+    #[serde(default)]
+    funding: Vec<MaDmpFunding>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MaDmpFunding {
+    funder_id: Option<MaDmpIdentifier>,
+    grant_id: Option<MaDmpIdentifier>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MaDmpCost {
+    title: String,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MaDmpDataset {
+    title: String,
+    description: Option<String>,
+    personal_data: Option<String>,
+    sensitive_data: Option<String>,
+    preservation_statement: Option<String>,
+    #[serde(default)]
+    data_quality_assurance: Vec<String>,
+    #[serde(default)]
+    metadata: Vec<MaDmpDescribed>,
+    #[serde(default)]
+    security_and_privacy: Vec<MaDmpDescribed>,
+    #[serde(default)]
+    distribution: Vec<MaDmpDistribution>,
+}
 
-        while let Some(essay) = cursor.try_next().await? {
-            essays.push(essay);
+/// Any maDMP object carrying a free-text description.
+#[derive(Debug, Deserialize)]
+struct MaDmpDescribed {
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MaDmpDistribution {
+    title: String,
+    description: Option<String>,
+    data_access: Option<String>,
+    host: Option<MaDmpHost>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MaDmpHost {
+    title: String,
+    storage_type: Option<String>,
+    backup_frequency: Option<String>,
+}
+
+/// Joins the non-empty paragraphs, or returns `None` if there are none.
+fn join_paragraphs(paragraphs: Vec<String>) -> Option<String> {
+    let paragraphs: Vec<String> = paragraphs
+        .into_iter()
+        .filter(|p| !p.trim().is_empty())
+        .collect();
+
+    if paragraphs.is_empty() {
+        None
+    } else {
+        Some(paragraphs.join("\n\n"))
+    }
+}
+
+/// Builds an unrated section of the given rubric title.
+fn unrated_section(title: &str, text: Option<String>, subsections: Vec<Subsection>) -> Section {
+    Section {
+        title: title.to_string(),
+        text,
+        rating: None,
+        tags: Vec::new(),
+        comments: None,
+        subsections,
+        cross_references: None,
+        annotations: Vec::new(),
+    }
+}
+
+/// Converts a maDMP into an unrated DMP, gathering the plan text relevant to
+/// each rubric section from its datasets, project, ethics and cost entries.
+/// Each dataset also gets a subsection holding its description.
+fn madmp_to_dmp(madmp: MaDmp) -> DataManagementPlan {
+    let created = madmp
+        .created
+        .as_deref()
+        .and_then(|date| DateTime::parse_rfc3339_str(date).ok())
+        .unwrap_or_else(DateTime::now);
+
+    let mut description = Vec::new();
+    let mut documentation = Vec::new();
+    let mut ethics = Vec::new();
+    let mut storage = Vec::new();
+    let mut sharing = Vec::new();
+    let mut dataset_subsections = Vec::new();
+
+    description.extend(madmp.description);
+
+    if let Some(exist) = madmp.ethical_issues_exist {
+        ethics.push(format!("Ethical issues exist: {}", exist));
+    }
+    ethics.extend(madmp.ethical_issues_description);
+
+    for dataset in madmp.dataset {
+        dataset_subsections.push(Subsection {
+            title: dataset.title.clone(),
+            text: dataset.description.clone(),
+            rating: None,
+            tags: Vec::new(),
+            comments: None,
+        });
+
+        documentation.extend(dataset.metadata.into_iter().filter_map(|m| m.description));
+        documentation.extend(dataset.data_quality_assurance);
+
+        if let Some(personal) = dataset.personal_data {
+            ethics.push(format!("{}: personal data: {}", dataset.title, personal));
         }
+        if let Some(sensitive) = dataset.sensitive_data {
+            ethics.push(format!("{}: sensitive data: {}", dataset.title, sensitive));
+        }
+        ethics.extend(
+            dataset
+                .security_and_privacy
+                .into_iter()
+                .filter_map(|s| s.description),
+        );
+
+        sharing.extend(dataset.preservation_statement);
+        for distribution in dataset.distribution {
+            sharing.extend(distribution.description);
+            if let Some(access) = distribution.data_access {
+                sharing.push(format!("{}: access: {}", distribution.title, access));
+            }
+            if let Some(host) = distribution.host {
+                let mut line = format!("{}: hosted at {}", distribution.title, host.title);
+                if let Some(storage_type) = host.storage_type {
+                    line.push_str(&format!(", storage: {}", storage_type));
+                }
+                if let Some(backup) = host.backup_frequency {
+                    line.push_str(&format!(", backups: {}", backup));
+                }
+                storage.push(line);
+            }
+        }
+    }
+
+    let resources = madmp
+        .cost
+        .into_iter()
+        .map(|cost| match cost.description {
+            Some(description) => format!("{}: {}", cost.title, description),
+            None => cost.title,
+        })
+        .collect();
 
-        // Write essays to file
-        let file = File::create(file_path)?;
-        let writer = BufWriter::new(file);
+    let sections = vec![
+        unrated_section(
+            RUBRIC_SECTIONS[0],
+            join_paragraphs(description),
+            dataset_subsections,
+        ),
+        unrated_section(
+            RUBRIC_SECTIONS[1],
+            join_paragraphs(documentation),
+            Vec::new(),
+        ),
+        unrated_section(RUBRIC_SECTIONS[2], join_paragraphs(ethics), Vec::new()),
+        unrated_section(RUBRIC_SECTIONS[3], join_paragraphs(storage), Vec::new()),
+        unrated_section(RUBRIC_SECTIONS[4], join_paragraphs(sharing), Vec::new()),
+        unrated_section(RUBRIC_SECTIONS[5], join_paragraphs(resources), Vec::new()),
+        unrated_section(RUBRIC_SECTIONS[6], None, Vec::new()),
+    ];
 
-        serde_json::to_writer_pretty(writer, &essays)?;
+    let authors = madmp
+        .contact
+        .map(|contact| Author {
+            name: contact.name,
+            affiliation: None,
+            id_type: contact
+                .contact_id
+                .as_ref()
+                .and_then(|id| id.id_type.parse::<AuthorIdType>().ok()),
+            identifier: contact.contact_id.map(|id| id.identifier),
+            email: contact.mbox,
+        })
+        .into_iter()
+        .collect();
 
-        Ok(essays.len())
+    let project_info = madmp.project.into_iter().next().map(|project| {
+        let funding = project.funding.into_iter().next();
+        ProjectInfo {
+            project_title: project.title,
+            grant_id: funding
+                .as_ref()
+                .and_then(|f| f.grant_id.as_ref())
+                .map(|id| id.identifier.clone()),
+            funder: funding.and_then(|f| f.funder_id).map(|id| id.identifier),
+            institution: None,
+            start_date: None,
+            end_date: None,
+        }
+    });
+
+    DataManagementPlan {
+        id: None,
+        title: madmp.title,
+        dmp_id: Identifier {
+            identifier: madmp.dmp_id.identifier,
+            id_type: IdentifierType::from(madmp.dmp_id.id_type),
+        },
+        authors,
+        project_info,
+        created_date: created,
+        last_modified: DateTime::now(),
+        version: "1.0".to_string(),
+        overall_rating: None,
+        sections,
+        machine_actionable: Some(MachineActionable {
+            is_machine_actionable: true,
+            format: Some("RDA DMP Common Standard".to_string()),
+            validation_date: None,
+        }),
+        history: None,
+        metrics: None,
+        related_dmps: None,
+        related_datasets: None,
+        related_publications: None,
+        related_software: None,
+        schema_version: SCHEMA_VERSION,
     }
+}
+
+pub struct ImportExport {
+    db_manager: Arc<DatabaseManager>,
+}
 
-    pub async fn import_from_file(
+impl ImportExport {
+    pub fn new(db_manager: Arc<DatabaseManager>) -> Self {
+        Self { db_manager }
+    }
+
+    /// Imports an archive of legacy essay records (see `test_essays.json`),
+    /// storing each essay as a DMP the way `DatabaseManager::import_dmps`
+    /// stores exported ones.
+    pub async fn import_legacy_essays(
         &self,
         file_path: &str,
     ) -> Result<usize, Box<dyn std::error::Error>> {
@@ -53,19 +430,27 @@ impl ImportExport {
             return Ok(0);
         }
 
-        // Insert essays into database
-        let collection = self.db_manager.get_essay_collection();
-        let mut count = 0;
+        // Convert every essay first so an invalid one imports nothing
+        let dmps = essays
+            .into_iter()
+            .map(essay_to_dmp)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
 
-        for essay in essays {
-            // Make sure to clear any existing ID to prevent conflicts
-            let mut essay_to_insert = essay;
-            essay_to_insert.id = None;
+        self.db_manager.import_dmps(dmps).await
+    }
 
-            collection.insert_one(essay_to_insert, None).await?;
-            count += 1;
-        }
+    /// Imports a plan in the RDA DMP Common Standard (maDMP) JSON format,
+    /// storing it as an unrated DMP whose sections hold the plan text.
+    pub async fn import_madmp(&self, file_path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let file = File::open(file_path)
+            .map_err(|e| Error::new(ErrorKind::NotFound, format!("Failed to open file: {}", e)))?;
+
+        let reader = BufReader::new(file);
+        let madmp: MaDmpFile = serde_json::from_reader(reader)?;
+
+        self.db_manager.add_dmp(madmp_to_dmp(madmp.dmp)).await?;
 
-        Ok(count)
+        Ok(1)
     }
 }
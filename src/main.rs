@@ -1,14 +1,49 @@
+mod analytics;
 mod cli;
+mod commands;
+mod conflict;
+mod dashboard;
 mod db;
+mod graph;
+mod identifiers;
+mod import_export;
+mod matching;
+mod migrations;
+mod openapi;
+mod query;
+mod query_parser;
+mod related;
+mod report;
+mod scales;
+mod server;
+mod text_search;
+mod trends;
+mod update;
+mod vocabulary;
+mod web;
 
 use cli::DmpCli;
 use db::DatabaseManager;
+use import_export::ImportExport;
 use mongodb::bson::DateTime;
+use query::DmpQuery;
+use scales::{RubricScales, RUBRIC_SCALES_FILE};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::io::{self, Write};
 use std::sync::Arc;
 
+/// Titles of the standard DMP sections rated for every plan, in rubric order.
+pub const RUBRIC_SECTIONS: [&str; 7] = [
+    "Data Description & Collection",
+    "Documentation & Metadata",
+    "Ethical & Legal Compliance",
+    "Storage & Backup During the Project",
+    "Data Sharing & Long-Term Preservation",
+    "Responsibilities & Resources",
+    "FAIR Principles",
+];
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Author {
     name: String,
@@ -27,10 +62,23 @@ pub enum AuthorIdType {
     Other,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The scheme of a DMP or related entity identifier. Stored in lowercase;
+/// values that are not recognized are kept as given in `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum IdentifierType {
+    Doi,
+    Handle,
+    Ark,
+    Url,
+    Urn,
+    Other(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Identifier {
     identifier: String,
-    id_type: String, // doi, handle, ark, url, other
+    id_type: IdentifierType,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -43,27 +91,27 @@ pub struct ProjectInfo {
     end_date: Option<DateTime>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RelatedDMP {
     dmp_id: Identifier,
-    relationship_type: String,
+    relationship_type: RelationType,
     title: String,
     relationship_notes: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RelatedDataset {
     dataset_id: Identifier,
-    relationship_type: String,
+    relationship_type: RelationType,
     title: String,
     repository: Option<String>,
     url: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RelatedPublication {
     publication_id: Identifier,
-    relationship_type: String,
+    relationship_type: RelationType,
     title: String,
     authors: Vec<String>,
     journal: Option<String>,
@@ -71,25 +119,146 @@ pub struct RelatedPublication {
     url: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RelatedSoftware {
     software_id: Identifier,
     name: String,
     version: Option<String>,
-    relationship: String,
+    relationship: RelationType,
     url: Option<String>,
 }
 
+/// How a DMP relates to a related entity, following the DataCite
+/// `relationType` vocabulary. Stored as the DataCite name; values outside
+/// the vocabulary are kept as given in `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum RelationType {
+    IsCitedBy,
+    Cites,
+    IsSupplementTo,
+    IsSupplementedBy,
+    IsContinuedBy,
+    Continues,
+    IsDescribedBy,
+    Describes,
+    HasMetadata,
+    IsMetadataFor,
+    HasVersion,
+    IsVersionOf,
+    IsNewVersionOf,
+    IsPreviousVersionOf,
+    IsPartOf,
+    HasPart,
+    IsPublishedIn,
+    IsReferencedBy,
+    References,
+    IsDocumentedBy,
+    Documents,
+    IsCompiledBy,
+    Compiles,
+    IsVariantFormOf,
+    IsOriginalFormOf,
+    IsIdenticalTo,
+    IsReviewedBy,
+    Reviews,
+    IsDerivedFrom,
+    IsSourceOf,
+    IsRequiredBy,
+    Requires,
+    IsObsoletedBy,
+    Obsoletes,
+    IsCollectedBy,
+    Collects,
+    IsTranslationOf,
+    HasTranslation,
+    Other(String),
+}
+
+/// Every DataCite relation type, in the order of the DataCite schema.
+pub const DATACITE_RELATION_TYPES: [RelationType; 38] = [
+    RelationType::IsCitedBy,
+    RelationType::Cites,
+    RelationType::IsSupplementTo,
+    RelationType::IsSupplementedBy,
+    RelationType::IsContinuedBy,
+    RelationType::Continues,
+    RelationType::IsDescribedBy,
+    RelationType::Describes,
+    RelationType::HasMetadata,
+    RelationType::IsMetadataFor,
+    RelationType::HasVersion,
+    RelationType::IsVersionOf,
+    RelationType::IsNewVersionOf,
+    RelationType::IsPreviousVersionOf,
+    RelationType::IsPartOf,
+    RelationType::HasPart,
+    RelationType::IsPublishedIn,
+    RelationType::IsReferencedBy,
+    RelationType::References,
+    RelationType::IsDocumentedBy,
+    RelationType::Documents,
+    RelationType::IsCompiledBy,
+    RelationType::Compiles,
+    RelationType::IsVariantFormOf,
+    RelationType::IsOriginalFormOf,
+    RelationType::IsIdenticalTo,
+    RelationType::IsReviewedBy,
+    RelationType::Reviews,
+    RelationType::IsDerivedFrom,
+    RelationType::IsSourceOf,
+    RelationType::IsRequiredBy,
+    RelationType::Requires,
+    RelationType::IsObsoletedBy,
+    RelationType::Obsoletes,
+    RelationType::IsCollectedBy,
+    RelationType::Collects,
+    RelationType::IsTranslationOf,
+    RelationType::HasTranslation,
+];
+
+/// How a section relates to the section it references.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ReferenceType {
+    Contradicts,
+    Supports,
+    DependsOn,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SectionCrossReference {
     section_title: String,
     reference_note: String,
+    reference_type: Option<ReferenceType>,
+    // The DMP holding the referenced section, if it is not the same DMP
+    target_dmp: Option<Identifier>,
+}
+
+/// Where an annotation points within a section's text.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum AnnotationAnchor {
+    /// Character range, end exclusive
+    Range { start: u32, end: u32 },
+    /// The first occurrence of the quoted passage
+    Quote { text: String },
+}
+
+/// A reviewer comment on a specific passage of a section's text.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Annotation {
+    anchor: AnnotationAnchor,
+    author: String,
+    tags: Vec<String>,
+    comment: String,
+    created_date: DateTime,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Subsection {
     title: String,
-    rating: Option<u8>,
+    // The text of the subsection as written in the plan
+    text: Option<String>,
+    rating: Option<Rating>,
     tags: Vec<String>,
     comments: Option<String>,
 }
@@ -97,11 +266,15 @@ pub struct Subsection {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Section {
     title: String,
-    rating: Option<u8>,
+    // The text of the section as written in the plan
+    text: Option<String>,
+    rating: Option<Rating>,
     tags: Vec<String>,
     comments: Option<String>,
     subsections: Vec<Subsection>,
     cross_references: Option<Vec<SectionCrossReference>>,
+    #[serde(default)]
+    annotations: Vec<Annotation>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -155,6 +328,237 @@ pub struct DataManagementPlan {
     related_datasets: Option<Vec<RelatedDataset>>,
     related_publications: Option<Vec<RelatedPublication>>,
     related_software: Option<Vec<RelatedSoftware>>,
+    // Version of the stored document layout; see `migrations`
+    #[serde(default)]
+    schema_version: i32,
+}
+
+/// A search stored under a name so it can be re-run later.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedSearch {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    id: Option<mongodb::bson::oid::ObjectId>,
+    name: String,
+    // The search expression the query was parsed from, if any
+    expression: Option<String>,
+    query: DmpQuery,
+    created_date: DateTime,
+    last_run: Option<DateTime>,
+}
+
+/// A tag of the managed tag vocabulary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VocabularyTag {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    id: Option<mongodb::bson::oid::ObjectId>,
+    name: String,
+    // Other spellings, stored as `name` when entered
+    #[serde(default)]
+    synonyms: Vec<String>,
+    // The broader tag; searching for it also finds this tag
+    parent: Option<String>,
+    // Rubric sections the tag may be used in, or empty for every section
+    #[serde(default)]
+    sections: Vec<String>,
+}
+
+/// A rating form saved from the browser to be finished later.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RatingDraft {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    id: Option<mongodb::bson::oid::ObjectId>,
+    // The form fields as entered, by field name
+    fields: std::collections::BTreeMap<String, String>,
+    saved_at: DateTime,
+}
+
+impl RelationType {
+    /// The DataCite name, or the stored value for `Other`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            RelationType::IsCitedBy => "IsCitedBy",
+            RelationType::Cites => "Cites",
+            RelationType::IsSupplementTo => "IsSupplementTo",
+            RelationType::IsSupplementedBy => "IsSupplementedBy",
+            RelationType::IsContinuedBy => "IsContinuedBy",
+            RelationType::Continues => "Continues",
+            RelationType::IsDescribedBy => "IsDescribedBy",
+            RelationType::Describes => "Describes",
+            RelationType::HasMetadata => "HasMetadata",
+            RelationType::IsMetadataFor => "IsMetadataFor",
+            RelationType::HasVersion => "HasVersion",
+            RelationType::IsVersionOf => "IsVersionOf",
+            RelationType::IsNewVersionOf => "IsNewVersionOf",
+            RelationType::IsPreviousVersionOf => "IsPreviousVersionOf",
+            RelationType::IsPartOf => "IsPartOf",
+            RelationType::HasPart => "HasPart",
+            RelationType::IsPublishedIn => "IsPublishedIn",
+            RelationType::IsReferencedBy => "IsReferencedBy",
+            RelationType::References => "References",
+            RelationType::IsDocumentedBy => "IsDocumentedBy",
+            RelationType::Documents => "Documents",
+            RelationType::IsCompiledBy => "IsCompiledBy",
+            RelationType::Compiles => "Compiles",
+            RelationType::IsVariantFormOf => "IsVariantFormOf",
+            RelationType::IsOriginalFormOf => "IsOriginalFormOf",
+            RelationType::IsIdenticalTo => "IsIdenticalTo",
+            RelationType::IsReviewedBy => "IsReviewedBy",
+            RelationType::Reviews => "Reviews",
+            RelationType::IsDerivedFrom => "IsDerivedFrom",
+            RelationType::IsSourceOf => "IsSourceOf",
+            RelationType::IsRequiredBy => "IsRequiredBy",
+            RelationType::Requires => "Requires",
+            RelationType::IsObsoletedBy => "IsObsoletedBy",
+            RelationType::Obsoletes => "Obsoletes",
+            RelationType::IsCollectedBy => "IsCollectedBy",
+            RelationType::Collects => "Collects",
+            RelationType::IsTranslationOf => "IsTranslationOf",
+            RelationType::HasTranslation => "HasTranslation",
+            RelationType::Other(value) => value,
+        }
+    }
+}
+
+impl std::str::FromStr for RelationType {
+    type Err = String;
+
+    /// Parses a DataCite relation type, ignoring case, spaces, hyphens and
+    /// underscores.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let folded = s.to_lowercase().replace(['-', '_', ' '], "");
+        DATACITE_RELATION_TYPES
+            .iter()
+            .find(|t| t.as_str().to_lowercase() == folded)
+            .cloned()
+            .ok_or_else(|| format!("'{}' is not a DataCite relation type", s))
+    }
+}
+
+impl From<String> for RelationType {
+    fn from(s: String) -> Self {
+        s.parse()
+            .unwrap_or_else(|_| RelationType::Other(s.trim().to_string()))
+    }
+}
+
+impl From<RelationType> for String {
+    fn from(relation_type: RelationType) -> Self {
+        match relation_type {
+            RelationType::Other(value) => value,
+            _ => relation_type.as_str().to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for RelationType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for ReferenceType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(['-', '_', ' '], "").as_str() {
+            "contradicts" => Ok(ReferenceType::Contradicts),
+            "supports" => Ok(ReferenceType::Supports),
+            "dependson" => Ok(ReferenceType::DependsOn),
+            _ => Err(format!("'{}' is not a valid reference type", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for ReferenceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReferenceType::Contradicts => write!(f, "contradicts"),
+            ReferenceType::Supports => write!(f, "supports"),
+            ReferenceType::DependsOn => write!(f, "depends on"),
+        }
+    }
+}
+
+impl Annotation {
+    /// Locates the annotated passage in `text` as a character range, or
+    /// returns `None` if the range is out of bounds or the quote is missing.
+    pub fn resolve(&self, text: &str) -> Option<(usize, usize)> {
+        match self.anchor {
+            AnnotationAnchor::Range { start, end } => {
+                let (start, end) = (start as usize, end as usize);
+                if start < end && end <= text.chars().count() {
+                    Some((start, end))
+                } else {
+                    None
+                }
+            }
+            AnnotationAnchor::Quote { text: ref quote } => {
+                if quote.is_empty() {
+                    return None;
+                }
+                let byte_start = text.find(quote.as_str())?;
+                let start = text[..byte_start].chars().count();
+                Some((start, start + quote.chars().count()))
+            }
+        }
+    }
+
+    /// Returns the annotated passage of `text`, if it can be located.
+    pub fn passage(&self, text: &str) -> Option<String> {
+        let (start, end) = self.resolve(text)?;
+        Some(text.chars().skip(start).take(end - start).collect())
+    }
+}
+
+impl IdentifierType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            IdentifierType::Doi => "doi",
+            IdentifierType::Handle => "handle",
+            IdentifierType::Ark => "ark",
+            IdentifierType::Url => "url",
+            IdentifierType::Urn => "urn",
+            IdentifierType::Other(value) => value,
+        }
+    }
+}
+
+impl std::str::FromStr for IdentifierType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "doi" => Ok(IdentifierType::Doi),
+            "handle" | "hdl" => Ok(IdentifierType::Handle),
+            "ark" => Ok(IdentifierType::Ark),
+            "url" => Ok(IdentifierType::Url),
+            "urn" => Ok(IdentifierType::Urn),
+            "other" => Ok(IdentifierType::Other("other".to_string())),
+            _ => Err(format!("'{}' is not a valid identifier type", s)),
+        }
+    }
+}
+
+impl From<String> for IdentifierType {
+    fn from(s: String) -> Self {
+        s.parse()
+            .unwrap_or_else(|_| IdentifierType::Other(s.trim().to_string()))
+    }
+}
+
+impl From<IdentifierType> for String {
+    fn from(id_type: IdentifierType) -> Self {
+        match id_type {
+            IdentifierType::Other(value) => value,
+            _ => id_type.as_str().to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for IdentifierType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 impl std::str::FromStr for AuthorIdType {
@@ -171,26 +575,282 @@ impl std::str::FromStr for AuthorIdType {
     }
 }
 
+/// A scale sections of the rubric are rated on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum RatingScale {
+    #[default]
+    OneToFive,
+    ZeroToThree,
+    PassFail,
+    /// A, B, C, D or F, stored as 4 down to 0
+    Letter,
+}
+
+pub const RATING_SCALES: [RatingScale; 4] = [
+    RatingScale::OneToFive,
+    RatingScale::ZeroToThree,
+    RatingScale::PassFail,
+    RatingScale::Letter,
+];
+
+/// Letter grades from the lowest value (0) up.
+const LETTER_GRADES: [&str; 5] = ["F", "D", "C", "B", "A"];
+
+impl RatingScale {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RatingScale::OneToFive => "1-5",
+            RatingScale::ZeroToThree => "0-3",
+            RatingScale::PassFail => "pass/fail",
+            RatingScale::Letter => "A-F",
+        }
+    }
+
+    /// The lowest and highest stored value.
+    pub fn bounds(&self) -> (u8, u8) {
+        match self {
+            RatingScale::OneToFive => (1, 5),
+            RatingScale::ZeroToThree => (0, 3),
+            RatingScale::PassFail => (0, 1),
+            RatingScale::Letter => (0, 4),
+        }
+    }
+
+    /// The values accepted when entering a rating, for prompts.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            RatingScale::OneToFive => "1-5",
+            RatingScale::ZeroToThree => "0-3",
+            RatingScale::PassFail => "pass or fail",
+            RatingScale::Letter => "A, B, C, D or F",
+        }
+    }
+
+    /// Shows a stored value on this scale.
+    pub fn label(&self, value: u8) -> String {
+        match self {
+            RatingScale::OneToFive => format!("{}/5", value),
+            RatingScale::ZeroToThree => format!("{}/3", value),
+            RatingScale::PassFail if value > 0 => "pass".to_string(),
+            RatingScale::PassFail => "fail".to_string(),
+            RatingScale::Letter => LETTER_GRADES
+                .get(value as usize)
+                .map_or(value.to_string(), |grade| grade.to_string()),
+        }
+    }
+
+    /// Parses an entered rating: a value on this scale, or `n/a` for a
+    /// section that does not apply.
+    pub fn parse_rating(&self, input: &str) -> Result<Rating, String> {
+        let input = input.trim();
+        let invalid = || {
+            format!(
+                "'{}' is not a valid rating on the {} scale; expected {} or n/a",
+                input,
+                self.as_str(),
+                self.hint()
+            )
+        };
+
+        match input.to_lowercase().as_str() {
+            "n/a" | "na" | "not applicable" => return Ok(Rating::not_applicable(*self)),
+            _ => {}
+        }
+
+        let value = match self {
+            RatingScale::OneToFive | RatingScale::ZeroToThree => {
+                input.parse::<u8>().map_err(|_| invalid())?
+            }
+            RatingScale::PassFail => match input.to_lowercase().as_str() {
+                "pass" | "p" => 1,
+                "fail" | "f" => 0,
+                _ => return Err(invalid()),
+            },
+            RatingScale::Letter => LETTER_GRADES
+                .iter()
+                .position(|grade| grade.eq_ignore_ascii_case(input))
+                .ok_or_else(invalid)? as u8,
+        };
+
+        Rating::new(*self, value).map_err(|_| invalid())
+    }
+}
+
+impl std::str::FromStr for RatingScale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace(' ', "").as_str() {
+            "1-5" | "1to5" => Ok(RatingScale::OneToFive),
+            "0-3" | "0to3" => Ok(RatingScale::ZeroToThree),
+            "pass/fail" | "pass-fail" | "passfail" => Ok(RatingScale::PassFail),
+            "a-f" | "letter" | "letters" => Ok(RatingScale::Letter),
+            _ => Err(format!(
+                "'{}' is not a valid rating scale; expected one of: {}",
+                s,
+                RATING_SCALES.map(|scale| scale.as_str()).join(", ")
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for RatingScale {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<RatingScale> for String {
+    fn from(scale: RatingScale) -> Self {
+        scale.as_str().to_string()
+    }
+}
+
+impl std::fmt::Display for RatingScale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A section or subsection rating, checked against its scale. Unrated
+/// sections have no rating at all; `value` is `None` for sections that do
+/// not apply to the plan.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "StoredRating")]
+pub struct Rating {
+    scale: RatingScale,
+    value: Option<u8>,
+}
+
+/// A rating as found in stored or exported documents.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredRating {
+    Scaled {
+        scale: RatingScale,
+        value: Option<u8>,
+    },
+    // Ratings stored before scales were introduced were 1-5
+    Legacy(i64),
+}
+
+impl Rating {
+    pub fn new(scale: RatingScale, value: u8) -> Result<Self, String> {
+        let (min, max) = scale.bounds();
+        if value < min || value > max {
+            return Err(format!(
+                "Rating {} is outside the {} scale",
+                value,
+                scale.as_str()
+            ));
+        }
+        Ok(Self {
+            scale,
+            value: Some(value),
+        })
+    }
+
+    pub fn not_applicable(scale: RatingScale) -> Self {
+        Self { scale, value: None }
+    }
+
+    pub fn scale(&self) -> RatingScale {
+        self.scale
+    }
+
+    /// The stored value, or `None` if not applicable.
+    pub fn value(&self) -> Option<u8> {
+        self.value
+    }
+
+    /// The value as a fraction of its scale, 0.0 for the lowest rating and
+    /// 1.0 for the highest, so ratings on different scales can be compared.
+    pub fn fraction(&self) -> Option<f64> {
+        let (min, max) = self.scale.bounds();
+        self.value
+            .map(|value| (value - min) as f64 / (max - min) as f64)
+    }
+}
+
+impl TryFrom<StoredRating> for Rating {
+    type Error = String;
+
+    fn try_from(stored: StoredRating) -> Result<Self, Self::Error> {
+        match stored {
+            StoredRating::Scaled { scale, value: None } => Ok(Rating::not_applicable(scale)),
+            StoredRating::Scaled {
+                scale,
+                value: Some(value),
+            } => Rating::new(scale, value),
+            StoredRating::Legacy(value) => u8::try_from(value)
+                .map_err(|_| format!("Rating {} is outside the 1-5 scale", value))
+                .and_then(|value| Rating::new(RatingScale::OneToFive, value)),
+        }
+    }
+}
+
+impl std::fmt::Display for Rating {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value {
+            Some(value) => write!(f, "{}", self.scale.label(value)),
+            None => write!(f, "N/A"),
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     println!("DMP Rating System");
     println!("================");
+    // Read the rating scales up front so a mistake in the file is reported
+    // before any DMPs are stored
+    let scales = RubricScales::load(RUBRIC_SCALES_FILE)?;
+
     println!("Connecting to MongoDB...");
 
     // Initialize the database manager
-    match DatabaseManager::new("mongodb://localhost:27017", "dmp_rating").await {
+    match DatabaseManager::new("mongodb://localhost:27017", "dmp_rating", scales).await {
         Ok(db_manager) => {
             println!("Connected to MongoDB successfully!");
 
             // Use an Arc to share the database manager across different handlers
             let db_manager = Arc::new(db_manager);
 
+            // Arguments run a single command instead of the interactive menu
+            let args: Vec<String> = std::env::args().skip(1).collect();
+
+            // Upgrade DMPs stored by earlier versions, unless asked to
+            // migrate explicitly
+            if args.first().map(String::as_str) != Some("migrate") {
+                match db_manager.count_outdated_dmps().await {
+                    Ok(0) => {}
+                    Ok(_) => match db_manager.run_migrations(false).await {
+                        Ok(run) => print!("{}", run.to_text()),
+                        Err(e) => eprintln!("Failed to upgrade stored DMPs: {}", e),
+                    },
+                    Err(e) => eprintln!("Failed to check stored DMPs: {}", e),
+                }
+            }
+
+            // Create the full-text search index once here rather than on
+            // every search
+            if let Err(e) = db_manager.ensure_text_index().await {
+                eprintln!("Failed to create the text search index: {}", e);
+            }
+
+            if !args.is_empty() {
+                return commands::run(&db_manager, &args).await;
+            }
+
             // Main menu
             loop {
                 println!("\nMain Menu:");
                 println!("1. Manage DMP Ratings (Add/Search)");
                 println!("2. Export DMP Ratings to JSON");
-                println!("3. Import DMP Ratings from JSON");
+                println!("3. Import DMP Ratings from JSON (or legacy essays)");
                 println!("4. Exit");
 
                 print!("Choose an option (1-4): ");
@@ -202,7 +862,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 match input.trim() {
                     "1" => {
                         // Create and run the CLI with a clone of the database manager
-                        let cli = DmpCli::new(Arc::clone(&db_manager));
+                        let cli = DmpCli::new(Arc::clone(&db_manager), db_manager.scales().clone());
                         if let Err(e) = cli.run().await {
                             eprintln!("Error in DMP management: {}", e);
                         }
@@ -219,12 +879,34 @@ async fn main() -> Result<(), Box<dyn Error>> {
                         }
                     }
                     "3" => {
+                        println!("Import format:");
+                        println!("1. DMP ratings (JSON export)");
+                        println!("2. Legacy essay archive (JSON)");
+                        println!("3. Machine-actionable DMP (RDA DMP Common Standard JSON)");
+
+                        print!("Choose a format (1-3): ");
+                        io::stdout().flush()?;
+                        let mut format = String::new();
+                        io::stdin().read_line(&mut format)?;
+
                         print!("Enter import file path: ");
                         io::stdout().flush()?;
                         let mut path = String::new();
                         io::stdin().read_line(&mut path)?;
 
-                        match db_manager.import_from_file(path.trim()).await {
+                        let result = match format.trim() {
+                            "2" => {
+                                let importer = ImportExport::new(Arc::clone(&db_manager));
+                                importer.import_legacy_essays(path.trim()).await
+                            }
+                            "3" => {
+                                let importer = ImportExport::new(Arc::clone(&db_manager));
+                                importer.import_madmp(path.trim()).await
+                            }
+                            _ => db_manager.import_from_file(path.trim()).await,
+                        };
+
+                        match result {
                             Ok(count) => println!("Successfully imported {} DMPs.", count),
                             Err(e) => eprintln!("Error importing DMPs: {}", e),
                         }
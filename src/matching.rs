@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::{DataManagementPlan, RUBRIC_SECTIONS};
+
+/// How search input is matched against stored section titles and tags.
+/// Every mode ignores case, diacritics and repeated whitespace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum MatchMode {
+    #[default]
+    Exact,
+    /// The stored value starts with the input
+    Prefix,
+    /// The stored value is within a few typos of the input
+    Fuzzy,
+}
+
+impl std::str::FromStr for MatchMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "exact" => Ok(MatchMode::Exact),
+            "prefix" => Ok(MatchMode::Prefix),
+            "fuzzy" => Ok(MatchMode::Fuzzy),
+            _ => Err(format!("'{}' is not a valid match mode", s)),
+        }
+    }
+}
+
+/// Collapses runs of whitespace into single spaces and trims the ends.
+fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Folds `s` for comparison: lowercase, without diacritics and with
+/// whitespace collapsed.
+pub fn fold(s: &str) -> String {
+    collapse_whitespace(s)
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The form tags are stored in: lowercase with whitespace collapsed.
+pub fn normalize_tag(tag: &str) -> String {
+    collapse_whitespace(tag).to_lowercase()
+}
+
+/// Splits comma-separated tag input into normalized tags, dropping empty and
+/// duplicate entries.
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+
+    for tag in input.split(',').map(normalize_tag) {
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    tags
+}
+
+/// Normalizes every tag of `dmp` in place, e.g. before storing imported DMPs.
+pub fn normalize_dmp_tags(dmp: &mut DataManagementPlan) {
+    for section in &mut dmp.sections {
+        section.tags = parse_tags(&section.tags.join(","));
+        for subsection in &mut section.subsections {
+            subsection.tags = parse_tags(&subsection.tags.join(","));
+        }
+    }
+
+    if let Some(ref mut overall_rating) = dmp.overall_rating {
+        overall_rating.overall_tags = parse_tags(&overall_rating.overall_tags.join(","));
+    }
+}
+
+/// Number of single-character edits needed to turn `a` into `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Edits tolerated by fuzzy matching: one per four characters of input, at
+/// least one.
+fn fuzzy_threshold(input: &str) -> usize {
+    (input.chars().count() / 4).max(1)
+}
+
+/// Returns `true` if the stored `candidate` matches the search `input`.
+pub fn matches(candidate: &str, input: &str, mode: MatchMode) -> bool {
+    let candidate = fold(candidate);
+    let input = fold(input);
+
+    match mode {
+        MatchMode::Exact => candidate == input,
+        MatchMode::Prefix => candidate.starts_with(&input),
+        MatchMode::Fuzzy => {
+            candidate.starts_with(&input)
+                || levenshtein(&candidate, &input) <= fuzzy_threshold(&input)
+        }
+    }
+}
+
+/// Resolves user input to one of the rubric's section titles. The input may
+/// be the section's number in `RUBRIC_SECTIONS`, its title in any case, an
+/// unambiguous prefix of it, or a close misspelling.
+pub fn resolve_section_title(input: &str) -> Option<&'static str> {
+    if let Ok(number) = input.trim().parse::<usize>() {
+        return RUBRIC_SECTIONS.get(number.checked_sub(1)?).copied();
+    }
+
+    let folded = fold(input);
+    if folded.is_empty() {
+        return None;
+    }
+
+    if let Some(title) = RUBRIC_SECTIONS.iter().find(|t| fold(t) == folded) {
+        return Some(title);
+    }
+
+    let prefixed: Vec<&&str> = RUBRIC_SECTIONS
+        .iter()
+        .filter(|t| matches(t, input, MatchMode::Prefix))
+        .collect();
+    if prefixed.len() == 1 {
+        return Some(prefixed[0]);
+    }
+
+    RUBRIC_SECTIONS
+        .iter()
+        .map(|t| (levenshtein(&fold(t), &folded), t))
+        .filter(|(distance, _)| *distance <= fuzzy_threshold(&folded))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, t)| *t)
+}
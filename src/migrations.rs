@@ -0,0 +1,442 @@
+use std::collections::BTreeMap;
+
+use mongodb::bson::{doc, Bson, Document};
+
+use crate::query::related_entity_array;
+use crate::related::ENTITY_TYPES;
+use crate::{IdentifierType, Rating, RatingScale, RelationType};
+
+/// Upgrades a raw DMP document by one schema version, recording what it
+/// changed in the report. Returns `true` if the document changed.
+type Upgrade = fn(&mut Document, &mut MigrationReport) -> bool;
+
+/// A step from the previous schema version to `version`.
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    upgrade: Upgrade,
+}
+
+/// Every migration, in the order they are applied. Each must leave documents
+/// it has already upgraded unchanged, so that an interrupted run can be
+/// repeated.
+pub const MIGRATIONS: [Migration; 3] = [
+    Migration {
+        version: 1,
+        name: "canonical-identifier-types",
+        upgrade: canonical_identifier_types,
+    },
+    Migration {
+        version: 2,
+        name: "canonical-relation-types",
+        upgrade: canonical_relation_types,
+    },
+    Migration {
+        version: 3,
+        name: "scaled-ratings",
+        upgrade: scaled_ratings,
+    },
+];
+
+/// The schema version of documents written by this version of the
+/// application.
+pub const SCHEMA_VERSION: i32 = MIGRATIONS.len() as i32;
+
+/// What a migration changed, or would change in a dry run.
+#[derive(Debug)]
+pub struct MigrationReport {
+    pub version: i32,
+    pub name: &'static str,
+    /// Number of DMPs the migration changed
+    pub documents: usize,
+    /// Number of values rewritten, by stored and new value
+    pub rewritten: BTreeMap<(String, String), usize>,
+    /// Values the migration did not recognize and kept as stored, with the
+    /// DMPs holding them
+    pub unrecognized: BTreeMap<String, Vec<String>>,
+}
+
+impl MigrationReport {
+    pub fn new(migration: &Migration) -> Self {
+        Self {
+            version: migration.version,
+            name: migration.name,
+            documents: 0,
+            rewritten: BTreeMap::new(),
+            unrecognized: BTreeMap::new(),
+        }
+    }
+
+    fn rewrite(&mut self, from: &str, to: &str) {
+        *self
+            .rewritten
+            .entry((from.to_string(), to.to_string()))
+            .or_insert(0) += 1;
+    }
+
+    fn merge(&mut self, other: MigrationReport) {
+        self.documents += other.documents;
+        for (values, count) in other.rewritten {
+            *self.rewritten.entry(values).or_insert(0) += count;
+        }
+        for (value, dmps) in other.unrecognized {
+            for dmp in dmps {
+                self.keep(&value, &dmp);
+            }
+        }
+    }
+
+    fn keep(&mut self, value: &str, dmp: &str) {
+        let dmps = self.unrecognized.entry(value.to_string()).or_default();
+        if !dmps.iter().any(|d| d == dmp) {
+            dmps.push(dmp.to_string());
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{}. {}: {} DMPs changed\n",
+            self.version, self.name, self.documents
+        );
+        for ((from, to), count) in &self.rewritten {
+            text.push_str(&format!("   '{}' -> '{}': {}\n", from, to, count));
+        }
+        for (value, dmps) in &self.unrecognized {
+            text.push_str(&format!(
+                "   '{}' is not recognized and was kept, in: {}\n",
+                value,
+                dmps.join(", ")
+            ));
+        }
+        text
+    }
+}
+
+/// The outcome of bringing stored DMPs up to `SCHEMA_VERSION`.
+#[derive(Debug)]
+pub struct MigrationRun {
+    pub dry_run: bool,
+    /// Number of DMPs below `SCHEMA_VERSION`
+    pub documents: usize,
+    pub reports: Vec<MigrationReport>,
+    /// DMPs that changed while the run read them and were left as they are
+    pub skipped: Vec<String>,
+}
+
+impl MigrationRun {
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            documents: 0,
+            reports: MIGRATIONS.iter().map(MigrationReport::new).collect(),
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Adds what `other` changed to this run.
+    pub fn merge(&mut self, other: MigrationRun) {
+        self.documents += other.documents;
+        for (report, other) in self.reports.iter_mut().zip(other.reports) {
+            report.merge(other);
+        }
+        self.skipped.extend(other.skipped);
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{} DMPs {} to schema version {}\n",
+            self.documents,
+            if self.dry_run {
+                "would be upgraded"
+            } else {
+                "upgraded"
+            },
+            SCHEMA_VERSION
+        );
+        for report in &self.reports {
+            text.push_str(&report.to_text());
+        }
+        if !self.skipped.is_empty() {
+            text.push_str(&format!(
+                "Changed during the upgrade and left as they are; migrate again to include them: {}\n",
+                self.skipped.join(", ")
+            ));
+        }
+        text
+    }
+}
+
+/// The schema version of a raw DMP document; documents stored before
+/// versioning count as version 0.
+pub fn schema_version(dmp: &Document) -> i32 {
+    match dmp.get("schema_version") {
+        Some(Bson::Int32(version)) => *version,
+        Some(Bson::Int64(version)) => *version as i32,
+        _ => 0,
+    }
+}
+
+/// Applies every migration newer than the document's schema version to the
+/// raw DMP document `dmp` and stamps it with `SCHEMA_VERSION`.
+pub fn upgrade_document(dmp: &mut Document, run: &mut MigrationRun) {
+    let stored = schema_version(dmp);
+
+    for (migration, report) in MIGRATIONS.iter().zip(run.reports.iter_mut()) {
+        if migration.version > stored && (migration.upgrade)(dmp, report) {
+            report.documents += 1;
+        }
+    }
+
+    dmp.insert("schema_version", SCHEMA_VERSION);
+    run.documents += 1;
+}
+
+fn dmp_label(dmp: &Document) -> String {
+    dmp.get_str("title").unwrap_or("(untitled)").to_string()
+}
+
+/// Calls `f` on every identifier stored in `dmp`: its own, those of its
+/// related entities and those of DMPs its sections refer to.
+fn for_each_identifier(dmp: &mut Document, mut f: impl FnMut(&mut Document)) {
+    if let Ok(id) = dmp.get_document_mut("dmp_id") {
+        f(id);
+    }
+
+    for entity_type in ENTITY_TYPES {
+        let (array, id_field, _) = match related_entity_array(entity_type) {
+            Some(paths) => paths,
+            None => continue,
+        };
+        for entry in dmp.get_array_mut(array).into_iter().flatten() {
+            if let Some(id) = entry
+                .as_document_mut()
+                .and_then(|e| e.get_document_mut(id_field).ok())
+            {
+                f(id);
+            }
+        }
+    }
+
+    for section in dmp.get_array_mut("sections").into_iter().flatten() {
+        let references = section
+            .as_document_mut()
+            .and_then(|s| s.get_array_mut("cross_references").ok());
+        for reference in references.into_iter().flatten() {
+            if let Some(id) = reference
+                .as_document_mut()
+                .and_then(|r| r.get_document_mut("target_dmp").ok())
+            {
+                f(id);
+            }
+        }
+    }
+}
+
+/// 1: identifier types were free text and may be stored as e.g. `DOI` or
+/// `hdl`; rewrite them as `IdentifierType` spells them.
+fn canonical_identifier_types(dmp: &mut Document, report: &mut MigrationReport) -> bool {
+    let label = dmp_label(dmp);
+    let mut changed = false;
+
+    for_each_identifier(dmp, |id| {
+        let value = match id.get_str("id_type") {
+            Ok(value) => value.to_string(),
+            Err(_) => return,
+        };
+        match value.parse::<IdentifierType>() {
+            Ok(id_type) if id_type.as_str() != value => {
+                report.rewrite(&value, id_type.as_str());
+                id.insert("id_type", id_type.as_str());
+                changed = true;
+            }
+            Ok(_) => {}
+            Err(_) => report.keep(&value, &label),
+        }
+    });
+
+    changed
+}
+
+/// 2: relationship types were free text; rewrite those naming a DataCite
+/// relation type in its spelling, e.g. `is supplement to` as `IsSupplementTo`.
+fn canonical_relation_types(dmp: &mut Document, report: &mut MigrationReport) -> bool {
+    let label = dmp_label(dmp);
+    let mut changed = false;
+
+    for entity_type in ENTITY_TYPES {
+        let (array, _, relation_field) = match related_entity_array(entity_type) {
+            Some(paths) => paths,
+            None => continue,
+        };
+        for entry in dmp.get_array_mut(array).into_iter().flatten() {
+            let entry = match entry.as_document_mut() {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let value = match entry.get_str(relation_field) {
+                Ok(value) => value.to_string(),
+                Err(_) => continue,
+            };
+            match value.parse::<RelationType>() {
+                Ok(relation) if relation.as_str() != value => {
+                    report.rewrite(&value, relation.as_str());
+                    entry.insert(relation_field, relation.as_str());
+                    changed = true;
+                }
+                Ok(_) => {}
+                Err(_) => report.keep(&value, &label),
+            }
+        }
+    }
+
+    changed
+}
+
+/// 3: section and subsection ratings were bare numbers from 1 to 5; store
+/// them with their scale. Values that were never valid ratings leave the
+/// section unrated and are kept for review at the end of its comments.
+fn scaled_ratings(dmp: &mut Document, report: &mut MigrationReport) -> bool {
+    let label = dmp_label(dmp);
+    let mut changed = false;
+    let mut upgrade = |holder: &mut Document| {
+        let stored = match holder.get("rating") {
+            None | Some(Bson::Null) | Some(Bson::Document(_)) => return,
+            Some(stored) => stored.clone(),
+        };
+        let value = match stored {
+            Bson::Int32(value) => Some(value as i64),
+            Bson::Int64(value) => Some(value),
+            Bson::Double(value) if value.fract() == 0.0 => Some(value as i64),
+            _ => None,
+        };
+        let rating = value
+            .and_then(|value| u8::try_from(value).ok())
+            .and_then(|value| Rating::new(RatingScale::OneToFive, value).ok());
+
+        let from = match stored {
+            Bson::String(ref value) => value.clone(),
+            ref other => other.to_string(),
+        };
+        match rating {
+            Some(rating) => {
+                report.rewrite(&from, &rating.to_string());
+                holder.insert(
+                    "rating",
+                    doc! {
+                        "scale": rating.scale().as_str(),
+                        "value": rating.value().map(i32::from)
+                    },
+                );
+            }
+            None => {
+                report.keep(&from, &label);
+                let note = format!("Legacy rating '{}' kept for review", from);
+                let comments = match holder.get_str("comments") {
+                    Ok(comments) if !comments.trim().is_empty() => {
+                        format!("{}\n{}", comments, note)
+                    }
+                    _ => note,
+                };
+                holder.insert("comments", comments);
+                holder.insert("rating", Bson::Null);
+            }
+        }
+        changed = true;
+    };
+
+    for section in dmp.get_array_mut("sections").into_iter().flatten() {
+        let section = match section.as_document_mut() {
+            Some(section) => section,
+            None => continue,
+        };
+        upgrade(section);
+        for subsection in section.get_array_mut("subsections").into_iter().flatten() {
+            if let Some(subsection) = subsection.as_document_mut() {
+                upgrade(subsection);
+            }
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legacy_dmp() -> Document {
+        doc! {
+            "title": "Legacy plan",
+            "dmp_id": { "identifier": "10.1234/plan", "id_type": "DOI" },
+            "related_datasets": [{
+                "dataset_id": { "identifier": "10.1234/data", "id_type": "doi" },
+                "relationship_type": "is supplement to"
+            }],
+            "sections": [{
+                "title": "Data Description",
+                "rating": 4,
+                "comments": "Clear",
+                "subsections": [{ "title": "Formats", "rating": 9 }]
+            }]
+        }
+    }
+
+    fn changes(run: &MigrationRun) -> usize {
+        run.reports
+            .iter()
+            .map(|r| r.documents + r.rewritten.len())
+            .sum()
+    }
+
+    #[test]
+    fn upgrade_document_upgrades_legacy_values() {
+        let mut dmp = legacy_dmp();
+        let mut run = MigrationRun::new(false);
+        upgrade_document(&mut dmp, &mut run);
+
+        assert_eq!(schema_version(&dmp), SCHEMA_VERSION);
+        assert_eq!(
+            dmp.get_document("dmp_id").unwrap().get_str("id_type"),
+            Ok("doi")
+        );
+        let section = dmp.get_array("sections").unwrap()[0]
+            .as_document()
+            .unwrap()
+            .clone();
+        assert_eq!(
+            section.get_document("rating").unwrap(),
+            &doc! { "scale": "1-5", "value": 4 }
+        );
+        let subsection = section.get_array("subsections").unwrap()[0]
+            .as_document()
+            .unwrap()
+            .clone();
+        assert_eq!(subsection.get("rating"), Some(&Bson::Null));
+        assert_eq!(
+            subsection.get_str("comments"),
+            Ok("Legacy rating '9' kept for review")
+        );
+        assert_eq!(run.reports.iter().map(|r| r.documents).sum::<usize>(), 3);
+        assert!(run.reports[2].unrecognized.contains_key("9"));
+    }
+
+    #[test]
+    fn upgrading_twice_changes_nothing() {
+        let mut dmp = legacy_dmp();
+        upgrade_document(&mut dmp, &mut MigrationRun::new(false));
+        let upgraded = dmp.clone();
+
+        let mut run = MigrationRun::new(false);
+        upgrade_document(&mut dmp, &mut run);
+        assert_eq!(dmp, upgraded);
+        assert_eq!(changes(&run), 0);
+
+        // As if an interrupted run had upgraded the values but not stamped
+        // the version
+        dmp.remove("schema_version");
+        let mut run = MigrationRun::new(false);
+        upgrade_document(&mut dmp, &mut run);
+        assert_eq!(dmp, upgraded);
+        assert_eq!(changes(&run), 0);
+    }
+}
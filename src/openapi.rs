@@ -0,0 +1,337 @@
+use serde_json::{json, Value};
+
+use crate::related::ENTITY_TYPES;
+use crate::RATING_SCALES;
+
+/// A response holding a list of DMPs.
+fn dmp_list(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": {
+            "type": "array",
+            "items": { "$ref": "#/components/schemas/DataManagementPlan" }
+        } } }
+    })
+}
+
+fn error(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } }
+    })
+}
+
+fn query_parameter(name: &str, description: &str, required: bool) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": required,
+        "description": description,
+        "schema": { "type": "string" }
+    })
+}
+
+/// The OpenAPI 3.0 description of the API `serve` provides.
+pub fn openapi_document() -> Value {
+    let dmp_parameters = json!([
+        {
+            "name": "id_type",
+            "in": "path",
+            "required": true,
+            "description": "Identifier type of the DMP, e.g. doi",
+            "schema": { "type": "string" }
+        },
+        {
+            "name": "id",
+            "in": "path",
+            "required": true,
+            "description": "Identifier of the DMP; it may contain slashes, as DOIs do",
+            "schema": { "type": "string" }
+        }
+    ]);
+    let dmp_body = json!({
+        "required": true,
+        "content": { "application/json": { "schema": {
+            "$ref": "#/components/schemas/DataManagementPlan"
+        } } }
+    });
+    let path_description = "Section title, or SECTION/SUBSECTION for a subsection";
+    let scales: Vec<&str> = RATING_SCALES.iter().map(|s| s.as_str()).collect();
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "DMP Rating System API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Stored DMPs and their ratings. DMPs are read and written in the layout of the JSON export; dates are extended JSON, e.g. {\"$date\": {\"$numberLong\": \"1704067200000\"}}."
+        },
+        "paths": {
+            "/dmps": {
+                "get": {
+                    "summary": "List DMPs, optionally those matching a search expression",
+                    "parameters": [query_parameter(
+                        "q",
+                        "Search expression, e.g. funder:NSF created>=2024-01-01",
+                        false
+                    )],
+                    "responses": {
+                        "200": dmp_list("The matching DMPs"),
+                        "400": error("The search expression is invalid")
+                    }
+                },
+                "post": {
+                    "summary": "Add a DMP",
+                    "requestBody": dmp_body,
+                    "responses": {
+                        "201": {
+                            "description": "The DMP was added",
+                            "content": { "application/json": { "schema": {
+                                "type": "object",
+                                "properties": { "id": { "type": "string", "description": "Database ID of the new DMP" } }
+                            } } }
+                        },
+                        "400": error("An identifier of the DMP is invalid")
+                    }
+                }
+            },
+            "/dmps/{id_type}/{id}": {
+                "parameters": dmp_parameters,
+                "get": {
+                    "summary": "Get a DMP",
+                    "responses": {
+                        "200": {
+                            "description": "The DMP",
+                            "content": { "application/json": { "schema": {
+                                "$ref": "#/components/schemas/DataManagementPlan"
+                            } } }
+                        },
+                        "404": error("There is no such DMP")
+                    }
+                },
+                "put": {
+                    "summary": "Replace a DMP",
+                    "description": "The last_modified of the body must be that of the stored DMP, i.e. the DMP must not have changed since it was read, and its dmp_id must be the identifier in the path.",
+                    "requestBody": dmp_body,
+                    "responses": {
+                        "204": { "description": "The DMP was replaced" },
+                        "400": error("An identifier of the DMP is invalid, or its dmp_id is not the one in the path"),
+                        "404": error("There is no such DMP"),
+                        "409": error("The DMP changed since it was read; current holds it as stored now")
+                    }
+                },
+                "patch": {
+                    "summary": "Change several ratings of a DMP at once",
+                    "description": "Every change is written in a single update recording one history entry, or none is.",
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": {
+                            "$ref": "#/components/schemas/RatingUpdate"
+                        } } }
+                    },
+                    "responses": {
+                        "200": {
+                            "description": "The changes were written",
+                            "content": { "application/json": { "schema": {
+                                "type": "object",
+                                "properties": { "modified": { "type": "boolean" } }
+                            } } }
+                        },
+                        "400": error("A change is invalid, e.g. names a missing section or a rating off its scale"),
+                        "404": error("There is no such DMP"),
+                        "409": error("The DMP changed since it was read; current holds it as stored now")
+                    }
+                },
+                "delete": {
+                    "summary": "Delete a DMP",
+                    "responses": {
+                        "204": { "description": "The DMP was deleted" },
+                        "404": error("There is no such DMP")
+                    }
+                }
+            },
+            "/search/section-tag": {
+                "get": {
+                    "summary": "Find DMPs by a tag of a section or subsection",
+                    "parameters": [
+                        query_parameter("path", path_description, true),
+                        query_parameter("tag", "Tag, also matching the vocabulary tags below it", true),
+                        {
+                            "name": "mode",
+                            "in": "query",
+                            "required": false,
+                            "schema": { "type": "string", "enum": ["exact", "prefix", "fuzzy"], "default": "exact" }
+                        }
+                    ],
+                    "responses": {
+                        "200": dmp_list("The matching DMPs"),
+                        "400": error("A parameter is invalid")
+                    }
+                }
+            },
+            "/search/rating": {
+                "get": {
+                    "summary": "Find DMPs rated at least a minimum in a section or subsection",
+                    "parameters": [
+                        query_parameter("path", path_description, true),
+                        query_parameter("min", "Minimum rating, on the scale of the section", true)
+                    ],
+                    "responses": {
+                        "200": dmp_list("The matching DMPs"),
+                        "400": error("A parameter is invalid")
+                    }
+                }
+            },
+            "/search/related": {
+                "get": {
+                    "summary": "Find DMPs listing a related entity",
+                    "parameters": [
+                        {
+                            "name": "entity",
+                            "in": "query",
+                            "required": true,
+                            "schema": { "type": "string", "enum": ENTITY_TYPES }
+                        },
+                        query_parameter("id", "Identifier of the entity", true),
+                        query_parameter("relation", "DataCite relation type, e.g. IsSupplementTo", false)
+                    ],
+                    "responses": {
+                        "200": dmp_list("The matching DMPs"),
+                        "400": error("A parameter is invalid")
+                    }
+                }
+            },
+            "/export": {
+                "get": {
+                    "summary": "Export every DMP",
+                    "responses": { "200": dmp_list("Every stored DMP, as in the JSON export") }
+                }
+            },
+            "/import": {
+                "post": {
+                    "summary": "Import DMPs from a JSON export",
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": {
+                            "type": "array",
+                            "items": { "$ref": "#/components/schemas/DataManagementPlan" }
+                        } } }
+                    },
+                    "responses": {
+                        "200": {
+                            "description": "The DMPs were added",
+                            "content": { "application/json": { "schema": {
+                                "type": "object",
+                                "properties": { "imported": { "type": "integer" } }
+                            } } }
+                        },
+                        "400": error("An identifier of a DMP is invalid")
+                    }
+                }
+            },
+            "/openapi.json": {
+                "get": {
+                    "summary": "This document",
+                    "responses": { "200": { "description": "The OpenAPI document" } }
+                }
+            }
+        },
+        "components": {
+            "schemas": {
+                "Error": {
+                    "type": "object",
+                    "properties": {
+                        "error": { "type": "string" },
+                        "current": { "$ref": "#/components/schemas/DataManagementPlan" }
+                    },
+                    "required": ["error"]
+                },
+                "Rating": {
+                    "type": "object",
+                    "properties": {
+                        "scale": { "type": "string", "enum": scales },
+                        "value": { "type": "integer", "nullable": true, "description": "null if the section does not apply" }
+                    }
+                },
+                "Section": {
+                    "type": "object",
+                    "properties": {
+                        "title": { "type": "string" },
+                        "text": { "type": "string", "nullable": true },
+                        "rating": { "allOf": [{ "$ref": "#/components/schemas/Rating" }], "nullable": true },
+                        "comments": { "type": "string", "nullable": true },
+                        "tags": { "type": "array", "items": { "type": "string" } },
+                        "subsections": { "type": "array", "items": { "type": "object" } }
+                    },
+                    "additionalProperties": true
+                },
+                "DataManagementPlan": {
+                    "type": "object",
+                    "properties": {
+                        "title": { "type": "string" },
+                        "dmp_id": {
+                            "type": "object",
+                            "properties": {
+                                "identifier": { "type": "string" },
+                                "id_type": { "type": "string" }
+                            }
+                        },
+                        "authors": { "type": "array", "items": { "type": "object" } },
+                        "project_info": { "type": "object", "nullable": true },
+                        "created_date": { "$ref": "#/components/schemas/Date" },
+                        "last_modified": { "$ref": "#/components/schemas/Date" },
+                        "version": { "type": "string" },
+                        "overall_rating": {
+                            "type": "object",
+                            "nullable": true,
+                            "properties": {
+                                "score": { "type": "number", "minimum": 1, "maximum": 5 },
+                                "reviewer": { "type": "string" },
+                                "review_date": { "$ref": "#/components/schemas/Date" },
+                                "comments": { "type": "string" },
+                                "overall_tags": { "type": "array", "items": { "type": "string" } }
+                            }
+                        },
+                        "sections": { "type": "array", "items": { "$ref": "#/components/schemas/Section" } }
+                    },
+                    "required": ["title", "dmp_id", "authors", "created_date", "last_modified", "version", "sections"],
+                    "additionalProperties": true
+                },
+                "Date": {
+                    "type": "object",
+                    "description": "Extended JSON date",
+                    "properties": { "$date": { "type": "object" } }
+                },
+                "RatingUpdate": {
+                    "type": "object",
+                    "properties": {
+                        "reviewer": { "type": "string" },
+                        "read_at": {
+                            "allOf": [{ "$ref": "#/components/schemas/Date" }],
+                            "description": "last_modified of the DMP as read"
+                        },
+                        "sections": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "path": { "type": "string", "description": path_description },
+                                    "rating": { "type": "string", "description": "Rating on the scale of the section, e.g. 4, pass or B, or n/a" },
+                                    "comments": { "type": "string" }
+                                },
+                                "required": ["path"]
+                            }
+                        },
+                        "overall": {
+                            "type": "object",
+                            "properties": {
+                                "score": { "type": "number", "minimum": 1, "maximum": 5 },
+                                "comments": { "type": "string" }
+                            }
+                        }
+                    },
+                    "required": ["reviewer", "read_at"]
+                }
+            }
+        }
+    })
+}
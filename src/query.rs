@@ -0,0 +1,471 @@
+use mongodb::bson::{doc, Bson, DateTime, Document};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{Error as IoError, ErrorKind};
+
+use crate::identifiers::identifier_variants;
+use crate::matching::{fold, normalize_tag};
+use crate::vocabulary::TagVocabulary;
+use crate::{DataManagementPlan, Rating, RelationType};
+
+/// How the tags of a query are matched against a section's tags.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum TagMatch {
+    /// The section carries at least one of the tags
+    #[default]
+    Any,
+    /// The section carries every one of the tags
+    All,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelatedEntityFilter {
+    entity_type: String,
+    identifier: String,
+    // Saved searches from before relation types were searchable lack this
+    #[serde(default)]
+    relation_type: Option<RelationType>,
+}
+
+/// A search combining several criteria, translated into a single MongoDB filter.
+///
+/// Section criteria (title, required and excluded tags, rating range) are matched against the
+/// same section, except that without a title, excluded tags are excluded from every section.
+/// All other criteria apply to the DMP as a whole.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DmpQuery {
+    section_title: Option<String>,
+    tags: Vec<String>,
+    tag_match: TagMatch,
+    excluded_tags: Vec<String>,
+    // Bounds on the section rating, matching only ratings on their scale.
+    // Saved searches from before scales stored bare 1-5 values, which are
+    // read as 1-5 ratings
+    min_rating: Option<Rating>,
+    max_rating: Option<Rating>,
+    min_overall_score: Option<f32>,
+    max_overall_score: Option<f32>,
+    funder: Option<String>,
+    institution: Option<String>,
+    author: Option<String>,
+    created_from: Option<DateTime>,
+    created_to: Option<DateTime>,
+    reviewed_from: Option<DateTime>,
+    reviewed_to: Option<DateTime>,
+    related_entity: Option<RelatedEntityFilter>,
+}
+
+/// Maps an entity type ("dataset", "publication", "software", "dmp") to the
+/// DMP array listing that kind of related entity and the names of the
+/// identifier and relation type fields of its entries.
+pub fn related_entity_array(
+    entity_type: &str,
+) -> Option<(&'static str, &'static str, &'static str)> {
+    match entity_type {
+        "dataset" => Some(("related_datasets", "dataset_id", "relationship_type")),
+        "publication" => Some((
+            "related_publications",
+            "publication_id",
+            "relationship_type",
+        )),
+        "software" => Some(("related_software", "software_id", "relationship")),
+        "dmp" => Some(("related_dmps", "dmp_id", "relationship_type")),
+        _ => None,
+    }
+}
+
+/// A section, or one of its subsections, written `section/subsection`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionPath {
+    pub section: String,
+    pub subsection: Option<String>,
+}
+
+impl SectionPath {
+    pub fn section(title: &str) -> Self {
+        Self {
+            section: title.trim().to_string(),
+            subsection: None,
+        }
+    }
+
+    pub fn subsection(section: &str, title: &str) -> Self {
+        Self {
+            section: section.trim().to_string(),
+            subsection: Some(title.trim().to_string()),
+        }
+    }
+
+    /// Filter matching DMPs with a section, or subsection, at this path that
+    /// also meets `conditions`.
+    pub fn filter(&self, conditions: Document) -> Document {
+        let mut section = doc! { "title": &self.section };
+        match self.subsection {
+            Some(ref subsection) => {
+                let mut inner = doc! { "title": subsection };
+                inner.extend(conditions);
+                section.insert("subsections", doc! { "$elemMatch": inner });
+            }
+            None => section.extend(conditions),
+        }
+        doc! { "sections": { "$elemMatch": section } }
+    }
+
+    /// The update path of a field of the section or subsection, using the
+    /// `s` and `sub` array filters of `array_filters`.
+    pub fn field(&self, name: &str) -> String {
+        match self.subsection {
+            Some(_) => format!("sections.$[s].subsections.$[sub].{}", name),
+            None => format!("sections.$[s].{}", name),
+        }
+    }
+
+    /// The array filters that `field` paths refer to.
+    pub fn array_filters(&self) -> Vec<Document> {
+        let mut filters = vec![doc! { "s.title": &self.section }];
+        if let Some(ref subsection) = self.subsection {
+            filters.push(doc! { "sub.title": subsection });
+        }
+        filters
+    }
+
+    /// Finds the section and subsection in `dmp`, ignoring case and accents
+    /// in their titles, returning their positions.
+    pub fn position(&self, dmp: &DataManagementPlan) -> Result<(usize, Option<usize>), String> {
+        let section = dmp
+            .sections
+            .iter()
+            .position(|s| fold(&s.title) == fold(&self.section))
+            .ok_or_else(|| format!("Section '{}' not found", self.section))?;
+
+        let subsection = match self.subsection {
+            Some(ref title) => Some(
+                dmp.sections[section]
+                    .subsections
+                    .iter()
+                    .position(|s| fold(&s.title) == fold(title))
+                    .ok_or_else(|| {
+                        format!(
+                            "Subsection '{}' not found in section '{}'",
+                            title, dmp.sections[section].title
+                        )
+                    })?,
+            ),
+            None => None,
+        };
+
+        Ok((section, subsection))
+    }
+
+    /// The rating and comments of the section or subsection in `dmp`.
+    pub fn rating_and_comments(
+        &self,
+        dmp: &DataManagementPlan,
+    ) -> Option<(Option<Rating>, Option<String>)> {
+        let (section, subsection) = self.position(dmp).ok()?;
+        let section = &dmp.sections[section];
+        match subsection {
+            Some(subsection) => {
+                let subsection = &section.subsections[subsection];
+                Some((subsection.rating, subsection.comments.clone()))
+            }
+            None => Some((section.rating, section.comments.clone())),
+        }
+    }
+}
+
+impl std::str::FromStr for SectionPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (section, subsection) = match s.split_once('/') {
+            Some((section, subsection)) => (section.trim(), Some(subsection.trim())),
+            None => (s.trim(), None),
+        };
+        if section.is_empty() || subsection == Some("") {
+            return Err(format!(
+                "'{}' is not a valid section path; expected SECTION or SECTION/SUBSECTION",
+                s.trim()
+            ));
+        }
+
+        Ok(match subsection {
+            Some(subsection) => SectionPath::subsection(section, subsection),
+            None => SectionPath::section(section),
+        })
+    }
+}
+
+impl std::fmt::Display for SectionPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.subsection {
+            Some(ref subsection) => write!(f, "{}/{}", self.section, subsection),
+            None => write!(f, "{}", self.section),
+        }
+    }
+}
+
+/// Milliseconds from the start of a day to its last millisecond.
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000 - 1;
+
+/// Parses a `YYYY-MM-DD` date as midnight UTC.
+pub fn parse_date(input: &str) -> Option<DateTime> {
+    DateTime::parse_rfc3339_str(format!("{}T00:00:00Z", input)).ok()
+}
+
+/// The last millisecond of the day starting at `date`, for ranges that
+/// include the whole day.
+pub fn end_of_day(date: DateTime) -> DateTime {
+    DateTime::from_millis(date.timestamp_millis() + DAY_MILLIS)
+}
+
+/// Builds a `{ "$gte": min, "$lte": max }` range document, or `None` if both
+/// bounds are unset.
+fn range<T: Into<Bson>>(min: Option<T>, max: Option<T>) -> Option<Document> {
+    let mut range = Document::new();
+    if let Some(min) = min {
+        range.insert("$gte", min);
+    }
+    if let Some(max) = max {
+        range.insert("$lte", max);
+    }
+
+    if range.is_empty() {
+        None
+    } else {
+        Some(range)
+    }
+}
+
+impl DmpQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn section(mut self, title: &str) -> Self {
+        self.section_title = Some(title.to_string());
+        self
+    }
+
+    /// Matches sections carrying at least one of `tags`.
+    pub fn any_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags.iter().map(|t| normalize_tag(t)).collect();
+        self.tag_match = TagMatch::Any;
+        self
+    }
+
+    /// Matches sections carrying all of `tags`.
+    pub fn all_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags.iter().map(|t| normalize_tag(t)).collect();
+        self.tag_match = TagMatch::All;
+        self
+    }
+
+    /// Matches sections carrying none of `tags`, or without a section title,
+    /// DMPs none of whose sections carry them.
+    pub fn exclude_tags(mut self, tags: Vec<String>) -> Self {
+        self.excluded_tags = tags.iter().map(|t| normalize_tag(t)).collect();
+        self
+    }
+
+    /// The query with tags that may match any tag, and excluded tags, widened
+    /// to the vocabulary tags below them. Tags that must all match are kept
+    /// as they are.
+    pub fn with_expanded_tags(&self, vocabulary: &TagVocabulary) -> Self {
+        let expand = |tags: &[String]| {
+            let mut expanded: Vec<String> = Vec::new();
+            for tag in tags.iter().flat_map(|t| vocabulary.expand(t)) {
+                if !expanded.contains(&tag) {
+                    expanded.push(tag);
+                }
+            }
+            expanded
+        };
+
+        let mut query = self.clone();
+        if query.tag_match == TagMatch::Any {
+            query.tags = expand(&self.tags);
+        }
+        query.excluded_tags = expand(&self.excluded_tags);
+        query
+    }
+
+    /// Matches sections rated on the scale of `rating` and at least as high.
+    pub fn min_rating(mut self, rating: Rating) -> Self {
+        self.min_rating = Some(rating);
+        self
+    }
+
+    /// Matches sections rated on the scale of `rating` and at most as high.
+    pub fn max_rating(mut self, rating: Rating) -> Self {
+        self.max_rating = Some(rating);
+        self
+    }
+
+    pub fn min_overall_score(mut self, score: f32) -> Self {
+        self.min_overall_score = Some(score);
+        self
+    }
+
+    pub fn max_overall_score(mut self, score: f32) -> Self {
+        self.max_overall_score = Some(score);
+        self
+    }
+
+    pub fn funder(mut self, funder: &str) -> Self {
+        self.funder = Some(funder.to_string());
+        self
+    }
+
+    pub fn institution(mut self, institution: &str) -> Self {
+        self.institution = Some(institution.to_string());
+        self
+    }
+
+    pub fn author(mut self, name: &str) -> Self {
+        self.author = Some(name.to_string());
+        self
+    }
+
+    pub fn created_between(mut self, from: Option<DateTime>, to: Option<DateTime>) -> Self {
+        self.created_from = from;
+        self.created_to = to;
+        self
+    }
+
+    pub fn reviewed_between(mut self, from: Option<DateTime>, to: Option<DateTime>) -> Self {
+        self.reviewed_from = from;
+        self.reviewed_to = to;
+        self
+    }
+
+    /// Matches DMPs related to the entity of the given type ("dataset",
+    /// "publication", "software" or "dmp") and identifier, and if
+    /// `relation_type` is given, related to it in that way.
+    pub fn related_entity(
+        mut self,
+        entity_type: &str,
+        identifier: &str,
+        relation_type: Option<RelationType>,
+    ) -> Self {
+        self.related_entity = Some(RelatedEntityFilter {
+            entity_type: entity_type.to_string(),
+            identifier: identifier.to_string(),
+            relation_type,
+        });
+        self
+    }
+
+    pub fn section_title(&self) -> Option<&str> {
+        self.section_title.as_deref()
+    }
+
+    /// Translates the query into a MongoDB filter document.
+    pub fn to_filter(&self) -> Result<Document, Box<dyn Error>> {
+        let mut filter = Document::new();
+
+        let mut section = Document::new();
+        if let Some(ref title) = self.section_title {
+            section.insert("title", title);
+        }
+        let mut tags = Document::new();
+        if !self.tags.is_empty() {
+            let operator = match self.tag_match {
+                TagMatch::Any => "$in",
+                TagMatch::All => "$all",
+            };
+            tags.insert(operator, &self.tags);
+        }
+        if !self.excluded_tags.is_empty() {
+            if self.section_title.is_some() {
+                tags.insert("$nin", &self.excluded_tags);
+            } else {
+                // Inside `$elemMatch` any section without the tags would do
+                filter.insert("sections.tags", doc! { "$nin": &self.excluded_tags });
+            }
+        }
+        if !tags.is_empty() {
+            section.insert("tags", tags);
+        }
+        // Values are only comparable on the same scale
+        if let (Some(min), Some(max)) = (self.min_rating, self.max_rating) {
+            if min.scale() != max.scale() {
+                return Err(Box::new(IoError::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "The rating bounds are on different scales ({} and {})",
+                        min.scale(),
+                        max.scale()
+                    ),
+                )));
+            }
+        }
+        if let Some(scale) = self.min_rating.or(self.max_rating).map(|r| r.scale()) {
+            section.insert("rating.scale", scale.as_str());
+        }
+        // Convert u8 to i32 for MongoDB compatibility; ratings that do not
+        // apply have a null value and match no range
+        if let Some(rating) = range(
+            self.min_rating.and_then(|r| r.value()).map(i32::from),
+            self.max_rating.and_then(|r| r.value()).map(i32::from),
+        ) {
+            section.insert("rating.value", rating);
+        }
+        if !section.is_empty() {
+            filter.insert("sections", doc! { "$elemMatch": section });
+        }
+
+        if let Some(score) = range(
+            self.min_overall_score.map(|s| s as f64),
+            self.max_overall_score.map(|s| s as f64),
+        ) {
+            filter.insert("overall_rating.score", score);
+        }
+        if let Some(ref funder) = self.funder {
+            filter.insert("project_info.funder", funder);
+        }
+        if let Some(ref institution) = self.institution {
+            filter.insert("project_info.institution", institution);
+        }
+        if let Some(ref author) = self.author {
+            filter.insert("authors.name", author);
+        }
+        if let Some(created) = range(self.created_from, self.created_to) {
+            filter.insert("created_date", created);
+        }
+        if let Some(reviewed) = range(self.reviewed_from, self.reviewed_to) {
+            filter.insert("overall_rating.review_date", reviewed);
+        }
+
+        if let Some(ref related) = self.related_entity {
+            let (array, id_field, relation_field) = related_entity_array(&related.entity_type)
+                .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "Invalid entity type"))?;
+            let id_path = format!("{}.identifier", id_field);
+            // Identifiers are stored in canonical form, older ones as entered
+            let identifiers = identifier_variants(&related.identifier, None);
+
+            match related.relation_type {
+                // Both must hold for the same entry
+                Some(ref relation_type) => {
+                    filter.insert(
+                        array,
+                        doc! {
+                            "$elemMatch": {
+                                id_path: { "$in": &identifiers },
+                                relation_field: relation_type.as_str()
+                            }
+                        },
+                    );
+                }
+                None => {
+                    filter.insert(
+                        format!("{}.{}", array, id_path),
+                        doc! { "$in": &identifiers },
+                    );
+                }
+            }
+        }
+
+        Ok(filter)
+    }
+}
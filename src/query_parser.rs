@@ -0,0 +1,518 @@
+use mongodb::bson::DateTime;
+use thiserror::Error;
+
+use crate::matching::resolve_section_title;
+use crate::query::{end_of_day, parse_date, DmpQuery};
+use crate::scales::RubricScales;
+use crate::{Rating, RatingScale, RelationType};
+
+const FIELDS: &str = "section, tag, rating, score, funder, institution, author, created, \
+                      reviewed, dataset, publication, software, dmp, relation";
+
+/// An error in a search expression, located by the character range of the
+/// offending token.
+#[derive(Debug, Error)]
+#[error("{message} (at position {})", .start + 1)]
+pub struct QueryParseError {
+    message: String,
+    start: usize,
+    end: usize,
+}
+
+impl QueryParseError {
+    fn new(message: String, start: usize, end: usize) -> Self {
+        Self {
+            message,
+            start,
+            end: end.max(start + 1),
+        }
+    }
+
+    /// Renders `input` followed by a line of carets under the offending token.
+    pub fn pointer(&self, input: &str) -> String {
+        format!(
+            "{}\n{}{}",
+            input,
+            " ".repeat(self.start),
+            "^".repeat(self.end - self.start)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Colon,
+    Equal,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+/// A single `[-]field<op>value` term of a search expression.
+#[derive(Debug)]
+struct Term {
+    negated: bool,
+    field: String,
+    operator: Operator,
+    value: String,
+    start: usize,
+    end: usize,
+}
+
+impl Term {
+    fn error(&self, message: String) -> QueryParseError {
+        QueryParseError::new(message, self.start, self.end)
+    }
+}
+
+/// Splits a search expression into terms, tracking character positions.
+fn tokenize(input: &str) -> Result<Vec<Term>, QueryParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut terms = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i].is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let word_end = chars[i..]
+            .iter()
+            .position(|c| c.is_whitespace())
+            .map_or(chars.len(), |offset| i + offset);
+
+        let negated = chars[i] == '-';
+        if negated {
+            i += 1;
+        }
+
+        let field_start = i;
+        while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+            i += 1;
+        }
+        let field: String = chars[field_start..i].iter().collect();
+
+        let operator = match (chars.get(i), chars.get(i + 1)) {
+            (Some('>'), Some('=')) => Some((Operator::GreaterOrEqual, 2)),
+            (Some('<'), Some('=')) => Some((Operator::LessOrEqual, 2)),
+            (Some('>'), _) => Some((Operator::Greater, 1)),
+            (Some('<'), _) => Some((Operator::Less, 1)),
+            (Some('='), _) => Some((Operator::Equal, 1)),
+            (Some(':'), _) => Some((Operator::Colon, 1)),
+            _ => None,
+        };
+
+        let (operator, operator_len) = match operator {
+            Some(operator) if !field.is_empty() => operator,
+            _ => {
+                let word: String = chars[start..word_end].iter().collect();
+                return Err(QueryParseError::new(
+                    format!(
+                        "'{}' is not a search term; expected field:value (e.g. tag:reuse) \
+                         or a comparison (e.g. rating>=4)",
+                        word
+                    ),
+                    start,
+                    word_end,
+                ));
+            }
+        };
+        i += operator_len;
+
+        let value = if chars.get(i) == Some(&'"') {
+            let quote = i;
+            let closing = chars[quote + 1..]
+                .iter()
+                .position(|&c| c == '"')
+                .map(|offset| quote + 1 + offset)
+                .ok_or_else(|| {
+                    QueryParseError::new("unterminated quote".to_string(), quote, chars.len())
+                })?;
+            i = closing + 1;
+            chars[quote + 1..closing].iter().collect::<String>()
+        } else {
+            let value_start = i;
+            while i < chars.len() && !chars[i].is_whitespace() {
+                i += 1;
+            }
+            chars[value_start..i].iter().collect::<String>()
+        };
+
+        if value.trim().is_empty() {
+            return Err(QueryParseError::new(
+                format!("missing value for '{}'", field),
+                start,
+                i,
+            ));
+        }
+
+        terms.push(Term {
+            negated,
+            field: field.to_lowercase(),
+            operator,
+            value: value.trim().to_string(),
+            start,
+            end: i,
+        });
+    }
+
+    Ok(terms)
+}
+
+/// Rejects comparison operators on fields that only support `:`.
+fn expect_colon(term: &Term) -> Result<(), QueryParseError> {
+    match term.operator {
+        Operator::Colon | Operator::Equal => Ok(()),
+        _ => Err(term.error(format!(
+            "'{}' cannot be compared; use {}:value",
+            term.field, term.field
+        ))),
+    }
+}
+
+/// Stores `value` in a single-valued criterion, rejecting repeated fields.
+fn set_once(slot: &mut Option<String>, term: &Term) -> Result<(), QueryParseError> {
+    expect_colon(term)?;
+    if slot.is_some() {
+        return Err(term.error(format!("'{}' is given more than once", term.field)));
+    }
+    *slot = Some(term.value.clone());
+    Ok(())
+}
+
+/// Applies a date comparison to a `(from, to)` range. `field:date` matches the
+/// whole day.
+fn date_range(
+    term: &Term,
+    range: &mut (Option<DateTime>, Option<DateTime>),
+) -> Result<(), QueryParseError> {
+    let date = parse_date(&term.value).ok_or_else(|| {
+        term.error(format!(
+            "'{}' is not a valid date; expected YYYY-MM-DD",
+            term.value
+        ))
+    })?;
+    let end_of_day = end_of_day(date);
+
+    match term.operator {
+        Operator::Colon | Operator::Equal => *range = (Some(date), Some(end_of_day)),
+        Operator::GreaterOrEqual => range.0 = Some(date),
+        Operator::LessOrEqual => range.1 = Some(end_of_day),
+        Operator::Greater | Operator::Less => {
+            return Err(term.error(format!("use >= or <= to compare '{}' dates", term.field)))
+        }
+    }
+
+    Ok(())
+}
+
+/// The `(min, max)` bounds a rating comparison sets, with the rating read on
+/// `scale`.
+fn rating_bounds(
+    term: &Term,
+    scale: RatingScale,
+) -> Result<(Option<Rating>, Option<Rating>), QueryParseError> {
+    let rating = scale.parse_rating(&term.value).map_err(|e| term.error(e))?;
+    let value = rating.value().ok_or_else(|| {
+        term.error("sections that do not apply have no rating to compare".to_string())
+    })?;
+    let bound = |value: Option<u8>| {
+        value
+            .and_then(|v| Rating::new(scale, v).ok())
+            .ok_or_else(|| term.error("no rating can satisfy this comparison".to_string()))
+    };
+
+    Ok(match term.operator {
+        Operator::Colon | Operator::Equal => (Some(rating), Some(rating)),
+        Operator::GreaterOrEqual => (Some(rating), None),
+        Operator::Greater => (Some(bound(value.checked_add(1))?), None),
+        Operator::LessOrEqual => (None, Some(rating)),
+        Operator::Less => (None, Some(bound(value.checked_sub(1))?)),
+    })
+}
+
+/// Parses a search expression such as
+/// `section:"FAIR Principles" rating>=4 tag:reuse funder:NSF -tag:incomplete`
+/// into a `DmpQuery`.
+///
+/// Every term must hold; repeated `tag:` terms require all of the tags and
+/// `-tag:` excludes sections carrying the tag, or without `section:`, DMPs
+/// with any section carrying it. `relation:` restricts the
+/// related entity term to a relation type, e.g. `dataset:10.5061/x relation:IsSupplementTo`.
+/// `rating` terms need a `section:` term and are read on its scale in `scales`.
+pub fn parse_query(input: &str, scales: &RubricScales) -> Result<DmpQuery, QueryParseError> {
+    let terms = tokenize(input)?;
+    if terms.is_empty() {
+        return Err(QueryParseError::new(
+            "empty search expression".to_string(),
+            0,
+            0,
+        ));
+    }
+
+    let mut section = None;
+    let mut tags = Vec::new();
+    let mut excluded_tags = Vec::new();
+    let mut ratings: Vec<&Term> = Vec::new();
+    let mut min_score = None;
+    let mut max_score = None;
+    let mut funder = None;
+    let mut institution = None;
+    let mut author = None;
+    let mut created = (None, None);
+    let mut reviewed = (None, None);
+    let mut related: Option<(String, String)> = None;
+    let mut relation: Option<&Term> = None;
+
+    for term in &terms {
+        if term.negated && term.field != "tag" {
+            return Err(term.error("only tag: terms can be negated".to_string()));
+        }
+
+        match term.field.as_str() {
+            "section" => {
+                set_once(&mut section, term)?;
+                // Accept any spelling that resolves to a rubric section
+                if let Some(title) = resolve_section_title(&term.value) {
+                    section = Some(title.to_string());
+                }
+            }
+            "tag" => {
+                expect_colon(term)?;
+                if term.negated {
+                    excluded_tags.push(term.value.clone());
+                } else {
+                    tags.push(term.value.clone());
+                }
+            }
+            // Read once the section, and with it the scale, is known
+            "rating" => ratings.push(term),
+            "score" => {
+                let score = term
+                    .value
+                    .parse::<f32>()
+                    .map_err(|_| term.error(format!("'{}' is not a valid score", term.value)))?;
+
+                match term.operator {
+                    Operator::Colon | Operator::Equal => {
+                        min_score = Some(score);
+                        max_score = Some(score);
+                    }
+                    Operator::GreaterOrEqual => min_score = Some(score),
+                    Operator::LessOrEqual => max_score = Some(score),
+                    Operator::Greater | Operator::Less => {
+                        return Err(term.error("use >= or <= to compare scores".to_string()))
+                    }
+                }
+            }
+            "funder" => set_once(&mut funder, term)?,
+            "institution" => set_once(&mut institution, term)?,
+            "author" => set_once(&mut author, term)?,
+            "created" => date_range(term, &mut created)?,
+            "reviewed" => date_range(term, &mut reviewed)?,
+            "dataset" | "publication" | "software" | "dmp" => {
+                expect_colon(term)?;
+                if related.is_some() {
+                    return Err(
+                        term.error("only one related entity can be searched at a time".to_string())
+                    );
+                }
+                related = Some((term.field.clone(), term.value.clone()));
+            }
+            "relation" => {
+                expect_colon(term)?;
+                if relation.is_some() {
+                    return Err(term.error("'relation' is given more than once".to_string()));
+                }
+                relation = Some(term);
+            }
+            _ => {
+                return Err(term.error(format!(
+                    "unknown field '{}'; expected one of: {}",
+                    term.field, FIELDS
+                )))
+            }
+        }
+    }
+
+    let mut min_rating = None;
+    let mut max_rating = None;
+    if let Some(first) = ratings.first() {
+        let section = section.as_deref().ok_or_else(|| {
+            first.error(
+                "'rating' needs a section: term, as ratings are compared on the section's scale"
+                    .to_string(),
+            )
+        })?;
+        let scale = scales.scale_for(section);
+        for term in ratings {
+            let (min, max) = rating_bounds(term, scale)?;
+            min_rating = min.or(min_rating);
+            max_rating = max.or(max_rating);
+        }
+    }
+
+    let mut query = DmpQuery::new();
+    if let Some(ref section) = section {
+        query = query.section(section);
+    }
+    if !tags.is_empty() {
+        query = query.all_tags(tags);
+    }
+    if !excluded_tags.is_empty() {
+        query = query.exclude_tags(excluded_tags);
+    }
+    if let Some(rating) = min_rating {
+        query = query.min_rating(rating);
+    }
+    if let Some(rating) = max_rating {
+        query = query.max_rating(rating);
+    }
+    if let Some(score) = min_score {
+        query = query.min_overall_score(score);
+    }
+    if let Some(score) = max_score {
+        query = query.max_overall_score(score);
+    }
+    if let Some(ref funder) = funder {
+        query = query.funder(funder);
+    }
+    if let Some(ref institution) = institution {
+        query = query.institution(institution);
+    }
+    if let Some(ref author) = author {
+        query = query.author(author);
+    }
+    if created.0.is_some() || created.1.is_some() {
+        query = query.created_between(created.0, created.1);
+    }
+    if reviewed.0.is_some() || reviewed.1.is_some() {
+        query = query.reviewed_between(reviewed.0, reviewed.1);
+    }
+    match (related, relation) {
+        (Some((entity_type, identifier)), relation) => {
+            let relation_type = relation.map(|term| RelationType::from(term.value.clone()));
+            query = query.related_entity(&entity_type, &identifier, relation_type);
+        }
+        (None, Some(term)) => {
+            return Err(term.error(
+                "'relation' needs a dataset:, publication:, software: or dmp: term".to_string(),
+            ))
+        }
+        (None, None) => {}
+    }
+
+    Ok(query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    fn parse(input: &str) -> Result<DmpQuery, QueryParseError> {
+        parse_query(input, &RubricScales::default())
+    }
+
+    fn error(input: &str) -> (String, usize, usize) {
+        let e = parse(input).unwrap_err();
+        (e.message, e.start, e.end)
+    }
+
+    fn date(input: &str) -> DateTime {
+        DateTime::parse_rfc3339_str(input).unwrap()
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_term() {
+        assert_eq!(error("   "), ("empty search expression".to_string(), 0, 1));
+        assert_eq!(
+            error("tag:\"open data"),
+            ("unterminated quote".to_string(), 4, 14)
+        );
+        assert_eq!(
+            error("section:\"Data Description\" colour:red"),
+            (
+                format!("unknown field 'colour'; expected one of: {}", FIELDS),
+                27,
+                37
+            )
+        );
+        assert_eq!(
+            error("funder:NSF -score>=3"),
+            ("only tag: terms can be negated".to_string(), 11, 20)
+        );
+        assert_eq!(
+            error("tag:reuse rating>=4"),
+            (
+                "'rating' needs a section: term, as ratings are compared on the section's scale"
+                    .to_string(),
+                10,
+                19
+            )
+        );
+        assert_eq!(
+            error("section:\"Data Description\" rating>5"),
+            ("no rating can satisfy this comparison".to_string(), 27, 35)
+        );
+    }
+
+    #[test]
+    fn dates_are_compared_with_inclusive_operators_only() {
+        assert_eq!(
+            error("created>2024-01-01"),
+            ("use >= or <= to compare 'created' dates".to_string(), 0, 18)
+        );
+        assert_eq!(
+            error("tag:reuse reviewed<2024-01-01"),
+            (
+                "use >= or <= to compare 'reviewed' dates".to_string(),
+                10,
+                29
+            )
+        );
+
+        let filter = parse("created:2024-03-01 reviewed<=2024-03-31")
+            .unwrap()
+            .to_filter()
+            .unwrap();
+        assert_eq!(
+            filter.get_document("created_date").unwrap(),
+            &doc! {
+                "$gte": date("2024-03-01T00:00:00Z"),
+                "$lte": date("2024-03-01T23:59:59.999Z")
+            }
+        );
+        assert_eq!(
+            filter.get_document("overall_rating.review_date").unwrap(),
+            &doc! { "$lte": date("2024-03-31T23:59:59.999Z") }
+        );
+    }
+
+    #[test]
+    fn excluded_tags_apply_to_every_section_without_a_section_term() {
+        let filter = parse("-tag:incomplete").unwrap().to_filter().unwrap();
+        assert_eq!(filter, doc! { "sections.tags": { "$nin": ["incomplete"] } });
+
+        let filter = parse("section:\"Data Description\" -tag:incomplete rating>3")
+            .unwrap()
+            .to_filter()
+            .unwrap();
+        assert_eq!(
+            filter,
+            doc! {
+                "sections": {
+                    "$elemMatch": {
+                        "title": "Data Description & Collection",
+                        "tags": { "$nin": ["incomplete"] },
+                        "rating.scale": "1-5",
+                        "rating.value": { "$gte": 4 }
+                    }
+                }
+            }
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use mongodb::bson::{self, Bson};
+
+use crate::identifiers::normalize_identifier;
+use crate::query::parse_date;
+use crate::{
+    DataManagementPlan, Identifier, IdentifierType, RelatedDMP, RelatedDataset, RelatedPublication,
+    RelatedSoftware, RelationType,
+};
+
+/// The kinds of related entity a DMP can list, as accepted by
+/// `find_dmps_by_related_entity`.
+pub const ENTITY_TYPES: [&str; 4] = ["dataset", "publication", "software", "dmp"];
+
+/// Value that clears an optional field when editing a related entity.
+pub const CLEAR_VALUE: &str = "-";
+
+/// Any of the related entities a DMP can list, so they can be added, edited
+/// and removed the same way.
+#[derive(Debug, Clone)]
+pub enum RelatedEntity {
+    Dataset(RelatedDataset),
+    Publication(RelatedPublication),
+    Software(RelatedSoftware),
+    Dmp(RelatedDMP),
+}
+
+impl RelatedEntity {
+    /// One of `ENTITY_TYPES`.
+    pub fn entity_type(&self) -> &'static str {
+        match self {
+            RelatedEntity::Dataset(_) => "dataset",
+            RelatedEntity::Publication(_) => "publication",
+            RelatedEntity::Software(_) => "software",
+            RelatedEntity::Dmp(_) => "dmp",
+        }
+    }
+
+    pub fn identifier(&self) -> &Identifier {
+        match self {
+            RelatedEntity::Dataset(dataset) => &dataset.dataset_id,
+            RelatedEntity::Publication(publication) => &publication.publication_id,
+            RelatedEntity::Software(software) => &software.software_id,
+            RelatedEntity::Dmp(dmp) => &dmp.dmp_id,
+        }
+    }
+
+    /// The entity's title, or name for software.
+    pub fn title(&self) -> &str {
+        match self {
+            RelatedEntity::Dataset(dataset) => &dataset.title,
+            RelatedEntity::Publication(publication) => &publication.title,
+            RelatedEntity::Software(software) => &software.name,
+            RelatedEntity::Dmp(dmp) => &dmp.title,
+        }
+    }
+
+    pub fn relationship(&self) -> &RelationType {
+        match self {
+            RelatedEntity::Dataset(dataset) => &dataset.relationship_type,
+            RelatedEntity::Publication(publication) => &publication.relationship_type,
+            RelatedEntity::Software(software) => &software.relationship,
+            RelatedEntity::Dmp(dmp) => &dmp.relationship_type,
+        }
+    }
+
+    /// A warning to show when the relationship is outside the DataCite
+    /// vocabulary, so typos are noticed before they are stored.
+    pub fn custom_relationship_note(&self) -> Option<String> {
+        match self.relationship() {
+            RelationType::Other(value) => Some(format!(
+                "Note: '{}' is not a DataCite relation type and is stored as given",
+                value
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn to_bson(&self) -> Result<Bson, bson::ser::Error> {
+        match self {
+            RelatedEntity::Dataset(dataset) => bson::to_bson(dataset),
+            RelatedEntity::Publication(publication) => bson::to_bson(publication),
+            RelatedEntity::Software(software) => bson::to_bson(software),
+            RelatedEntity::Dmp(dmp) => bson::to_bson(dmp),
+        }
+    }
+}
+
+impl std::fmt::Display for RelatedEntity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}/{}: {} ({})",
+            self.entity_type(),
+            self.identifier().id_type,
+            self.identifier().identifier,
+            self.title(),
+            self.relationship()
+        )
+    }
+}
+
+/// Returns every entity related to `dmp`, in `ENTITY_TYPES` order.
+pub fn related_entities(dmp: &DataManagementPlan) -> Vec<RelatedEntity> {
+    let mut entities = Vec::new();
+
+    for dataset in dmp.related_datasets.iter().flatten() {
+        entities.push(RelatedEntity::Dataset(dataset.clone()));
+    }
+    for publication in dmp.related_publications.iter().flatten() {
+        entities.push(RelatedEntity::Publication(publication.clone()));
+    }
+    for software in dmp.related_software.iter().flatten() {
+        entities.push(RelatedEntity::Software(software.clone()));
+    }
+    for related_dmp in dmp.related_dmps.iter().flatten() {
+        entities.push(RelatedEntity::Dmp(related_dmp.clone()));
+    }
+
+    entities
+}
+
+/// Values entered for a related entity. Unset fields keep the value of the
+/// entity being edited; `CLEAR_VALUE` clears an optional field.
+#[derive(Debug, Default)]
+pub struct RelatedEntityFields {
+    pub identifier: Option<String>,
+    pub id_type: Option<String>,
+    pub relationship: Option<String>,
+    pub title: Option<String>,
+    pub repository: Option<String>,
+    pub url: Option<String>,
+    // Publication authors, separated by semicolons
+    pub authors: Option<String>,
+    pub journal: Option<String>,
+    pub publication_date: Option<String>,
+    pub version: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// Picks the entered value of a required field, falling back to the current one.
+fn required(value: Option<String>, current: Option<String>, name: &str) -> Result<String, String> {
+    value
+        .filter(|v| !v.trim().is_empty() && v.trim() != CLEAR_VALUE)
+        .map(|v| v.trim().to_string())
+        .or(current)
+        .ok_or_else(|| format!("{} is required", name))
+}
+
+/// Picks the entered value of an optional field, falling back to the current one.
+fn optional(value: Option<String>, current: Option<String>) -> Option<String> {
+    match value {
+        Some(v) if v.trim() == CLEAR_VALUE => None,
+        Some(v) if !v.trim().is_empty() => Some(v.trim().to_string()),
+        _ => current,
+    }
+}
+
+/// Builds a related entity of `entity_type` from `fields`, starting from
+/// `existing` when editing one.
+pub fn build_related_entity(
+    entity_type: &str,
+    fields: RelatedEntityFields,
+    existing: Option<RelatedEntity>,
+) -> Result<RelatedEntity, String> {
+    if let Some(ref existing) = existing {
+        if existing.entity_type() != entity_type {
+            return Err(format!(
+                "Cannot turn a related {} into a related {}",
+                existing.entity_type(),
+                entity_type
+            ));
+        }
+    }
+
+    let current_id = existing.as_ref().map(|e| e.identifier().clone());
+    let (id_type, identifier) = normalize_identifier(
+        &required(
+            fields.identifier,
+            current_id.as_ref().map(|id| id.identifier.clone()),
+            "Identifier",
+        )?,
+        &IdentifierType::from(required(
+            fields.id_type,
+            current_id.map(|id| id.id_type.to_string()),
+            "Identifier type",
+        )?),
+    )?;
+    let id = Identifier {
+        identifier,
+        id_type,
+    };
+    let relationship = RelationType::from(required(
+        fields.relationship,
+        existing.as_ref().map(|e| e.relationship().to_string()),
+        "Relationship type",
+    )?);
+    let title = required(
+        fields.title,
+        existing.as_ref().map(|e| e.title().to_string()),
+        if entity_type == "software" {
+            "Name"
+        } else {
+            "Title"
+        },
+    )?;
+
+    match (entity_type, existing) {
+        ("dataset", existing) => {
+            let current = match existing {
+                Some(RelatedEntity::Dataset(dataset)) => Some(dataset),
+                _ => None,
+            };
+            Ok(RelatedEntity::Dataset(RelatedDataset {
+                dataset_id: id,
+                relationship_type: relationship,
+                title,
+                repository: optional(
+                    fields.repository,
+                    current.as_ref().and_then(|d| d.repository.clone()),
+                ),
+                url: optional(fields.url, current.and_then(|d| d.url)),
+            }))
+        }
+        ("publication", existing) => {
+            let current = match existing {
+                Some(RelatedEntity::Publication(publication)) => Some(publication),
+                _ => None,
+            };
+            let authors = match fields.authors {
+                Some(ref a) if a.trim() == CLEAR_VALUE => Vec::new(),
+                Some(ref a) if !a.trim().is_empty() => a
+                    .split(';')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect(),
+                _ => current
+                    .as_ref()
+                    .map(|p| p.authors.clone())
+                    .unwrap_or_default(),
+            };
+            let publication_date = match fields.publication_date {
+                Some(ref d) if d.trim() == CLEAR_VALUE => None,
+                Some(ref d) if !d.trim().is_empty() => {
+                    Some(parse_date(d.trim()).ok_or_else(|| {
+                        format!("'{}' is not a valid date; expected YYYY-MM-DD", d.trim())
+                    })?)
+                }
+                _ => current.as_ref().and_then(|p| p.publication_date),
+            };
+            Ok(RelatedEntity::Publication(RelatedPublication {
+                publication_id: id,
+                relationship_type: relationship,
+                title,
+                authors,
+                journal: optional(
+                    fields.journal,
+                    current.as_ref().and_then(|p| p.journal.clone()),
+                ),
+                publication_date,
+                url: optional(fields.url, current.and_then(|p| p.url)),
+            }))
+        }
+        ("software", existing) => {
+            let current = match existing {
+                Some(RelatedEntity::Software(software)) => Some(software),
+                _ => None,
+            };
+            Ok(RelatedEntity::Software(RelatedSoftware {
+                software_id: id,
+                name: title,
+                version: optional(
+                    fields.version,
+                    current.as_ref().and_then(|s| s.version.clone()),
+                ),
+                relationship,
+                url: optional(fields.url, current.and_then(|s| s.url)),
+            }))
+        }
+        ("dmp", existing) => {
+            let current = match existing {
+                Some(RelatedEntity::Dmp(dmp)) => Some(dmp),
+                _ => None,
+            };
+            Ok(RelatedEntity::Dmp(RelatedDMP {
+                dmp_id: id,
+                relationship_type: relationship,
+                title,
+                relationship_notes: optional(
+                    fields.notes,
+                    current.and_then(|d| d.relationship_notes),
+                ),
+            }))
+        }
+        _ => Err(format!(
+            "'{}' is not a valid entity type; expected one of: {}",
+            entity_type,
+            ENTITY_TYPES.join(", ")
+        )),
+    }
+}
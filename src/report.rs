@@ -0,0 +1,260 @@
+use std::fs;
+use std::io;
+
+use crate::{Annotation, DataManagementPlan};
+
+const REPORT_STYLE: &str = "body { font-family: sans-serif; max-width: 50em; margin: 2em auto; }
+mark { background: #fff3a0; }
+.section-text { white-space: pre-wrap; border-left: 3px solid #ccc; padding-left: 1em; }
+.meta { color: #555; }
+.annotations li { margin-bottom: 0.5em; }";
+
+/// Uppercases the first character of `text`.
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Escapes the characters with special meaning in HTML.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders `text` as HTML with every annotated passage wrapped in `<mark>`
+/// and followed by the annotation's number (counted from 1 in `annotations`
+/// order). Overlapping passages are split so each part names every
+/// annotation covering it.
+pub fn highlight_annotations(text: &str, annotations: &[Annotation]) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let ranges: Vec<(usize, (usize, usize))> = annotations
+        .iter()
+        .enumerate()
+        .filter_map(|(i, annotation)| annotation.resolve(text).map(|range| (i + 1, range)))
+        .collect();
+
+    let mut boundaries: Vec<usize> = vec![0, chars.len()];
+    for (_, (start, end)) in &ranges {
+        boundaries.push(*start);
+        boundaries.push(*end);
+    }
+    boundaries.sort();
+    boundaries.dedup();
+
+    let mut html = String::new();
+    for window in boundaries.windows(2) {
+        let (start, end) = (window[0], window[1]);
+        let segment = escape_html(&chars[start..end].iter().collect::<String>());
+        let covering: Vec<String> = ranges
+            .iter()
+            .filter(|(_, (s, e))| *s <= start && end <= *e)
+            .map(|(number, _)| number.to_string())
+            .collect();
+
+        if covering.is_empty() {
+            html.push_str(&segment);
+        } else {
+            html.push_str(&format!(
+                "<mark title=\"Annotation {}\">{}</mark>",
+                covering.join(", "),
+                segment
+            ));
+        }
+
+        // Number each annotation where its passage ends
+        for (number, _) in ranges.iter().filter(|(_, (_, e))| *e == end) {
+            html.push_str(&format!("<sup>[{}]</sup>", number));
+        }
+    }
+
+    html
+}
+
+/// Renders a standalone HTML report of a DMP's ratings, with annotated
+/// passages of the section text highlighted.
+pub fn render_dmp_report(dmp: &DataManagementPlan) -> String {
+    let mut html = String::new();
+
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape_html(&dmp.title)));
+    html.push_str(&format!("<style>\n{}\n</style>\n", REPORT_STYLE));
+    html.push_str("</head>\n<body>\n");
+
+    html.push_str(&format!("<h1>{}</h1>\n", escape_html(&dmp.title)));
+    html.push_str(&format!(
+        "<p class=\"meta\">ID: {}/{} &middot; Version {} &middot; Last modified {}</p>\n",
+        escape_html(dmp.dmp_id.id_type.as_str()),
+        escape_html(&dmp.dmp_id.identifier),
+        escape_html(&dmp.version),
+        dmp.last_modified
+    ));
+
+    if !dmp.authors.is_empty() {
+        let authors: Vec<String> = dmp.authors.iter().map(|a| escape_html(&a.name)).collect();
+        html.push_str(&format!("<p>Authors: {}</p>\n", authors.join(", ")));
+    }
+
+    if let Some(ref project) = dmp.project_info {
+        html.push_str(&format!(
+            "<p>Project: {}",
+            escape_html(&project.project_title)
+        ));
+        if let Some(ref funder) = project.funder {
+            html.push_str(&format!(" &middot; Funder: {}", escape_html(funder)));
+        }
+        if let Some(ref institution) = project.institution {
+            html.push_str(&format!(
+                " &middot; Institution: {}",
+                escape_html(institution)
+            ));
+        }
+        html.push_str("</p>\n");
+    }
+
+    if let Some(ref overall_rating) = dmp.overall_rating {
+        html.push_str(&format!(
+            "<h2>Overall rating: {}/5</h2>\n<p class=\"meta\">Reviewed by {} on {}</p>\n",
+            overall_rating.score,
+            escape_html(&overall_rating.reviewer),
+            overall_rating.review_date
+        ));
+        if !overall_rating.comments.is_empty() {
+            html.push_str(&format!(
+                "<p>{}</p>\n",
+                escape_html(&overall_rating.comments)
+            ));
+        }
+    }
+
+    for (i, section) in dmp.sections.iter().enumerate() {
+        html.push_str(&format!(
+            "<h2 id=\"section-{}\">{} ({})</h2>\n",
+            i + 1,
+            escape_html(&section.title),
+            section
+                .rating
+                .map_or("Not rated".to_string(), |r| r.to_string())
+        ));
+
+        if !section.tags.is_empty() {
+            html.push_str(&format!(
+                "<p class=\"meta\">Tags: {}</p>\n",
+                escape_html(&section.tags.join(", "))
+            ));
+        }
+
+        if let Some(ref text) = section.text {
+            html.push_str(&format!(
+                "<div class=\"section-text\">{}</div>\n",
+                highlight_annotations(text, &section.annotations)
+            ));
+        }
+
+        if let Some(ref comments) = section.comments {
+            html.push_str(&format!("<p>{}</p>\n", escape_html(comments)));
+        }
+
+        if let Some(ref cross_references) = section.cross_references {
+            html.push_str("<ul class=\"cross-references\">\n");
+            for reference in cross_references {
+                let reference_type = reference
+                    .reference_type
+                    .map_or("References".to_string(), |t| capitalize(&t.to_string()));
+                // Sections of this DMP link to their heading in the report
+                let target = match reference.target_dmp {
+                    Some(ref target) => format!(
+                        "&ldquo;{}&rdquo; in DMP {}/{}",
+                        escape_html(&reference.section_title),
+                        escape_html(target.id_type.as_str()),
+                        escape_html(&target.identifier)
+                    ),
+                    None => match dmp
+                        .sections
+                        .iter()
+                        .position(|s| s.title == reference.section_title)
+                    {
+                        Some(position) => format!(
+                            "<a href=\"#section-{}\">{}</a>",
+                            position + 1,
+                            escape_html(&reference.section_title)
+                        ),
+                        None => escape_html(&reference.section_title),
+                    },
+                };
+                html.push_str(&format!(
+                    "<li>{} {}: {}</li>\n",
+                    reference_type,
+                    target,
+                    escape_html(&reference.reference_note)
+                ));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        if !section.annotations.is_empty() {
+            html.push_str("<ol class=\"annotations\">\n");
+            for annotation in &section.annotations {
+                let passage = section
+                    .text
+                    .as_deref()
+                    .and_then(|text| annotation.passage(text))
+                    .map_or("(passage not found)".to_string(), |p| {
+                        format!("&ldquo;{}&rdquo;", escape_html(&p))
+                    });
+                html.push_str(&format!(
+                    "<li>{} &mdash; <strong>{}</strong>",
+                    passage,
+                    escape_html(&annotation.author)
+                ));
+                if !annotation.tags.is_empty() {
+                    html.push_str(&format!(
+                        " <span class=\"meta\">[{}]</span>",
+                        escape_html(&annotation.tags.join(", "))
+                    ));
+                }
+                html.push_str(&format!(": {}</li>\n", escape_html(&annotation.comment)));
+            }
+            html.push_str("</ol>\n");
+        }
+
+        for subsection in &section.subsections {
+            html.push_str(&format!(
+                "<h3>{} ({})</h3>\n",
+                escape_html(&subsection.title),
+                subsection
+                    .rating
+                    .map_or("Not rated".to_string(), |r| r.to_string())
+            ));
+            if let Some(ref text) = subsection.text {
+                html.push_str(&format!(
+                    "<div class=\"section-text\">{}</div>\n",
+                    escape_html(text)
+                ));
+            }
+            if let Some(ref comments) = subsection.comments {
+                html.push_str(&format!("<p>{}</p>\n", escape_html(comments)));
+            }
+        }
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Writes the HTML report of `dmp` to `file_path`.
+pub fn write_dmp_report(dmp: &DataManagementPlan, file_path: &str) -> io::Result<()> {
+    fs::write(file_path, render_dmp_report(dmp))
+}
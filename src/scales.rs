@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+
+use crate::matching::{fold, resolve_section_title};
+use crate::{DataManagementPlan, Rating, RatingScale};
+
+/// File mapping rubric section titles to the scale they are rated on, e.g.
+/// `{ "FAIR Principles": "A-F", "Ethical & Legal Compliance": "pass/fail" }`.
+/// Sections it does not list, and every section if it does not exist, are
+/// rated 1-5.
+pub const RUBRIC_SCALES_FILE: &str = "rubric_scales.json";
+
+/// The rating scale of each rubric section. Subsections are rated on the
+/// scale of their section.
+#[derive(Debug, Clone, Default)]
+pub struct RubricScales {
+    // Scales by folded section title
+    scales: HashMap<String, RatingScale>,
+}
+
+impl RubricScales {
+    /// Reads the scales from `file_path`, using 1-5 for every section if the
+    /// file does not exist. Fails if it cannot be read or names a section or
+    /// scale that does not exist.
+    pub fn load(file_path: &str) -> Result<Self, String> {
+        let contents = match fs::read_to_string(file_path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("Failed to read {}: {}", file_path, e)),
+        };
+        let entries: HashMap<String, String> = serde_json::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", file_path, e))?;
+
+        let mut scales = HashMap::new();
+        for (section, scale) in entries {
+            let title = resolve_section_title(&section)
+                .ok_or_else(|| format!("{}: '{}' is not a rubric section", file_path, section))?;
+            let scale = scale
+                .parse::<RatingScale>()
+                .map_err(|e| format!("{}: {}", file_path, e))?;
+            scales.insert(fold(title), scale);
+        }
+
+        Ok(Self { scales })
+    }
+
+    /// The scale `section_title` is rated on.
+    pub fn scale_for(&self, section_title: &str) -> RatingScale {
+        self.scales
+            .get(&fold(section_title))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The section and subsection ratings of `dmp` that are not on the
+    /// scale of their section, e.g. ratings entered before the section was
+    /// given another scale.
+    pub fn off_scale(&self, dmp: &DataManagementPlan) -> Vec<OffScaleRating> {
+        let mut off_scale = Vec::new();
+        for section in &dmp.sections {
+            let scale = self.scale_for(&section.title);
+            let mut check = |subsection: Option<&str>, rating: Option<Rating>| {
+                if let Some(rating) = rating.filter(|r| r.scale() != scale) {
+                    off_scale.push(OffScaleRating {
+                        section: section.title.clone(),
+                        subsection: subsection.map(str::to_string),
+                        rating,
+                        scale,
+                    });
+                }
+            };
+            check(None, section.rating);
+            for subsection in &section.subsections {
+                check(Some(&subsection.title), subsection.rating);
+            }
+        }
+        off_scale
+    }
+
+    /// Checks that the ratings of `dmp` that are new or changed since
+    /// `stored`, the DMP as stored before, are on the scale of their section.
+    /// Ratings kept as stored may stay on another scale.
+    pub fn check_dmp(
+        &self,
+        dmp: &DataManagementPlan,
+        stored: Option<&DataManagementPlan>,
+    ) -> Result<(), String> {
+        match self
+            .off_scale(dmp)
+            .into_iter()
+            .find(|off| stored.and_then(|s| off.rating_in(s)) != Some(off.rating))
+        {
+            Some(off) => Err(format!(
+                "'{}' is rated on the {} scale, but the section is rated {}",
+                off.path(),
+                off.rating.scale(),
+                off.scale
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A rating on another scale than its section's.
+#[derive(Debug)]
+pub struct OffScaleRating {
+    pub section: String,
+    pub subsection: Option<String>,
+    pub rating: Rating,
+    /// The scale of the section
+    pub scale: RatingScale,
+}
+
+impl OffScaleRating {
+    /// `SECTION` or `SECTION/SUBSECTION`.
+    pub fn path(&self) -> String {
+        match self.subsection {
+            Some(ref subsection) => format!("{}/{}", self.section, subsection),
+            None => self.section.clone(),
+        }
+    }
+
+    /// The rating at the same place in `dmp`.
+    fn rating_in(&self, dmp: &DataManagementPlan) -> Option<Rating> {
+        let section = dmp.sections.iter().find(|s| s.title == self.section)?;
+        match self.subsection {
+            Some(ref title) => {
+                section
+                    .subsections
+                    .iter()
+                    .find(|s| &s.title == title)?
+                    .rating
+            }
+            None => section.rating,
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use std::error::Error;
+use std::io::{Error as IoError, ErrorKind};
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use mongodb::bson::DateTime;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::conflict::ConflictError;
+use crate::db::DatabaseManager;
+use crate::matching::MatchMode;
+use crate::openapi::openapi_document;
+use crate::query::{DmpQuery, SectionPath};
+use crate::query_parser::parse_query;
+use crate::related::ENTITY_TYPES;
+use crate::scales::RubricScales;
+use crate::update::DmpUpdate;
+use crate::web;
+use crate::{DataManagementPlan, RelationType};
+
+/// Address `serve` listens on unless given another.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+
+/// What the request handlers share: the database manager, as the
+/// interactive menu shares it, and the scales ratings are entered on.
+#[derive(Clone)]
+struct ApiState {
+    db_manager: Arc<DatabaseManager>,
+    scales: Arc<RubricScales>,
+}
+
+/// An error response: its status and a JSON body holding the message under
+/// `error`, and for conflicts the DMP as stored now under `current`.
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    body: Value,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl std::fmt::Display) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.to_string() }),
+        }
+    }
+
+    fn bad_request(message: impl std::fmt::Display) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    fn not_found(id_type: &str, dmp_id: &str) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            format!("DMP {}/{} not found", id_type, dmp_id),
+        )
+    }
+}
+
+impl From<Box<dyn Error>> for ApiError {
+    fn from(e: Box<dyn Error>) -> Self {
+        if let Some(conflict) = e.downcast_ref::<ConflictError>() {
+            return Self {
+                status: StatusCode::CONFLICT,
+                body: json!({ "error": e.to_string(), "current": conflict.current }),
+            };
+        }
+
+        Self::new(error_status(e.as_ref()), e)
+    }
+}
+
+/// The status reporting `e`: conflicts, invalid input and missing DMPs are
+/// the client's to fix, anything else is the server's.
+pub fn error_status(e: &(dyn Error + 'static)) -> StatusCode {
+    if e.is::<ConflictError>() {
+        return StatusCode::CONFLICT;
+    }
+    match e.downcast_ref::<IoError>().map(IoError::kind) {
+        Some(ErrorKind::InvalidInput) => StatusCode::BAD_REQUEST,
+        Some(ErrorKind::NotFound) => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.body)).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+/// Query of `GET /dmps`.
+#[derive(Deserialize)]
+struct ListParams {
+    /// Search expression, as taken by the `--query` options
+    q: Option<String>,
+}
+
+/// Query of `GET /search/section-tag`.
+#[derive(Deserialize)]
+struct SectionTagParams {
+    path: String,
+    tag: String,
+    mode: Option<String>,
+}
+
+/// Query of `GET /search/rating`.
+#[derive(Deserialize)]
+struct RatingParams {
+    path: String,
+    min: String,
+}
+
+/// Query of `GET /search/related`.
+#[derive(Deserialize)]
+struct RelatedParams {
+    entity: String,
+    id: String,
+    relation: Option<String>,
+}
+
+/// Body of `PATCH /dmps/{id_type}/{id}`: rating changes written together as
+/// by the "Update several ratings of a DMP at once" menu option.
+#[derive(Deserialize)]
+struct RatingUpdate {
+    reviewer: String,
+    /// `last_modified` of the DMP as read
+    read_at: DateTime,
+    #[serde(default)]
+    sections: Vec<SectionChange>,
+    overall: Option<OverallChange>,
+}
+
+#[derive(Deserialize)]
+struct SectionChange {
+    /// `SECTION` or `SECTION/SUBSECTION`
+    path: String,
+    /// A rating on the section's scale, or `n/a`
+    rating: Option<String>,
+    comments: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OverallChange {
+    score: Option<f32>,
+    comments: Option<String>,
+}
+
+fn parse_path(path: &str) -> ApiResult<SectionPath> {
+    path.parse::<SectionPath>().map_err(ApiError::bad_request)
+}
+
+async fn openapi() -> Json<Value> {
+    Json(openapi_document())
+}
+
+async fn list_dmps(
+    State(state): State<ApiState>,
+    Query(params): Query<ListParams>,
+) -> ApiResult<Json<Vec<DataManagementPlan>>> {
+    let query = match params.q {
+        Some(ref expression) => parse_query(expression, &state.scales)
+            .map_err(|e| ApiError::bad_request(format!("Invalid search expression: {}", e)))?,
+        None => DmpQuery::new(),
+    };
+
+    Ok(Json(state.db_manager.find_dmps(&query).await?))
+}
+
+async fn create_dmp(
+    State(state): State<ApiState>,
+    Json(mut dmp): Json<DataManagementPlan>,
+) -> ApiResult<(StatusCode, Json<Value>)> {
+    dmp.id = None;
+    let id = state.db_manager.add_dmp(dmp).await?;
+
+    Ok((StatusCode::CREATED, Json(json!({ "id": id }))))
+}
+
+async fn get_dmp(
+    State(state): State<ApiState>,
+    Path((id_type, dmp_id)): Path<(String, String)>,
+) -> ApiResult<Json<DataManagementPlan>> {
+    state
+        .db_manager
+        .find_dmp_by_id(&dmp_id, &id_type)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(&id_type, &dmp_id))
+}
+
+async fn replace_dmp(
+    State(state): State<ApiState>,
+    Path((id_type, dmp_id)): Path<(String, String)>,
+    Json(dmp): Json<DataManagementPlan>,
+) -> ApiResult<StatusCode> {
+    state.db_manager.replace_dmp(&dmp_id, &id_type, dmp).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn update_ratings(
+    State(state): State<ApiState>,
+    Path((id_type, dmp_id)): Path<(String, String)>,
+    Json(body): Json<RatingUpdate>,
+) -> ApiResult<Json<Value>> {
+    let mut update = DmpUpdate::new(&body.reviewer);
+
+    for change in &body.sections {
+        let path = parse_path(&change.path)?;
+        let rating = match change.rating {
+            Some(ref rating) => Some(
+                state
+                    .scales
+                    .scale_for(&path.section)
+                    .parse_rating(rating)
+                    .map_err(ApiError::bad_request)?,
+            ),
+            None => None,
+        };
+        let comments = change.comments.as_deref();
+        update = match path.subsection {
+            Some(ref subsection) => update.subsection(&path.section, subsection, rating, comments),
+            None => update.section(&path.section, rating, comments),
+        };
+    }
+    if let Some(ref overall) = body.overall {
+        update = update.overall(overall.score, overall.comments.as_deref());
+    }
+
+    let modified = state
+        .db_manager
+        .apply_update(&dmp_id, &id_type, &update, body.read_at)
+        .await?;
+
+    Ok(Json(json!({ "modified": modified })))
+}
+
+async fn delete_dmp(
+    State(state): State<ApiState>,
+    Path((id_type, dmp_id)): Path<(String, String)>,
+) -> ApiResult<StatusCode> {
+    if state.db_manager.delete_dmp(&dmp_id, &id_type).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found(&id_type, &dmp_id))
+    }
+}
+
+async fn search_section_tag(
+    State(state): State<ApiState>,
+    Query(params): Query<SectionTagParams>,
+) -> ApiResult<Json<Vec<DataManagementPlan>>> {
+    let path = parse_path(&params.path)?;
+    let mode = match params.mode {
+        Some(ref mode) => mode.parse::<MatchMode>().map_err(ApiError::bad_request)?,
+        None => MatchMode::default(),
+    };
+
+    Ok(Json(
+        state
+            .db_manager
+            .find_dmps_by_section_tag(&path, &params.tag, mode)
+            .await?,
+    ))
+}
+
+async fn search_rating(
+    State(state): State<ApiState>,
+    Query(params): Query<RatingParams>,
+) -> ApiResult<Json<Vec<DataManagementPlan>>> {
+    let path = parse_path(&params.path)?;
+    let min_rating = state
+        .scales
+        .scale_for(&path.section)
+        .parse_rating(&params.min)
+        .map_err(ApiError::bad_request)?;
+    if min_rating.value().is_none() {
+        return Err(ApiError::bad_request(
+            "The minimum rating must be a value on the section's scale",
+        ));
+    }
+
+    Ok(Json(
+        state
+            .db_manager
+            .find_dmps_by_rating(&path, min_rating)
+            .await?,
+    ))
+}
+
+async fn search_related(
+    State(state): State<ApiState>,
+    Query(params): Query<RelatedParams>,
+) -> ApiResult<Json<Vec<DataManagementPlan>>> {
+    if !ENTITY_TYPES.contains(&params.entity.as_str()) {
+        return Err(ApiError::bad_request(format!(
+            "'{}' is not a related entity type; expected one of: {}",
+            params.entity,
+            ENTITY_TYPES.join(", ")
+        )));
+    }
+    let relation = match params.relation {
+        Some(ref relation) => Some(
+            relation
+                .parse::<RelationType>()
+                .map_err(ApiError::bad_request)?,
+        ),
+        None => None,
+    };
+
+    Ok(Json(
+        state
+            .db_manager
+            .find_dmps_by_related_entity(&params.entity, &params.id, relation)
+            .await?,
+    ))
+}
+
+async fn export_dmps(State(state): State<ApiState>) -> ApiResult<Json<Vec<DataManagementPlan>>> {
+    Ok(Json(state.db_manager.all_dmps().await?))
+}
+
+async fn import_dmps(
+    State(state): State<ApiState>,
+    Json(dmps): Json<Vec<DataManagementPlan>>,
+) -> ApiResult<Json<Value>> {
+    let imported = state.db_manager.import_dmps(dmps).await?;
+
+    Ok(Json(json!({ "imported": imported })))
+}
+
+fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/openapi.json", get(openapi))
+        .route("/dmps", get(list_dmps).post(create_dmp))
+        // Identifiers such as DOIs contain slashes, so the identifier is the
+        // rest of the path
+        .route(
+            "/dmps/:id_type/*id",
+            get(get_dmp)
+                .put(replace_dmp)
+                .patch(update_ratings)
+                .delete(delete_dmp),
+        )
+        .route("/search/section-tag", get(search_section_tag))
+        .route("/search/rating", get(search_rating))
+        .route("/search/related", get(search_related))
+        .route("/export", get(export_dmps))
+        .route("/import", post(import_dmps))
+        .with_state(state)
+}
+
+/// Serves the JSON API and the rating form on `address` until the process
+/// is stopped.
+pub async fn serve(
+    db_manager: Arc<DatabaseManager>,
+    scales: RubricScales,
+    address: &str,
+) -> Result<(), Box<dyn Error>> {
+    let scales = Arc::new(scales);
+    let app = web::router(Arc::clone(&db_manager), Arc::clone(&scales))
+        .merge(router(ApiState { db_manager, scales }));
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    let address = listener.local_addr()?;
+    println!(
+        "Serving the API on http://{} (OpenAPI document at /openapi.json)",
+        address
+    );
+    println!("Rating form and DMP list at http://{}/ui", address);
+
+    axum::serve(listener, app).await?;
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use crate::DataManagementPlan;
+
+/// Number of characters kept on either side of the first match in a snippet.
+const SNIPPET_CONTEXT: usize = 40;
+
+/// A DMP returned by a full-text search, with its relevance score and the
+/// passages that matched.
+#[derive(Debug)]
+pub struct TextSearchHit {
+    pub dmp: DataManagementPlan,
+    pub score: f64,
+    pub snippets: Vec<Snippet>,
+}
+
+/// An excerpt of one searchable field around the matched terms.
+#[derive(Debug)]
+pub struct Snippet {
+    /// Human-readable name of the field the excerpt comes from
+    pub field: String,
+    pub text: String,
+    /// Character ranges of `text` that matched a search term
+    pub highlights: Vec<(usize, usize)>,
+}
+
+impl Snippet {
+    /// Returns the snippet text with every match wrapped in `open`/`close`.
+    pub fn highlighted(&self, open: &str, close: &str) -> String {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut result = String::new();
+        let mut position = 0;
+
+        for &(start, end) in &self.highlights {
+            result.extend(&chars[position..start]);
+            result.push_str(open);
+            result.extend(&chars[start..end]);
+            result.push_str(close);
+            position = end;
+        }
+        result.extend(&chars[position..]);
+
+        result
+    }
+}
+
+/// Extracts the positive terms of a MongoDB `$text` search string: quoted
+/// phrases are kept whole, negated terms (`-word`) are dropped.
+pub fn search_terms(search: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+
+    for (i, part) in search.split('"').enumerate() {
+        if i % 2 == 1 {
+            // Inside quotes
+            if !part.trim().is_empty() {
+                terms.push(part.trim().to_lowercase());
+            }
+        } else {
+            terms.extend(
+                part.split_whitespace()
+                    .filter(|word| !word.starts_with('-'))
+                    .map(|word| word.to_lowercase()),
+            );
+        }
+    }
+
+    terms
+}
+
+/// Finds every character range of `text` matching one of `terms`, ignoring case.
+fn find_matches(text: &[char], terms: &[String]) -> Vec<(usize, usize)> {
+    let lowered: Vec<char> = text
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let mut matches = Vec::new();
+
+    for term in terms {
+        let term: Vec<char> = term.chars().collect();
+        if term.is_empty() || term.len() > lowered.len() {
+            continue;
+        }
+        for start in 0..=(lowered.len() - term.len()) {
+            if lowered[start..start + term.len()] == term[..] {
+                matches.push((start, start + term.len()));
+            }
+        }
+    }
+
+    // Keep matches ordered and drop those overlapping an earlier one
+    matches.sort();
+    let mut result: Vec<(usize, usize)> = Vec::new();
+    for m in matches {
+        if result.last().is_none_or(|last| m.0 >= last.1) {
+            result.push(m);
+        }
+    }
+
+    result
+}
+
+/// Builds a snippet of `text` around its first match, or `None` if no term matches.
+fn snippet(field: String, text: &str, terms: &[String]) -> Option<Snippet> {
+    let chars: Vec<char> = text.chars().collect();
+    let matches = find_matches(&chars, terms);
+    let first = matches.first()?;
+
+    let start = first.0.saturating_sub(SNIPPET_CONTEXT);
+    let end = (first.1 + SNIPPET_CONTEXT).min(chars.len());
+
+    let prefix = if start > 0 { "..." } else { "" };
+    let suffix = if end < chars.len() { "..." } else { "" };
+    let offset = prefix.chars().count();
+
+    let highlights = matches
+        .iter()
+        .filter(|m| m.0 >= start && m.1 <= end)
+        .map(|m| (m.0 - start + offset, m.1 - start + offset))
+        .collect();
+
+    Some(Snippet {
+        field,
+        text: format!(
+            "{}{}{}",
+            prefix,
+            chars[start..end].iter().collect::<String>(),
+            suffix
+        ),
+        highlights,
+    })
+}
+
+/// Collects the snippets of every searchable field of `dmp` matching `terms`:
+/// the title, section and subsection text and comments, the overall rating
+/// comments and related DMP notes.
+pub fn snippets_for(dmp: &DataManagementPlan, terms: &[String]) -> Vec<Snippet> {
+    let mut snippets = Vec::new();
+
+    snippets.extend(snippet("Title".to_string(), &dmp.title, terms));
+
+    for section in &dmp.sections {
+        if let Some(ref text) = section.text {
+            snippets.extend(snippet(format!("{} text", section.title), text, terms));
+        }
+        if let Some(ref comments) = section.comments {
+            snippets.extend(snippet(
+                format!("{} comments", section.title),
+                comments,
+                terms,
+            ));
+        }
+        for subsection in &section.subsections {
+            if let Some(ref text) = subsection.text {
+                snippets.extend(snippet(
+                    format!("{} / {} text", section.title, subsection.title),
+                    text,
+                    terms,
+                ));
+            }
+            if let Some(ref comments) = subsection.comments {
+                snippets.extend(snippet(
+                    format!("{} / {} comments", section.title, subsection.title),
+                    comments,
+                    terms,
+                ));
+            }
+        }
+    }
+
+    if let Some(ref overall_rating) = dmp.overall_rating {
+        snippets.extend(snippet(
+            "Overall comments".to_string(),
+            &overall_rating.comments,
+            terms,
+        ));
+    }
+
+    if let Some(ref related_dmps) = dmp.related_dmps {
+        for related in related_dmps {
+            if let Some(ref notes) = related.relationship_notes {
+                snippets.extend(snippet(
+                    format!("Relationship notes for {}", related.title),
+                    notes,
+                    terms,
+                ));
+            }
+        }
+    }
+
+    snippets
+}
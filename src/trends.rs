@@ -0,0 +1,389 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+use mongodb::bson::{doc, Bson, Document};
+
+use crate::analytics::{bar, count, number, rating_fraction};
+
+/// The length of the periods a trend is bucketed into.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Period {
+    Month,
+    #[default]
+    Quarter,
+    Year,
+}
+
+impl Period {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Period::Month => "month",
+            Period::Quarter => "quarter",
+            Period::Year => "year",
+        }
+    }
+
+    /// The period holding `month` (1-12) of `year`, as the year and the
+    /// number of the month or quarter in it (0 for whole years).
+    fn key(&self, year: i32, month: u32) -> (i32, u32) {
+        match self {
+            Period::Month => (year, month),
+            Period::Quarter => (year, (month - 1) / 3 + 1),
+            Period::Year => (year, 0),
+        }
+    }
+
+    /// The period after `key`.
+    fn next(&self, (year, index): (i32, u32)) -> (i32, u32) {
+        let last = match self {
+            Period::Month => 12,
+            Period::Quarter => 4,
+            Period::Year => 0,
+        };
+        if index >= last {
+            (year + 1, last.min(1))
+        } else {
+            (year, index + 1)
+        }
+    }
+
+    fn label(&self, (year, index): (i32, u32)) -> String {
+        match self {
+            Period::Month => format!("{}-{:02}", year, index),
+            Period::Quarter => format!("{}-Q{}", year, index),
+            Period::Year => year.to_string(),
+        }
+    }
+}
+
+impl std::str::FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "month" | "monthly" => Ok(Period::Month),
+            "quarter" | "quarterly" => Ok(Period::Quarter),
+            "year" | "yearly" => Ok(Period::Year),
+            _ => Err(format!(
+                "'{}' is not a valid period; expected month, quarter or year",
+                s
+            )),
+        }
+    }
+}
+
+/// The date DMPs are placed in time by.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TrendDate {
+    /// When the DMP was added
+    #[default]
+    Created,
+    /// When its overall rating was last reviewed
+    Reviewed,
+}
+
+impl TrendDate {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrendDate::Created => "created",
+            TrendDate::Reviewed => "reviewed",
+        }
+    }
+
+    fn field(&self) -> &'static str {
+        match self {
+            TrendDate::Created => "created_date",
+            TrendDate::Reviewed => "overall_rating.review_date",
+        }
+    }
+}
+
+impl std::str::FromStr for TrendDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "created" => Ok(TrendDate::Created),
+            "reviewed" => Ok(TrendDate::Reviewed),
+            _ => Err(format!(
+                "'{}' is not a valid date; expected created or reviewed",
+                s
+            )),
+        }
+    }
+}
+
+/// A running total for computing a mean.
+#[derive(Debug, Clone, Copy, Default)]
+struct Sum {
+    count: u64,
+    total: f64,
+}
+
+impl Sum {
+    fn add(&mut self, other: Sum) {
+        self.count += other.count;
+        self.total += other.total;
+    }
+
+    fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.total / self.count as f64)
+    }
+}
+
+/// The DMPs of one period.
+#[derive(Debug)]
+pub struct TrendPoint {
+    pub period: String,
+    pub dmps: u64,
+    pub mean_score: Option<f64>,
+    /// Mean rating of each section as a fraction of its scale, by section
+    /// title
+    pub sections: BTreeMap<String, Option<f64>>,
+}
+
+/// Overall score and section rating means over time.
+#[derive(Debug)]
+pub struct Trends {
+    pub period: Period,
+    pub date: TrendDate,
+    /// Every period from the first to the last holding a DMP
+    pub points: Vec<TrendPoint>,
+    /// Every section title rated in any period
+    pub sections: Vec<String>,
+}
+
+/// The year and month of a result grouped by `overall_pipeline` or
+/// `section_pipeline`.
+fn year_and_month(id: &Document) -> Option<(i32, u32)> {
+    let year = number(id, "year")? as i32;
+    let month = number(id, "month")? as u32;
+    (1..=12).contains(&month).then_some((year, month))
+}
+
+/// Totals the overall scores of the DMPs matching `filter` by the year and
+/// month of `date`.
+pub fn overall_pipeline(filter: Document, date: TrendDate) -> Vec<Document> {
+    let field = format!("${}", date.field());
+
+    vec![
+        doc! { "$match": filter },
+        doc! { "$match": { date.field(): { "$type": "date" } } },
+        doc! {
+            "$group": {
+                "_id": { "year": { "$year": &field }, "month": { "$month": &field } },
+                "dmps": { "$sum": 1 },
+                "scored": {
+                    "$sum": { "$cond": [{ "$isNumber": "$overall_rating.score" }, 1, 0] }
+                },
+                "score": { "$sum": "$overall_rating.score" }
+            }
+        },
+    ]
+}
+
+/// Totals the section ratings, as fractions of their scale, of the DMPs
+/// matching `filter` by section and the year and month of `date`.
+pub fn section_pipeline(filter: Document, date: TrendDate) -> Vec<Document> {
+    let field = format!("${}", date.field());
+
+    vec![
+        doc! { "$match": filter },
+        doc! { "$match": { date.field(): { "$type": "date" } } },
+        doc! { "$unwind": "$sections" },
+        doc! {
+            "$project": {
+                "date": &field,
+                "section": "$sections.title",
+                "rating": rating_fraction("$sections.rating")
+            }
+        },
+        doc! { "$match": { "rating": { "$ne": Bson::Null } } },
+        doc! {
+            "$group": {
+                "_id": {
+                    "year": { "$year": "$date" },
+                    "month": { "$month": "$date" },
+                    "section": "$section"
+                },
+                "rated": { "$sum": 1 },
+                "rating": { "$sum": "$rating" }
+            }
+        },
+    ]
+}
+
+impl Trends {
+    /// Buckets the results of `overall_pipeline` and `section_pipeline`
+    /// into periods.
+    pub fn from_results(
+        period: Period,
+        date: TrendDate,
+        overall: &[Document],
+        sections: &[Document],
+    ) -> Self {
+        let mut dmps: BTreeMap<(i32, u32), u64> = BTreeMap::new();
+        let mut scores: BTreeMap<(i32, u32), Sum> = BTreeMap::new();
+        let mut ratings: BTreeMap<((i32, u32), String), Sum> = BTreeMap::new();
+
+        for result in overall {
+            let key = match result.get_document("_id").ok().and_then(year_and_month) {
+                Some((year, month)) => period.key(year, month),
+                None => continue,
+            };
+            *dmps.entry(key).or_insert(0) += count(result, "dmps");
+            scores.entry(key).or_default().add(Sum {
+                count: count(result, "scored"),
+                total: number(result, "score").unwrap_or(0.0),
+            });
+        }
+
+        for result in sections {
+            let id = match result.get_document("_id") {
+                Ok(id) => id,
+                Err(_) => continue,
+            };
+            let (key, section) = match (year_and_month(id), id.get_str("section")) {
+                (Some((year, month)), Ok(section)) => {
+                    (period.key(year, month), section.to_string())
+                }
+                _ => continue,
+            };
+            ratings.entry((key, section)).or_default().add(Sum {
+                count: count(result, "rated"),
+                total: number(result, "rating").unwrap_or(0.0),
+            });
+        }
+
+        let mut section_titles: Vec<String> =
+            ratings.keys().map(|(_, section)| section.clone()).collect();
+        section_titles.sort();
+        section_titles.dedup();
+
+        let mut points = Vec::new();
+        if let (Some(first), Some(last)) = (dmps.keys().next(), dmps.keys().next_back()) {
+            let mut key = *first;
+            while key <= *last {
+                points.push(TrendPoint {
+                    period: period.label(key),
+                    dmps: dmps.get(&key).copied().unwrap_or(0),
+                    mean_score: scores.get(&key).and_then(Sum::mean),
+                    sections: section_titles
+                        .iter()
+                        .map(|section| {
+                            let mean = ratings.get(&(key, section.clone())).and_then(Sum::mean);
+                            (section.clone(), mean)
+                        })
+                        .collect(),
+                });
+                key = period.next(key);
+            }
+        }
+
+        Self {
+            period,
+            date,
+            points,
+            sections: section_titles,
+        }
+    }
+
+    /// One row per period: the number of DMPs, their mean overall score and
+    /// the mean rating of each section as a fraction of its scale.
+    pub fn to_csv(&self) -> String {
+        let mut header = vec![
+            self.period.as_str().to_string(),
+            "dmps".to_string(),
+            "mean_overall_score".to_string(),
+        ];
+        header.extend(self.sections.iter().map(|s| csv_field(s)));
+        let mut csv = header.join(",");
+        csv.push('\n');
+
+        for point in &self.points {
+            let mut row = vec![
+                point.period.clone(),
+                point.dmps.to_string(),
+                point
+                    .mean_score
+                    .map_or(String::new(), |s| format!("{:.3}", s)),
+            ];
+            row.extend(self.sections.iter().map(|section| {
+                point
+                    .sections
+                    .get(section)
+                    .copied()
+                    .flatten()
+                    .map_or(String::new(), |r| format!("{:.3}", r))
+            }));
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+
+        csv
+    }
+
+    /// Writes `to_csv` to `file_path`.
+    pub fn write_csv(&self, file_path: &str) -> io::Result<()> {
+        fs::write(file_path, self.to_csv())
+    }
+
+    /// Bar charts of the mean overall score and the mean rating of each
+    /// section per period.
+    pub fn to_chart(&self) -> String {
+        if self.points.is_empty() {
+            return format!("No DMPs have a {} date\n", self.date.as_str());
+        }
+
+        let mut text = format!(
+            "Mean overall score by {} {}\n",
+            self.period.as_str(),
+            self.date.as_str()
+        );
+        for point in &self.points {
+            text.push_str(&chart_line(
+                &point.period,
+                point.mean_score.map(|s| format!("{:.2}", s)),
+                point.mean_score.map_or(0.0, |s| s / 5.0),
+                &format!("({} DMPs)", point.dmps),
+            ));
+        }
+
+        for section in &self.sections {
+            text.push_str(&format!("\n{}: mean rating, % of the scale\n", section));
+            for point in &self.points {
+                let mean = point.sections.get(section).copied().flatten();
+                text.push_str(&chart_line(
+                    &point.period,
+                    mean.map(|r| format!("{:.0}%", r * 100.0)),
+                    mean.unwrap_or(0.0),
+                    "",
+                ));
+            }
+        }
+
+        text
+    }
+}
+
+/// One bar of a chart: `fraction` of the full width, labelled with `value`.
+fn chart_line(period: &str, value: Option<String>, fraction: f64, note: &str) -> String {
+    let line = format!(
+        "{:<8} {:>5} {:<40} {}",
+        period,
+        value.unwrap_or_else(|| "-".to_string()),
+        bar(fraction, 1.0, 40),
+        note
+    );
+    format!("{}\n", line.trim_end())
+}
+
+/// Quotes a CSV field if it holds a comma, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
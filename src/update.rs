@@ -0,0 +1,273 @@
+use mongodb::bson::{self, doc, DateTime, Document};
+
+use crate::conflict::MergeField;
+use crate::query::SectionPath;
+use crate::{DataManagementPlan, Rating, VersionHistory};
+
+/// A new rating and/or comments for a section or subsection.
+#[derive(Debug, Clone)]
+struct RatingChange {
+    path: SectionPath,
+    rating: Option<Rating>,
+    comments: Option<String>,
+}
+
+/// A new overall score and/or comments.
+#[derive(Debug, Clone)]
+struct OverallChange {
+    score: Option<f32>,
+    comments: Option<String>,
+}
+
+/// Changes to the ratings of one DMP, written together in a single update
+/// that records one history entry.
+#[derive(Debug, Clone)]
+pub struct DmpUpdate {
+    reviewer: String,
+    ratings: Vec<RatingChange>,
+    overall: Option<OverallChange>,
+}
+
+impl DmpUpdate {
+    pub fn new(reviewer: &str) -> Self {
+        Self {
+            reviewer: reviewer.trim().to_string(),
+            ratings: Vec::new(),
+            overall: None,
+        }
+    }
+
+    pub fn section(mut self, title: &str, rating: Option<Rating>, comments: Option<&str>) -> Self {
+        self.ratings.push(RatingChange {
+            path: SectionPath::section(title),
+            rating,
+            comments: comments.map(str::to_string),
+        });
+        self
+    }
+
+    pub fn subsection(
+        mut self,
+        section: &str,
+        title: &str,
+        rating: Option<Rating>,
+        comments: Option<&str>,
+    ) -> Self {
+        self.ratings.push(RatingChange {
+            path: SectionPath::subsection(section, title),
+            rating,
+            comments: comments.map(str::to_string),
+        });
+        self
+    }
+
+    pub fn overall(mut self, score: Option<f32>, comments: Option<&str>) -> Self {
+        self.overall = Some(OverallChange {
+            score,
+            comments: comments.map(str::to_string),
+        });
+        self
+    }
+
+    /// Returns `true` if the update would not change anything.
+    pub fn is_empty(&self) -> bool {
+        self.ratings
+            .iter()
+            .all(|c| c.rating.is_none() && c.comments.is_none())
+            && self
+                .overall
+                .as_ref()
+                .is_none_or(|o| o.score.is_none() && o.comments.is_none())
+    }
+
+    /// One line per change, as recorded in the history entry.
+    pub fn summary(&self, dmp: &DataManagementPlan) -> Result<String, String> {
+        let mut lines = Vec::new();
+
+        for change in &self.ratings {
+            let (section, subsection) = change.path.position(dmp)?;
+            let mut title = dmp.sections[section].title.clone();
+            if let Some(subsection) = subsection {
+                title.push_str(&format!(
+                    " / {}",
+                    dmp.sections[section].subsections[subsection].title
+                ));
+            }
+            let mut parts = Vec::new();
+            if let Some(rating) = change.rating {
+                parts.push(format!("rating {}", rating));
+            }
+            if change.comments.is_some() {
+                parts.push("comments".to_string());
+            }
+            if !parts.is_empty() {
+                lines.push(format!("{}: {}", title, parts.join(", ")));
+            }
+        }
+
+        if let Some(ref overall) = self.overall {
+            if let Some(score) = overall.score {
+                lines.push(format!("Overall score {}", score));
+            }
+            if overall.comments.is_some() {
+                lines.push("Overall comments".to_string());
+            }
+        }
+
+        Ok(lines.join("; "))
+    }
+
+    /// Builds the MongoDB update applying every change to `dmp`. Fails if a
+    /// section or subsection does not exist or the overall score is out of
+    /// range, so that either every change is written or none.
+    pub fn to_update(&self, dmp: &DataManagementPlan) -> Result<Document, String> {
+        if self.reviewer.is_empty() {
+            return Err("A reviewer is required".to_string());
+        }
+        if self.is_empty() {
+            return Err("The update does not change anything".to_string());
+        }
+
+        let now = DateTime::now();
+        let mut set = doc! { "last_modified": now };
+
+        for change in &self.ratings {
+            let (section, subsection) = change.path.position(dmp)?;
+            let path = match subsection {
+                Some(subsection) => format!("sections.{}.subsections.{}", section, subsection),
+                None => format!("sections.{}", section),
+            };
+            if let Some(rating) = change.rating {
+                let rating = bson::to_bson(&rating).map_err(|e| e.to_string())?;
+                set.insert(format!("{}.rating", path), rating);
+            }
+            if let Some(ref comments) = change.comments {
+                set.insert(format!("{}.comments", path), comments.trim());
+            }
+        }
+
+        if let Some(ref overall) = self.overall {
+            if let Some(score) = overall.score {
+                if !(1.0..=5.0).contains(&score) {
+                    return Err(format!(
+                        "Overall score must be between 1 and 5, not {}",
+                        score
+                    ));
+                }
+            }
+            match (dmp.overall_rating.is_some(), overall.score) {
+                (true, score) => {
+                    if let Some(score) = score {
+                        set.insert("overall_rating.score", score as f64);
+                    }
+                    if let Some(ref comments) = overall.comments {
+                        set.insert("overall_rating.comments", comments.trim());
+                    }
+                    set.insert("overall_rating.reviewer", &self.reviewer);
+                    set.insert("overall_rating.review_date", now);
+                }
+                (false, Some(score)) => {
+                    set.insert(
+                        "overall_rating",
+                        doc! {
+                            "score": score as f64,
+                            "reviewer": &self.reviewer,
+                            "review_date": now,
+                            "comments": overall.comments.as_deref().unwrap_or("").trim(),
+                            "overall_tags": []
+                        },
+                    );
+                }
+                (false, None) => {
+                    return Err("The DMP has no overall rating yet; a score is required".to_string())
+                }
+            }
+        }
+
+        let entry = VersionHistory {
+            version: dmp.version.clone(),
+            date: now,
+            reviewer: self.reviewer.clone(),
+            changes: self.summary(dmp)?,
+        };
+        let entry = bson::to_bson(&entry).map_err(|e| e.to_string())?;
+
+        // DMPs stored without history hold null, which $push cannot append to
+        if dmp.history.is_some() {
+            Ok(doc! { "$set": set, "$push": { "history": entry } })
+        } else {
+            set.insert("history", vec![entry]);
+            Ok(doc! { "$set": set })
+        }
+    }
+
+    /// The values each change touches in `base`, as read, and in `current`,
+    /// as stored now, for showing a conflict.
+    pub fn merge_fields(
+        &self,
+        base: &DataManagementPlan,
+        current: &DataManagementPlan,
+    ) -> Vec<MergeField> {
+        let show_rating =
+            |rating: Option<Rating>| rating.map_or("Not rated".to_string(), |r| r.to_string());
+        let missing = "(removed)".to_string();
+        let mut fields = Vec::new();
+
+        for change in &self.ratings {
+            let read = change.path.rating_and_comments(base);
+            let stored = change.path.rating_and_comments(current);
+            let name = change.path.to_string();
+            if let Some(rating) = change.rating {
+                fields.push(MergeField {
+                    name: format!("{}: rating", name),
+                    base: read
+                        .as_ref()
+                        .map_or(missing.clone(), |(r, _)| show_rating(*r)),
+                    current: stored
+                        .as_ref()
+                        .map_or(missing.clone(), |(r, _)| show_rating(*r)),
+                    mine: rating.to_string(),
+                });
+            }
+            if let Some(ref comments) = change.comments {
+                fields.push(MergeField {
+                    name: format!("{}: comments", name),
+                    base: read.map_or(missing.clone(), |(_, c)| c.unwrap_or_default()),
+                    current: stored.map_or(missing.clone(), |(_, c)| c.unwrap_or_default()),
+                    mine: comments.trim().to_string(),
+                });
+            }
+        }
+
+        if let Some(ref overall) = self.overall {
+            let score = |dmp: &DataManagementPlan| {
+                dmp.overall_rating
+                    .as_ref()
+                    .map_or("None".to_string(), |r| r.score.to_string())
+            };
+            let comments = |dmp: &DataManagementPlan| {
+                dmp.overall_rating
+                    .as_ref()
+                    .map_or(String::new(), |r| r.comments.clone())
+            };
+            if let Some(new_score) = overall.score {
+                fields.push(MergeField {
+                    name: "Overall score".to_string(),
+                    base: score(base),
+                    current: score(current),
+                    mine: new_score.to_string(),
+                });
+            }
+            if let Some(ref new_comments) = overall.comments {
+                fields.push(MergeField {
+                    name: "Overall comments".to_string(),
+                    base: comments(base),
+                    current: comments(current),
+                    mine: new_comments.trim().to_string(),
+                });
+            }
+        }
+
+        fields
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::HashSet;
+
+use crate::matching::{fold, matches, normalize_tag, MatchMode};
+use crate::{DataManagementPlan, VocabularyTag};
+
+/// The managed tag vocabulary: the tags reviewers may enter, the synonyms
+/// they are normalized from and the hierarchy searches expand along. While
+/// it has no tags, any tag may be entered.
+#[derive(Debug, Clone, Default)]
+pub struct TagVocabulary {
+    tags: Vec<VocabularyTag>,
+}
+
+/// The outcome of renaming a tag across the vocabulary and every DMP.
+#[derive(Debug, Default)]
+pub struct TagRename {
+    pub from: String,
+    pub to: String,
+    /// `true` if the new name was already a vocabulary tag
+    pub merged: bool,
+    /// Number of DMPs whose tags were rewritten
+    pub dmps: usize,
+    /// DMPs that changed while the rename ran and were left as they are
+    pub skipped: Vec<String>,
+}
+
+impl TagRename {
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "'{}' {} '{}' in {} DMP(s)\n",
+            self.from,
+            if self.merged {
+                "merged into"
+            } else {
+                "renamed to"
+            },
+            self.to,
+            self.dmps
+        );
+        if !self.skipped.is_empty() {
+            text.push_str(&format!(
+                "Changed during the rename and left as they are; rename again to include them: {}\n",
+                self.skipped.join(", ")
+            ));
+        }
+        text
+    }
+}
+
+impl TagVocabulary {
+    pub fn new(tags: Vec<VocabularyTag>) -> Self {
+        Self { tags }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// The vocabulary tag named `tag`, or listing it as a synonym.
+    pub fn find(&self, tag: &str) -> Option<&VocabularyTag> {
+        let folded = fold(tag);
+        self.tags
+            .iter()
+            .find(|t| fold(&t.name) == folded)
+            .or_else(|| {
+                self.tags
+                    .iter()
+                    .find(|t| t.synonyms.iter().any(|s| fold(s) == folded))
+            })
+    }
+
+    /// The vocabulary tag named `name`, spelled as stored, ignoring
+    /// synonyms.
+    pub fn named(&self, name: &str) -> Option<&VocabularyTag> {
+        let folded = fold(name);
+        self.tags.iter().find(|t| fold(&t.name) == folded)
+    }
+
+    /// `tag` in normalized form, or the tag it is a synonym of.
+    pub fn canonical(&self, tag: &str) -> String {
+        self.find(tag)
+            .map_or_else(|| normalize_tag(tag), |t| t.name.clone())
+    }
+
+    /// Resolves a tag entered in `section` (any section if `None`) to the
+    /// vocabulary tag it names. Fails if the vocabulary has tags and none of
+    /// them is `tag` or may be used in `section`.
+    pub fn resolve(&self, tag: &str, section: Option<&str>) -> Result<String, String> {
+        if self.is_empty() {
+            return Ok(normalize_tag(tag));
+        }
+
+        match self.find(tag) {
+            Some(found) if allowed_in(found, section) => Ok(found.name.clone()),
+            Some(found) => Err(format!(
+                "'{}' is not used in {}; it is used in: {}",
+                found.name,
+                section.unwrap_or("this section"),
+                found.sections.join(", ")
+            )),
+            None => {
+                let suggestions: Vec<&str> = self
+                    .tags
+                    .iter()
+                    .filter(|t| allowed_in(t, section) && matches(&t.name, tag, MatchMode::Fuzzy))
+                    .map(|t| t.name.as_str())
+                    .collect();
+                if suggestions.is_empty() {
+                    Err(format!(
+                        "'{}' is not in the tag vocabulary",
+                        normalize_tag(tag)
+                    ))
+                } else {
+                    Err(format!(
+                        "'{}' is not in the tag vocabulary; did you mean: {}?",
+                        normalize_tag(tag),
+                        suggestions.join(", ")
+                    ))
+                }
+            }
+        }
+    }
+
+    /// The tags usable in `section` (any section if `None`) whose name or a
+    /// synonym starts with `prefix`, sorted.
+    pub fn complete(&self, prefix: &str, section: Option<&str>) -> Vec<String> {
+        let mut completions: Vec<String> = self
+            .tags
+            .iter()
+            .filter(|t| allowed_in(t, section))
+            .filter(|t| {
+                matches(&t.name, prefix, MatchMode::Prefix)
+                    || t.synonyms
+                        .iter()
+                        .any(|s| matches(s, prefix, MatchMode::Prefix))
+            })
+            .map(|t| t.name.clone())
+            .collect();
+        completions.sort();
+        completions
+    }
+
+    /// The tags directly below `tag` in the hierarchy.
+    pub fn children(&self, tag: &str) -> Vec<&VocabularyTag> {
+        let mut children: Vec<&VocabularyTag> = self
+            .tags
+            .iter()
+            .filter(|t| t.parent.as_deref().is_some_and(|p| fold(p) == fold(tag)))
+            .collect();
+        children.sort_by(|a, b| a.name.cmp(&b.name));
+        children
+    }
+
+    /// The tags at the top of the hierarchy, including those whose parent
+    /// is not in the vocabulary.
+    pub fn roots(&self) -> Vec<&VocabularyTag> {
+        let mut roots: Vec<&VocabularyTag> = self
+            .tags
+            .iter()
+            .filter(|t| t.parent.as_deref().is_none_or(|p| self.find(p).is_none()))
+            .collect();
+        roots.sort_by(|a, b| a.name.cmp(&b.name));
+        roots
+    }
+
+    /// `tag`, resolved from a synonym, and every tag below it in the
+    /// hierarchy, for searches.
+    pub fn expand(&self, tag: &str) -> Vec<String> {
+        let mut expanded = vec![self.canonical(tag)];
+        let mut i = 0;
+
+        while i < expanded.len() {
+            for child in self.children(&expanded[i].clone()) {
+                if !expanded.contains(&child.name) {
+                    expanded.push(child.name.clone());
+                }
+            }
+            i += 1;
+        }
+
+        expanded
+    }
+
+    /// Checks `tag` before it is added to the vocabulary, or replaces the
+    /// tag of the same name: its parent must exist and not be the tag or
+    /// below it, and its synonyms must not name another tag.
+    pub fn validate(&self, tag: &VocabularyTag) -> Result<(), String> {
+        if tag.name.is_empty() {
+            return Err("A tag name is required".to_string());
+        }
+
+        if let Some(ref parent) = tag.parent {
+            if self.find(parent).is_none() {
+                return Err(format!("'{}' is not in the tag vocabulary", parent));
+            }
+            if self
+                .expand(&tag.name)
+                .iter()
+                .any(|t| fold(t) == fold(parent))
+            {
+                return Err(format!(
+                    "'{}' cannot be placed below '{}', which is below it",
+                    tag.name, parent
+                ));
+            }
+        }
+
+        for name in std::iter::once(&tag.name).chain(&tag.synonyms) {
+            if let Some(other) = self.find(name).filter(|t| fold(&t.name) != fold(&tag.name)) {
+                return Err(format!("'{}' already names the tag '{}'", name, other.name));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renames the vocabulary tag `from` to `to`, merging it into the tag `to`
+    /// if there is one: the old name becomes a synonym and the tags below it
+    /// move along. Returns the tags to store in place of those of the same name;
+    /// `from` itself, as `named` spells it, is to be deleted. Returns nothing
+    /// if `from` is not a vocabulary tag.
+    pub fn rename(&self, from: &str, to: &str) -> Vec<VocabularyTag> {
+        let tags = &self.tags;
+        let source = match self.named(from) {
+            Some(source) => source,
+            None => return Vec::new(),
+        };
+
+        let mut renamed = match self.named(to) {
+            Some(target) => {
+                let mut merged = target.clone();
+                // The merged tag may be used wherever either tag could
+                if source.sections.is_empty() {
+                    merged.sections.clear();
+                } else if !merged.sections.is_empty() {
+                    for section in &source.sections {
+                        if !merged.sections.contains(section) {
+                            merged.sections.push(section.clone());
+                        }
+                    }
+                }
+                merged.synonyms.extend(source.synonyms.iter().cloned());
+                merged
+            }
+            None => VocabularyTag {
+                id: None,
+                name: to.to_string(),
+                ..source.clone()
+            },
+        };
+        renamed.synonyms.push(source.name.clone());
+        let mut seen = HashSet::new();
+        renamed
+            .synonyms
+            .retain(|s| fold(s) != fold(to) && seen.insert(fold(s)));
+        if renamed
+            .parent
+            .as_deref()
+            .is_some_and(|p| fold(p) == fold(from))
+        {
+            renamed.parent = source.parent.clone();
+        }
+
+        let mut changed = vec![renamed];
+        for child in tags.iter().filter(|t| {
+            fold(&t.name) != fold(to) && t.parent.as_deref().is_some_and(|p| fold(p) == fold(from))
+        }) {
+            changed.push(VocabularyTag {
+                parent: Some(to.to_string()),
+                ..child.clone()
+            });
+        }
+
+        changed
+    }
+}
+
+/// Returns `true` if `tag` may be used in `section`.
+fn allowed_in(tag: &VocabularyTag, section: Option<&str>) -> bool {
+    match section {
+        Some(section) if !tag.sections.is_empty() => {
+            tag.sections.iter().any(|s| fold(s) == fold(section))
+        }
+        _ => true,
+    }
+}
+
+/// Calls `f` on every tag list of `dmp`: those of its sections,
+/// subsections, annotations and overall rating.
+fn for_each_tag_list(dmp: &mut DataManagementPlan, mut f: impl FnMut(&mut Vec<String>)) {
+    for section in &mut dmp.sections {
+        f(&mut section.tags);
+        for subsection in &mut section.subsections {
+            f(&mut subsection.tags);
+        }
+        for annotation in &mut section.annotations {
+            f(&mut annotation.tags);
+        }
+    }
+
+    if let Some(ref mut overall_rating) = dmp.overall_rating {
+        f(&mut overall_rating.overall_tags);
+    }
+}
+
+/// Replaces synonyms among the tags of `dmp` with the tags they stand for,
+/// e.g. before storing an imported DMP.
+pub fn canonicalize_dmp_tags(dmp: &mut DataManagementPlan, vocabulary: &TagVocabulary) {
+    if vocabulary.is_empty() {
+        return;
+    }
+
+    for_each_tag_list(dmp, |tags| {
+        let mut canonical: Vec<String> = Vec::new();
+        for tag in tags.iter() {
+            let tag = vocabulary.canonical(tag);
+            if !canonical.contains(&tag) {
+                canonical.push(tag);
+            }
+        }
+        *tags = canonical;
+    });
+}
+
+/// Replaces the tag `from` with `to` everywhere in `dmp`, dropping `from`
+/// where `to` is already present. Returns `true` if anything changed.
+pub fn rename_dmp_tag(dmp: &mut DataManagementPlan, from: &str, to: &str) -> bool {
+    let mut changed = false;
+
+    for_each_tag_list(dmp, |tags| {
+        if let Some(position) = tags.iter().position(|t| fold(t) == fold(from)) {
+            if tags.iter().any(|t| fold(t) == fold(to)) {
+                tags.remove(position);
+            } else {
+                tags[position] = to.to_string();
+            }
+            changed = true;
+        }
+    });
+
+    changed
+}
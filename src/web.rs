@@ -0,0 +1,854 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::Arc;
+
+use axum::extract::{Form, Path, Query, Request, State};
+use axum::http::{header, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::Deserialize;
+
+use crate::db::DatabaseManager;
+use crate::identifiers::{normalize_author_identifier, normalize_identifier};
+use crate::migrations::SCHEMA_VERSION;
+use crate::query::DmpQuery;
+use crate::query_parser::parse_query;
+use crate::report::{escape_html, render_dmp_report};
+use crate::scales::RubricScales;
+use crate::server::error_status;
+use crate::vocabulary::TagVocabulary;
+use crate::{
+    Author, AuthorIdType, DataManagementPlan, Identifier, IdentifierType, OverallRating,
+    ProjectInfo, Rating, RatingDraft, RatingScale, Section, Subsection, RUBRIC_SECTIONS,
+};
+
+const WEB_STYLE: &str = "body { font-family: sans-serif; max-width: 60em; margin: 2em auto; }
+nav { margin-bottom: 1.5em; }
+.meta { color: #555; }
+.errors { color: #a00; border: 1px solid #a00; padding: 0.5em 1em; }
+.notice { color: #060; }
+fieldset { margin-bottom: 1.5em; }
+fieldset fieldset { margin: 1em 0 0 1em; }
+label { display: block; margin-top: 0.6em; }
+input[type=text], textarea { width: 100%; box-sizing: border-box; }
+textarea { height: 5em; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.3em 0.6em; border-bottom: 1px solid #ddd; }
+form.inline { display: inline; }";
+
+/// Identifier types offered for the DMP identifier.
+const ID_TYPES: [&str; 5] = ["doi", "handle", "ark", "url", "other"];
+
+/// Identifier types offered for author identifiers.
+const AUTHOR_ID_TYPES: [&str; 4] = ["orcid", "isni", "openid", "other"];
+
+/// Authors, and subsections per section, a form can hold.
+const MAX_ROWS: usize = 100;
+
+/// What the page handlers share.
+#[derive(Clone)]
+struct WebState {
+    db_manager: Arc<DatabaseManager>,
+    scales: Arc<RubricScales>,
+}
+
+/// A page reporting that a request failed.
+struct WebError {
+    status: StatusCode,
+    message: String,
+}
+
+impl From<Box<dyn Error>> for WebError {
+    fn from(e: Box<dyn Error>) -> Self {
+        Self {
+            status: error_status(e.as_ref()),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for WebError {
+    fn into_response(self) -> Response {
+        let body = format!(
+            "<h1>{}</h1>\n<p class=\"errors\">{}</p>\n",
+            self.status,
+            escape_html(&self.message)
+        );
+        (self.status, page("Error", &body)).into_response()
+    }
+}
+
+type WebResult<T> = Result<T, WebError>;
+
+#[derive(Deserialize)]
+struct IndexParams {
+    /// Search expression, as taken by the `--query` options
+    q: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FormParams {
+    draft: Option<String>,
+    saved: Option<String>,
+}
+
+/// Wraps `body` in a page with links to the DMP list and a new rating form.
+fn page(title: &str, body: &str) -> Html<String> {
+    Html(format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<nav><a href=\"/ui\">DMPs and drafts</a> &middot; <a href=\"/ui/form\">Rate a DMP</a></nav>\n{}</body>\n</html>\n",
+        escape_html(title),
+        WEB_STYLE,
+        body
+    ))
+}
+
+/// Percent-encodes `text` for a URL path, keeping slashes.
+fn encode_path(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// The page of the stored DMP `identifier`.
+fn dmp_link(identifier: &Identifier) -> String {
+    format!(
+        "/ui/dmps/{}/{}",
+        encode_path(identifier.id_type.as_str()),
+        encode_path(&identifier.identifier)
+    )
+}
+
+/// `date` as YYYY-MM-DD HH:MM (UTC).
+fn show_time(date: DateTime) -> String {
+    date.try_to_rfc3339_string()
+        .map(|d| d.chars().take(16).collect::<String>().replace('T', " "))
+        .unwrap_or_default()
+}
+
+/// The trimmed value of the field `name`, or "" if it was left empty.
+fn field<'a>(fields: &'a BTreeMap<String, String>, name: &str) -> &'a str {
+    fields.get(name).map_or("", |v| v.trim())
+}
+
+fn optional_field(fields: &BTreeMap<String, String>, name: &str) -> Option<String> {
+    Some(field(fields, name))
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// The numbers `n` of the fields named `{prefix}{n}_...`, in order. Rows
+/// numbered `MAX_ROWS` or higher are dropped.
+fn row_numbers(fields: &BTreeMap<String, String>, prefix: &str) -> Vec<usize> {
+    let mut numbers: Vec<usize> = fields
+        .keys()
+        .filter_map(|key| key.strip_prefix(prefix))
+        .filter_map(|rest| rest.split_once('_'))
+        .filter_map(|(number, _)| number.parse().ok())
+        .filter(|n| *n < MAX_ROWS)
+        .collect();
+    numbers.sort();
+    numbers.dedup();
+    numbers
+}
+
+/// The value a rating is entered as, which `RatingScale::parse_rating`
+/// reads back.
+fn rating_input(scale: RatingScale, value: u8) -> String {
+    match scale {
+        RatingScale::OneToFive | RatingScale::ZeroToThree => value.to_string(),
+        RatingScale::PassFail | RatingScale::Letter => scale.label(value),
+    }
+}
+
+fn text_input(fields: &BTreeMap<String, String>, name: &str, label: &str) -> String {
+    format!(
+        "<label>{}<input type=\"text\" name=\"{}\" value=\"{}\"></label>\n",
+        label,
+        name,
+        escape_html(field(fields, name))
+    )
+}
+
+fn text_area(fields: &BTreeMap<String, String>, name: &str, label: &str) -> String {
+    format!(
+        "<label>{}<textarea name=\"{}\">{}</textarea></label>\n",
+        label,
+        name,
+        escape_html(field(fields, name))
+    )
+}
+
+/// A drop-down of `options`, given as the value sent and the text shown,
+/// with the option matching the field selected.
+fn select(
+    fields: &BTreeMap<String, String>,
+    name: &str,
+    label: &str,
+    options: &[(String, String)],
+) -> String {
+    let current = field(fields, name);
+    let mut html = format!("<label>{}<select name=\"{}\">\n", label, name);
+    for (value, text) in options {
+        html.push_str(&format!(
+            "<option value=\"{}\"{}>{}</option>\n",
+            escape_html(value),
+            if value.eq_ignore_ascii_case(current) {
+                " selected"
+            } else {
+                ""
+            },
+            escape_html(text)
+        ));
+    }
+    html.push_str("</select></label>\n");
+    html
+}
+
+fn rating_select(fields: &BTreeMap<String, String>, name: &str, scale: RatingScale) -> String {
+    let (min, max) = scale.bounds();
+    let mut options = vec![(String::new(), "Not rated".to_string())];
+    options.extend((min..=max).map(|value| (rating_input(scale, value), scale.label(value))));
+    options.push(("n/a".to_string(), "Not applicable".to_string()));
+
+    select(
+        fields,
+        name,
+        &format!("Rating ({})", scale.as_str()),
+        &options,
+    )
+}
+
+/// An input for comma-separated tags, listing the vocabulary tags usable in
+/// `section` if there is a vocabulary.
+fn tags_input(
+    fields: &BTreeMap<String, String>,
+    name: &str,
+    vocabulary: &TagVocabulary,
+    section: Option<&str>,
+) -> String {
+    let mut html = text_input(fields, name, "Tags (comma-separated)");
+    if !vocabulary.is_empty() {
+        html.push_str(&format!(
+            "<p class=\"meta\">Vocabulary tags: {}</p>\n",
+            escape_html(&vocabulary.complete("", section).join(", "))
+        ));
+    }
+    html
+}
+
+/// Renders the rating form filled in with `fields`. One blank author row and
+/// one blank subsection per section follow those entered, so more can be
+/// added by saving the draft.
+fn render_form(
+    fields: &BTreeMap<String, String>,
+    draft: Option<&str>,
+    errors: &[String],
+    notice: Option<&str>,
+    scales: &RubricScales,
+    vocabulary: &TagVocabulary,
+) -> Html<String> {
+    let mut body = String::from("<h1>Rate a DMP</h1>\n");
+    if let Some(notice) = notice {
+        body.push_str(&format!(
+            "<p class=\"notice\">{}</p>\n",
+            escape_html(notice)
+        ));
+    }
+    if !errors.is_empty() {
+        body.push_str("<div class=\"errors\"><p>The rating was not submitted:</p>\n<ul>\n");
+        for error in errors {
+            body.push_str(&format!("<li>{}</li>\n", escape_html(error)));
+        }
+        body.push_str("</ul></div>\n");
+    }
+
+    body.push_str("<form method=\"post\" action=\"/ui/form\">\n");
+    if let Some(draft) = draft {
+        body.push_str(&format!(
+            "<input type=\"hidden\" name=\"draft\" value=\"{}\">\n",
+            escape_html(draft)
+        ));
+    }
+
+    body.push_str("<fieldset><legend>DMP</legend>\n");
+    body.push_str(&text_input(fields, "title", "Title"));
+    body.push_str(&text_input(
+        fields,
+        "identifier",
+        "Identifier (e.g., DOI or URL)",
+    ));
+    let id_types: Vec<(String, String)> = ID_TYPES
+        .iter()
+        .map(|t| (t.to_string(), t.to_string()))
+        .collect();
+    body.push_str(&select(fields, "id_type", "Identifier type", &id_types));
+    body.push_str("</fieldset>\n");
+
+    body.push_str("<fieldset><legend>Authors</legend>\n");
+    let mut authors = row_numbers(fields, "author");
+    authors.push(authors.last().map_or(0, |n| n + 1));
+    let author_id_types: Vec<(String, String)> = AUTHOR_ID_TYPES
+        .iter()
+        .map(|t| (t.to_string(), t.to_string()))
+        .collect();
+    for n in authors {
+        body.push_str(&format!("<fieldset><legend>Author {}</legend>\n", n + 1));
+        body.push_str(&text_input(fields, &format!("author{}_name", n), "Name"));
+        body.push_str(&text_input(
+            fields,
+            &format!("author{}_identifier", n),
+            "Identifier (optional)",
+        ));
+        body.push_str(&select(
+            fields,
+            &format!("author{}_id_type", n),
+            "Identifier type",
+            &author_id_types,
+        ));
+        body.push_str(&text_input(
+            fields,
+            &format!("author{}_affiliation", n),
+            "Affiliation (optional)",
+        ));
+        body.push_str(&text_input(
+            fields,
+            &format!("author{}_email", n),
+            "Email (optional)",
+        ));
+        body.push_str("</fieldset>\n");
+    }
+    body.push_str("</fieldset>\n");
+
+    body.push_str("<fieldset><legend>Project (optional)</legend>\n");
+    body.push_str(&text_input(fields, "project_title", "Project title"));
+    body.push_str(&text_input(fields, "grant_id", "Grant ID"));
+    body.push_str(&text_input(fields, "funder", "Funder"));
+    body.push_str(&text_input(fields, "institution", "Institution"));
+    body.push_str("</fieldset>\n");
+
+    for (i, title) in RUBRIC_SECTIONS.iter().enumerate() {
+        let scale = scales.scale_for(title);
+        body.push_str(&format!(
+            "<fieldset><legend>{}</legend>\n",
+            escape_html(title)
+        ));
+        body.push_str(&text_area(
+            fields,
+            &format!("s{}_text", i),
+            "Section text from the plan (optional)",
+        ));
+        body.push_str(&rating_select(fields, &format!("s{}_rating", i), scale));
+        body.push_str(&tags_input(
+            fields,
+            &format!("s{}_tags", i),
+            vocabulary,
+            Some(title),
+        ));
+        body.push_str(&text_area(fields, &format!("s{}_comments", i), "Comments"));
+
+        let prefix = format!("s{}_sub", i);
+        let mut subsections = row_numbers(fields, &prefix);
+        subsections.push(subsections.last().map_or(0, |n| n + 1));
+        for j in subsections {
+            let name = |part: &str| format!("{}{}_{}", prefix, j, part);
+            body.push_str(&format!(
+                "<fieldset><legend>Subsection {}.{} (optional)</legend>\n",
+                i + 1,
+                j + 1
+            ));
+            body.push_str(&text_input(fields, &name("title"), "Title"));
+            body.push_str(&text_area(fields, &name("text"), "Text (optional)"));
+            body.push_str(&rating_select(fields, &name("rating"), scale));
+            body.push_str(&tags_input(fields, &name("tags"), vocabulary, Some(title)));
+            body.push_str(&text_area(fields, &name("comments"), "Comments"));
+            body.push_str("</fieldset>\n");
+        }
+        body.push_str("</fieldset>\n");
+    }
+
+    body.push_str("<fieldset><legend>Overall rating</legend>\n");
+    body.push_str(&format!(
+        "<label>Score (1-5)<input type=\"number\" name=\"overall_score\" min=\"1\" max=\"5\" step=\"0.5\" value=\"{}\"></label>\n",
+        escape_html(field(fields, "overall_score"))
+    ));
+    body.push_str(&text_input(fields, "reviewer", "Reviewer name"));
+    body.push_str(&text_area(fields, "overall_comments", "Comments"));
+    body.push_str(&tags_input(fields, "overall_tags", vocabulary, None));
+    body.push_str("</fieldset>\n");
+
+    body.push_str(
+        "<p><button type=\"submit\" name=\"action\" value=\"save\">Save draft</button>\n",
+    );
+    body.push_str(
+        "<button type=\"submit\" name=\"action\" value=\"submit\">Submit rating</button></p>\n",
+    );
+    body.push_str("</form>\n");
+
+    page("Rate a DMP", &body)
+}
+
+/// Reads a rating entered on `scale`, or `None` if it was left empty.
+fn parse_rating_field(scale: RatingScale, input: &str) -> Result<Option<Rating>, String> {
+    if input.is_empty() {
+        return Ok(None);
+    }
+    scale.parse_rating(input).map(Some)
+}
+
+/// Resolves comma-separated tags against the vocabulary.
+fn parse_tags_field(
+    input: &str,
+    vocabulary: &TagVocabulary,
+    section: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let mut tags: Vec<String> = Vec::new();
+    for entry in input.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let tag = vocabulary.resolve(entry, section)?;
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    Ok(tags)
+}
+
+/// Builds the DMP a submitted form describes, as `add_dmp` in the
+/// interactive menu does from its prompts. Fails with every problem found.
+fn build_dmp(
+    fields: &BTreeMap<String, String>,
+    scales: &RubricScales,
+    vocabulary: &TagVocabulary,
+) -> Result<DataManagementPlan, Vec<String>> {
+    let mut errors = Vec::new();
+
+    let title = field(fields, "title");
+    if title.is_empty() {
+        errors.push("A title is required".to_string());
+    }
+
+    let dmp_id = if field(fields, "identifier").is_empty() {
+        errors.push("A DMP identifier is required".to_string());
+        None
+    } else {
+        let id_type = IdentifierType::from(field(fields, "id_type").to_string());
+        match normalize_identifier(field(fields, "identifier"), &id_type) {
+            Ok((id_type, identifier)) => Some(Identifier {
+                identifier,
+                id_type,
+            }),
+            Err(e) => {
+                errors.push(format!("DMP identifier: {}", e));
+                None
+            }
+        }
+    };
+
+    let mut authors = Vec::new();
+    for n in row_numbers(fields, "author") {
+        let name = field(fields, &format!("author{}_name", n));
+        if name.is_empty() {
+            continue;
+        }
+        let id_type = match field(fields, &format!("author{}_id_type", n)) {
+            "" => AuthorIdType::Other,
+            id_type => match id_type.parse::<AuthorIdType>() {
+                Ok(id_type) => id_type,
+                Err(e) => {
+                    errors.push(format!("Author {}: {}", name, e));
+                    continue;
+                }
+            },
+        };
+        let identifier = match field(fields, &format!("author{}_identifier", n)) {
+            "" => None,
+            identifier => match normalize_author_identifier(identifier, Some(&id_type)) {
+                Ok(identifier) => Some(identifier),
+                Err(e) => {
+                    errors.push(format!("Author {}: {}", name, e));
+                    continue;
+                }
+            },
+        };
+        authors.push(Author {
+            name: name.to_string(),
+            identifier,
+            id_type: Some(id_type),
+            affiliation: optional_field(fields, &format!("author{}_affiliation", n)),
+            email: optional_field(fields, &format!("author{}_email", n)),
+        });
+    }
+
+    let project_fields = ["project_title", "grant_id", "funder", "institution"];
+    let project_info = if project_fields.iter().any(|f| !field(fields, f).is_empty()) {
+        Some(ProjectInfo {
+            project_title: field(fields, "project_title").to_string(),
+            grant_id: optional_field(fields, "grant_id"),
+            funder: optional_field(fields, "funder"),
+            institution: optional_field(fields, "institution"),
+            start_date: None,
+            end_date: None,
+        })
+    } else {
+        None
+    };
+
+    let mut sections = Vec::new();
+    for (i, title) in RUBRIC_SECTIONS.iter().enumerate() {
+        let scale = scales.scale_for(title);
+        let rating = parse_rating_field(scale, field(fields, &format!("s{}_rating", i)))
+            .unwrap_or_else(|e| {
+                errors.push(format!("{}: {}", title, e));
+                None
+            });
+        let tags = parse_tags_field(
+            field(fields, &format!("s{}_tags", i)),
+            vocabulary,
+            Some(title),
+        )
+        .unwrap_or_else(|e| {
+            errors.push(format!("{}: {}", title, e));
+            Vec::new()
+        });
+
+        let prefix = format!("s{}_sub", i);
+        let mut subsections = Vec::new();
+        for j in row_numbers(fields, &prefix) {
+            let name = |part: &str| format!("{}{}_{}", prefix, j, part);
+            let subsection_title = field(fields, &name("title"));
+            if subsection_title.is_empty() {
+                continue;
+            }
+            let context = format!("{} / {}", title, subsection_title);
+            let rating =
+                parse_rating_field(scale, field(fields, &name("rating"))).unwrap_or_else(|e| {
+                    errors.push(format!("{}: {}", context, e));
+                    None
+                });
+            let tags = parse_tags_field(field(fields, &name("tags")), vocabulary, Some(title))
+                .unwrap_or_else(|e| {
+                    errors.push(format!("{}: {}", context, e));
+                    Vec::new()
+                });
+            subsections.push(Subsection {
+                title: subsection_title.to_string(),
+                text: optional_field(fields, &name("text")),
+                rating,
+                tags,
+                comments: optional_field(fields, &name("comments")),
+            });
+        }
+
+        sections.push(Section {
+            title: title.to_string(),
+            text: optional_field(fields, &format!("s{}_text", i)),
+            rating,
+            tags,
+            comments: optional_field(fields, &format!("s{}_comments", i)),
+            subsections,
+            cross_references: None,
+            annotations: Vec::new(),
+        });
+    }
+
+    let score = match field(fields, "overall_score").parse::<f32>() {
+        Ok(score) if (1.0..=5.0).contains(&score) => score,
+        _ => {
+            errors.push("An overall score between 1 and 5 is required".to_string());
+            0.0
+        }
+    };
+    let reviewer = field(fields, "reviewer");
+    if reviewer.is_empty() {
+        errors.push("A reviewer name is required".to_string());
+    }
+    let overall_tags = parse_tags_field(field(fields, "overall_tags"), vocabulary, None)
+        .unwrap_or_else(|e| {
+            errors.push(format!("Overall tags: {}", e));
+            Vec::new()
+        });
+
+    let dmp_id = match dmp_id {
+        Some(dmp_id) if errors.is_empty() => dmp_id,
+        _ => return Err(errors),
+    };
+
+    Ok(DataManagementPlan {
+        id: None,
+        title: title.to_string(),
+        dmp_id,
+        authors,
+        project_info,
+        created_date: DateTime::now(),
+        last_modified: DateTime::now(),
+        version: "1.0".to_string(),
+        overall_rating: Some(OverallRating {
+            score,
+            reviewer: reviewer.to_string(),
+            review_date: DateTime::now(),
+            comments: field(fields, "overall_comments").to_string(),
+            overall_tags,
+        }),
+        sections,
+        machine_actionable: None,
+        history: None,
+        metrics: None,
+        related_dmps: None,
+        related_datasets: None,
+        related_publications: None,
+        related_software: None,
+        schema_version: SCHEMA_VERSION,
+    })
+}
+
+/// Lists the rating drafts and the DMPs matching a search expression.
+async fn index(
+    State(state): State<WebState>,
+    Query(params): Query<IndexParams>,
+) -> WebResult<Html<String>> {
+    let expression = params.q.unwrap_or_default();
+    let mut body = String::from("<h1>DMP ratings</h1>\n");
+
+    let drafts = state.db_manager.list_rating_drafts().await?;
+    if !drafts.is_empty() {
+        body.push_str("<h2>Drafts</h2>\n<table>\n<tr><th>Title</th><th>Reviewer</th><th>Saved</th><th></th></tr>\n");
+        for draft in &drafts {
+            let id = draft.id.map(|id| id.to_hex()).unwrap_or_default();
+            body.push_str(&format!(
+                "<tr><td><a href=\"/ui/form?draft={}\">{}</a></td><td>{}</td><td>{}</td><td><form class=\"inline\" method=\"post\" action=\"/ui/drafts/{}/delete\"><button type=\"submit\">Delete</button></form></td></tr>\n",
+                id,
+                escape_html(Some(field(&draft.fields, "title")).filter(|t| !t.is_empty()).unwrap_or("(untitled)")),
+                escape_html(field(&draft.fields, "reviewer")),
+                show_time(draft.saved_at),
+                id
+            ));
+        }
+        body.push_str("</table>\n");
+    }
+
+    body.push_str(&format!(
+        "<h2>DMPs</h2>\n<form method=\"get\" action=\"/ui\"><label>Search expression, e.g. funder:NSF rating&gt;=3<input type=\"text\" name=\"q\" value=\"{}\"></label><button type=\"submit\">Search</button></form>\n",
+        escape_html(&expression)
+    ));
+
+    let query = if expression.trim().is_empty() {
+        Ok(DmpQuery::new())
+    } else {
+        parse_query(&expression, &state.scales)
+    };
+    match query {
+        Ok(query) => {
+            let dmps = state.db_manager.find_dmps(&query).await?;
+            body.push_str(&format!("<p class=\"meta\">{} DMP(s)</p>\n", dmps.len()));
+            body.push_str("<table>\n<tr><th>Title</th><th>ID</th><th>Funder</th><th>Overall score</th><th>Reviewer</th><th>Last modified</th></tr>\n");
+            for dmp in &dmps {
+                let (score, reviewer) = match dmp.overall_rating {
+                    Some(ref rating) => (rating.score.to_string(), rating.reviewer.as_str()),
+                    None => ("-".to_string(), ""),
+                };
+                body.push_str(&format!(
+                    "<tr><td><a href=\"{}\">{}</a></td><td>{}/{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    dmp_link(&dmp.dmp_id),
+                    escape_html(&dmp.title),
+                    escape_html(dmp.dmp_id.id_type.as_str()),
+                    escape_html(&dmp.dmp_id.identifier),
+                    escape_html(
+                        dmp.project_info
+                            .as_ref()
+                            .and_then(|p| p.funder.as_deref())
+                            .unwrap_or("")
+                    ),
+                    score,
+                    escape_html(reviewer),
+                    show_time(dmp.last_modified)
+                ));
+            }
+            body.push_str("</table>\n");
+        }
+        Err(e) => body.push_str(&format!(
+            "<p class=\"errors\">Invalid search expression: {}</p>\n",
+            escape_html(&e.to_string())
+        )),
+    }
+
+    Ok(page("DMP ratings", &body))
+}
+
+/// Shows an empty rating form, or the draft given.
+async fn show_form(
+    State(state): State<WebState>,
+    Query(params): Query<FormParams>,
+) -> WebResult<Html<String>> {
+    let vocabulary = state.db_manager.load_tag_vocabulary().await?;
+    let fields = match params.draft {
+        Some(ref id) => {
+            state
+                .db_manager
+                .find_rating_draft(id)
+                .await?
+                .ok_or_else(|| WebError {
+                    status: StatusCode::NOT_FOUND,
+                    message: format!("Draft {} not found", id),
+                })?
+                .fields
+        }
+        None => BTreeMap::new(),
+    };
+    let notice = params
+        .saved
+        .is_some()
+        .then_some("Draft saved. It is listed on the DMPs page until it is submitted.");
+
+    Ok(render_form(
+        &fields,
+        params.draft.as_deref(),
+        &[],
+        notice,
+        &state.scales,
+        &vocabulary,
+    ))
+}
+
+/// Saves the form as a draft, or submits it as a new DMP rating.
+async fn submit_form(
+    State(state): State<WebState>,
+    Form(mut fields): Form<BTreeMap<String, String>>,
+) -> WebResult<Response> {
+    let action = fields.remove("action").unwrap_or_default();
+    let draft = fields.remove("draft").filter(|d| !d.trim().is_empty());
+    fields.retain(|_, value| !value.trim().is_empty());
+
+    if action == "save" {
+        let id = match draft {
+            Some(ref id) => Some(ObjectId::parse_str(id.trim()).map_err(|_| WebError {
+                status: StatusCode::BAD_REQUEST,
+                message: format!("'{}' is not a valid draft ID", id),
+            })?),
+            None => None,
+        };
+        let id = state
+            .db_manager
+            .save_rating_draft(RatingDraft {
+                id,
+                fields,
+                saved_at: DateTime::now(),
+            })
+            .await?;
+        return Ok(Redirect::to(&format!("/ui/form?draft={}&saved=1", id)).into_response());
+    }
+
+    let vocabulary = state.db_manager.load_tag_vocabulary().await?;
+    let errors = match build_dmp(&fields, &state.scales, &vocabulary) {
+        Ok(dmp) => {
+            let link = dmp_link(&dmp.dmp_id);
+            // Convert the error at once: it cannot be held across an await
+            let added = state.db_manager.add_dmp(dmp).await.map_err(WebError::from);
+            match added {
+                Ok(_) => {
+                    if let Some(ref draft) = draft {
+                        state.db_manager.delete_rating_draft(draft).await?;
+                    }
+                    return Ok(Redirect::to(&link).into_response());
+                }
+                Err(e) if e.status == StatusCode::BAD_REQUEST => vec![e.message],
+                Err(e) => return Err(e),
+            }
+        }
+        Err(errors) => errors,
+    };
+
+    Ok((
+        StatusCode::UNPROCESSABLE_ENTITY,
+        render_form(
+            &fields,
+            draft.as_deref(),
+            &errors,
+            None,
+            &state.scales,
+            &vocabulary,
+        ),
+    )
+        .into_response())
+}
+
+async fn delete_draft(
+    State(state): State<WebState>,
+    Path(id): Path<String>,
+) -> WebResult<Redirect> {
+    state.db_manager.delete_rating_draft(&id).await?;
+    Ok(Redirect::to("/ui"))
+}
+
+/// Shows the report of a stored DMP.
+async fn show_dmp(
+    State(state): State<WebState>,
+    Path((id_type, dmp_id)): Path<(String, String)>,
+) -> WebResult<Html<String>> {
+    let dmp = state
+        .db_manager
+        .find_dmp_by_id(&dmp_id, &id_type)
+        .await?
+        .ok_or_else(|| WebError {
+            status: StatusCode::NOT_FOUND,
+            message: format!("DMP {}/{} not found", id_type, dmp_id),
+        })?;
+
+    // The report is a page of its own; add the way back to the list
+    Ok(Html(render_dmp_report(&dmp).replacen(
+        "<body>\n",
+        "<body>\n<nav><a href=\"/ui\">DMPs and drafts</a> &middot; <a href=\"/ui/form\">Rate a DMP</a></nav>\n",
+        1,
+    )))
+}
+
+/// Rejects form posts sent from pages of other sites: browsers name the
+/// page's origin in `Origin`, or failing that `Referer`, and it must be this
+/// server as named in `Host`. Posts naming neither come from other clients
+/// than browsers and are let through.
+async fn same_origin(request: Request, next: Next) -> Response {
+    if request.method() == Method::POST {
+        let header = |name: header::HeaderName| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        let source = header(header::ORIGIN).or_else(|| header(header::REFERER));
+        if let Some(source) = source {
+            let authority = source
+                .split_once("://")
+                .map(|(_, rest)| rest.split('/').next().unwrap_or(rest));
+            let host = header(header::HOST);
+            if authority.is_none() || authority != host {
+                return WebError {
+                    status: StatusCode::FORBIDDEN,
+                    message: "Forms can only be sent from the pages of this server".to_string(),
+                }
+                .into_response();
+            }
+        }
+    }
+
+    next.run(request).await
+}
+
+/// The pages of the rating form and DMP list, under `/ui`.
+pub fn router(db_manager: Arc<DatabaseManager>, scales: Arc<RubricScales>) -> Router {
+    Router::new()
+        .route("/", get(|| async { Redirect::to("/ui") }))
+        .route("/ui", get(index))
+        .route("/ui/form", get(show_form).post(submit_form))
+        .route("/ui/drafts/:id/delete", post(delete_draft))
+        .route("/ui/dmps/:id_type/*id", get(show_dmp))
+        .route_layer(middleware::from_fn(same_origin))
+        .with_state(WebState { db_manager, scales })
+}